@@ -1,96 +1,152 @@
 use std::error::Error;
 use std::fmt;
 
+// Numeric replies as defined in RFC 1459 section 6. The text holds one `{}`
+// placeholder per parameter, they are filled in by `server::reply::Reply`.
 #[allow(dead_code)]
-pub const RPL_SUCCESS: (i32, &str) = (201, ": Success!");
+pub const RPL_WELCOME: (i32, &str) = (1, ":Welcome to the Internet Relay Network {}");
 #[allow(dead_code)]
-pub const RPL_UNAWAY: (i32, &str) = (305, ": You are no longer marked as being away");
+pub const RPL_YOURHOST: (i32, &str) = (2, ":Your host is {}, running version {}");
 #[allow(dead_code)]
-pub const RPL_NOWAWAY: (i32, &str) = (306, ": You have been marked as being away");
+pub const RPL_CREATED: (i32, &str) = (3, ":This server was created {}");
 #[allow(dead_code)]
-pub const RPL_TOPIC: (i32, &str) = (332, "{} : {}");
+pub const RPL_MYINFO: (i32, &str) = (4, "{} {} {} {}");
 #[allow(dead_code)]
-pub const RPL_INVITING: (i32, &str) = (341, "INVITED {} {}");
+pub const RPL_ISUPPORT: (i32, &str) = (5, "{} :are supported by this server");
+#[allow(dead_code)]
+pub const RPL_MAP: (i32, &str) = (15, ":{}");
+#[allow(dead_code)]
+pub const RPL_MAPEND: (i32, &str) = (17, ":End of /MAP");
+#[allow(dead_code)]
+pub const RPL_UMODEIS: (i32, &str) = (221, "{}");
+#[allow(dead_code)]
+pub const RPL_ADMINME: (i32, &str) = (256, "{} :Administrative info");
+#[allow(dead_code)]
+pub const RPL_ADMINLOC1: (i32, &str) = (257, ":{}");
+#[allow(dead_code)]
+pub const RPL_ADMINLOC2: (i32, &str) = (258, ":{}");
+#[allow(dead_code)]
+pub const RPL_ADMINEMAIL: (i32, &str) = (259, ":{}");
+#[allow(dead_code)]
+pub const RPL_NONE: (i32, &str) = (300, "");
+#[allow(dead_code)]
+pub const RPL_AWAY: (i32, &str) = (301, "{} :{}");
+#[allow(dead_code)]
+pub const RPL_UNAWAY: (i32, &str) = (305, ":You are no longer marked as being away");
+#[allow(dead_code)]
+pub const RPL_NOWAWAY: (i32, &str) = (306, ":You have been marked as being away");
+#[allow(dead_code)]
+pub const RPL_WHOISUSER: (i32, &str) = (311, "{} {} {} * :{}");
+#[allow(dead_code)]
+pub const RPL_WHOISSERVER: (i32, &str) = (312, "{} {} :{}");
+#[allow(dead_code)]
+pub const RPL_WHOISOPERATOR: (i32, &str) = (313, "{} :is an IRC operator");
+#[allow(dead_code)]
+pub const RPL_ENDOFWHOIS: (i32, &str) = (318, "{} :End of /WHOIS list");
+#[allow(dead_code)]
+pub const RPL_CHANNELMODEIS: (i32, &str) = (324, "{} {}");
+#[allow(dead_code)]
+pub const RPL_NOTOPIC: (i32, &str) = (331, "{} :No topic is set");
+#[allow(dead_code)]
+pub const RPL_TOPIC: (i32, &str) = (332, "{} :{}");
+#[allow(dead_code)]
+pub const RPL_TOPICWHOTIME: (i32, &str) = (333, "{} {} {}");
+#[allow(dead_code)]
+pub const RPL_INVITING: (i32, &str) = (341, "{} {}");
+#[allow(dead_code)]
+pub const RPL_EXCEPTLIST: (i32, &str) = (348, "{} {}");
+#[allow(dead_code)]
+pub const RPL_ENDOFEXCEPTLIST: (i32, &str) = (349, "{} :End of channel exception list");
+#[allow(dead_code)]
+pub const RPL_VERSION: (i32, &str) = (351, "{} {} :{}");
+#[allow(dead_code)]
+pub const RPL_LINKS: (i32, &str) = (364, "{} {} :{} {}");
+#[allow(dead_code)]
+pub const RPL_ENDOFLINKS: (i32, &str) = (365, "{} :End of /LINKS list");
+#[allow(dead_code)]
+pub const RPL_BANLIST: (i32, &str) = (367, "{} {}");
+#[allow(dead_code)]
+pub const RPL_ENDOFBANLIST: (i32, &str) = (368, "{} :End of channel ban list");
+#[allow(dead_code)]
+pub const RPL_INFO: (i32, &str) = (371, ":{}");
+#[allow(dead_code)]
+pub const RPL_MOTD: (i32, &str) = (372, ":- {}");
+#[allow(dead_code)]
+pub const RPL_ENDOFINFO: (i32, &str) = (374, ":End of /INFO list");
+#[allow(dead_code)]
+pub const RPL_MOTDSTART: (i32, &str) = (375, ":- {} Message of the day - ");
+#[allow(dead_code)]
+pub const RPL_ENDOFMOTD: (i32, &str) = (376, ":End of /MOTD command");
 #[allow(dead_code)]
 pub const RPL_YOUREOPER: (i32, &str) = (381, ":You are now an IRC operator");
 #[allow(dead_code)]
-pub const ERR_NOSUCHNICK: (i32, &str) = (401, "{}:No such nick/channel");
+pub const RPL_TIME: (i32, &str) = (391, "{} :{}");
+#[allow(dead_code)]
+pub const ERR_NOSUCHNICK: (i32, &str) = (401, "{} :No such nick/channel");
+#[allow(dead_code)]
+pub const ERR_NOSUCHSERVER: (i32, &str) = (402, "{} :No such server");
 #[allow(dead_code)]
 pub const ERR_NOSUCHCHANNEL: (i32, &str) = (403, "{} :No such channel");
 #[allow(dead_code)]
+pub const ERR_CANNOTSENDTOCHAN: (i32, &str) = (404, "{} :Cannot send to channel");
+#[allow(dead_code)]
+pub const ERR_NOORIGIN: (i32, &str) = (409, ":No origin specified");
+#[allow(dead_code)]
 pub const ERR_UNKNOWNCOMMAND: (i32, &str) = (421, "{} :Unknown command");
 #[allow(dead_code)]
-pub const RPL_YOUAREIN: (i32, &str) = (200, ":Succesfully Connected ");
+pub const ERR_NOMOTD: (i32, &str) = (422, ":MOTD File is missing");
 #[allow(dead_code)]
-pub const ERR_NONICKNAMEGIVEN: (i32, &str) = (431, ":No nickname given");
+pub const ERR_NOADMININFO: (i32, &str) = (423, "{} :No administrative info available");
 #[allow(dead_code)]
-pub const ERR_NICKCOLLISION: (i32, &str) = (436, "{} :Nickname collision KILL");
+pub const ERR_FILEERROR: (i32, &str) = (424, ":File error doing {} on {}");
 #[allow(dead_code)]
-pub const ERR_UNKNOWNMODE: (i32, &str) = (472, "{} :is unknown mode char to me");
+pub const ERR_NONICKNAMEGIVEN: (i32, &str) = (431, ":No nickname given");
 #[allow(dead_code)]
-pub const ERR_INVITEONLYCHAN: (i32, &str) = (473, "{} :Cannot join channel (+i)");
+pub const ERR_ERRONEUSNICKNAME: (i32, &str) = (432, "{} :Erroneus nickname");
 #[allow(dead_code)]
-pub const ERR_SERVERCOLLISION: (i32, &str) = (499, "{} :Servername collision KILL");
+pub const ERR_NICKNAMEINUSE: (i32, &str) = (433, "{} :Nickname is already in use");
+#[allow(dead_code)]
+pub const ERR_NICKCOLLISION: (i32, &str) = (436, "{} :Nickname collision KILL");
 #[allow(dead_code)]
 pub const ERR_NOTONCHANNEL: (i32, &str) = (442, "{} :You're not on that channel");
 #[allow(dead_code)]
-pub const ERR_USERONCHANNEL: (i32, &str) = (443, "{} {}:is already on channel");
+pub const ERR_USERONCHANNEL: (i32, &str) = (443, "{} {} :is already on channel");
 #[allow(dead_code)]
-pub const ERR_NOLOGIN: (i32, &str) = (444, " :User not logged in");
+pub const ERR_NOTREGISTERED: (i32, &str) = (451, ":You have not registered");
 #[allow(dead_code)]
 pub const ERR_NEEDMOREPARAMS: (i32, &str) = (461, "{} :Not enough parameters");
 #[allow(dead_code)]
 pub const ERR_ALREADYREGISTRED: (i32, &str) = (462, ":You may not reregister");
 #[allow(dead_code)]
+pub const ERR_NOPERMFORHOST: (i32, &str) = (463, ":Your host isn't among the privileged");
+#[allow(dead_code)]
 pub const ERR_PASSWDMISMATCH: (i32, &str) = (464, ":Password incorrect");
 #[allow(dead_code)]
+pub const ERR_YOUREBANNEDCREEP: (i32, &str) = (465, ":You are banned from this server");
+#[allow(dead_code)]
+pub const ERR_KEYSET: (i32, &str) = (467, "{} :Channel key already set");
+#[allow(dead_code)]
 pub const ERR_CHANNELISFULL: (i32, &str) = (471, "{} :Cannot join channel (+l)");
 #[allow(dead_code)]
+pub const ERR_UNKNOWNMODE: (i32, &str) = (472, "{} :is unknown mode char to me");
+#[allow(dead_code)]
+pub const ERR_INVITEONLYCHAN: (i32, &str) = (473, "{} :Cannot join channel (+i)");
+#[allow(dead_code)]
+pub const ERR_BANNEDFROMCHAN: (i32, &str) = (474, "{} :Cannot join channel (+b)");
+#[allow(dead_code)]
+pub const ERR_BADCHANNELKEY: (i32, &str) = (475, "{} :Cannot join channel (+k)");
+#[allow(dead_code)]
 pub const ERR_NOPRIVILEGES: (i32, &str) = (481, ":Permission Denied- You're not an IRC operator");
 #[allow(dead_code)]
-pub const ERR_UNEXPECTED: (i32, &str) = (100, "Unexpected error."); //para los que no estan implementados aun
+pub const ERR_CHANOPRIVSNEEDED: (i32, &str) = (482, "{} :You're not channel operator");
 #[allow(dead_code)]
-pub const ERR_NOCHANPRIVILEGES: (i32, &str) =
-    (498, ":Permission Denied- You're not a channel operator");
+pub const ERR_CANTKILLSERVER: (i32, &str) = (483, ":You cant kill a server!");
 #[allow(dead_code)]
-pub const ERR_UNTRUSTEDSERVER: (i32, &str) = (499, "{} :Untrusted server.");
+pub const ERR_NOOPERHOST: (i32, &str) = (491, ":No O-lines for your host");
 #[allow(dead_code)]
-pub const ERR_NOSUCHSERVER: (i32, &str) = (403, "{} :No such server");
-
-//ir agregando a medida que se necesitan..
-/* const ERR_NOSUCHNICK: i32 = 401;
-pub const ERR_NOSUCHSERVER: i32 = 402;
-pub const ERR_CANNOTSENDTOCHAN: i32 = 404;
-pub const ERR_TOOMANYCHANNELS: i32 = 405;
-pub const ERR_WASNOSUCHNICK: i32 = 406;
-pub const ERR_TOOMANYTARGETS: i32 = 407;
-pub const ERR_NOORIGIN: i32 = 409;
-pub const ERR_NORECIPIENT: i32 = 411;
-pub const ERR_NOTEXTTOSEND: i32 = 412;
-pub const ERR_NOTOPLEVEL: i32 = 413;
-pub const ERR_WILDTOPLEVEL: i32 = 414;
-pub const ERR_NOMOTD: i32 = 422; */
-
-/*
-pub struct Reply {
-    code: (i32, &'static str),
-    params: Vec<String>,
-}
-
-impl Reply {
-    pub fn build(code: (i32, &'static str), params: Vec<String>) -> Reply {
-        Reply { code, params }
-    }
-
-    pub fn to_string(self) -> String {
-        let mut res = self.code.1.clone();
-        for param in self.params {
-            res.replacen("{}", param.as_str(), 1);
-        }
-        return res.to_string();
-    }
-}
-*/
+pub const ERR_UMODEUNKNOWNFLAG: (i32, &str) = (501, ":Unknown MODE flag");
+#[allow(dead_code)]
+pub const ERR_USERSDONTMATCH: (i32, &str) = (502, ":Cant change mode for other users");
 
 #[derive(Debug)]
 pub struct ApplicationError(pub String);
@@ -2,6 +2,7 @@
 #![allow(unused_variables)]
 
 use crate::app_errors;
+use irc_2c_2022::server::tls::{self, Stream};
 use std::collections::HashSet;
 use std::error::Error;
 use std::fs::{create_dir_all, File, OpenOptions};
@@ -67,6 +68,8 @@ pub enum Received {
 pub struct ClientBuilder {
     ip: Option<String>,
     port: Option<String>,
+    tls: bool,
+    tls_ca_path: Option<String>,
 }
 
 impl ClientBuilder {
@@ -76,10 +79,18 @@ impl ClientBuilder {
     pub fn set_port(&mut self, port: String) {
         self.port = Some(port);
     }
+    pub fn set_tls(&mut self, tls: bool) {
+        self.tls = tls;
+    }
+    pub fn set_tls_ca_path(&mut self, tls_ca_path: String) {
+        self.tls_ca_path = Some(tls_ca_path);
+    }
     pub fn new() -> ClientBuilder {
         ClientBuilder {
             ip: None,
             port: None,
+            tls: false,
+            tls_ca_path: None,
         }
     }
     pub fn get_client(&self) -> Result<ClientC, String> {
@@ -87,8 +98,9 @@ impl ClientBuilder {
             (None, _) => Err("Ip is missing".to_string()),
             (_, None) => Err("Port is missing".to_string()),
             (Some(ip), Some(port)) => {
-                return match ClientC::new(ip, port) {
+                return match ClientC::new(ip, port, self.tls, self.tls_ca_path.clone()) {
                     Ok(client) => Ok(client),
+                    Err(_) if self.tls => Err("Ip, port or certificate incorrect!".to_string()),
                     Err(_) => Err("Ip or port incorrect!".to_string()),
                 };
             }
@@ -99,7 +111,7 @@ impl ClientBuilder {
 /// ClientC is the client-side that conects to a server
 pub struct ClientC {
     //logger_file_path: String,
-    server: TcpStream,
+    server: Stream,
     channels: HashSet<String>,
     nick: Option<String>,
     dcc_chat: Option<TcpStream>,
@@ -107,8 +119,13 @@ pub struct ClientC {
 
 impl ClientC {
     // #![allow(dead_code)]
-    pub fn new(address: String, port: String) -> Result<ClientC, Box<dyn Error>> {
-        let server = TcpStream::connect(address + ":" + &port)?;
+    pub fn new(
+        address: String,
+        port: String,
+        tls: bool,
+        tls_ca_path: Option<String>,
+    ) -> Result<ClientC, Box<dyn Error>> {
+        let server = Self::connect(&address, &port, tls, tls_ca_path.as_deref())?;
         server.set_read_timeout(Some(Duration::from_millis(100)))?;
         let channels = HashSet::new();
         
@@ -122,6 +139,22 @@ impl ClientC {
         })
     }
 
+    /// Given the address and port of the server connects to it, over TLS if asked to.
+    /// Without a file of trusted certificates the ones of the well known authorities are used
+    fn connect(
+        address: &str,
+        port: &str,
+        tls: bool,
+        tls_ca_path: Option<&str>,
+    ) -> Result<Stream, Box<dyn Error>> {
+        if !tls {
+            let server = TcpStream::connect(format!("{}:{}", address, port))?;
+            return Ok(Stream::from(server));
+        }
+        let config = tls::client_config(tls_ca_path)?;
+        Ok(Stream::connect(address, port, config)?)
+    }
+
     /// registers a new user to the server, sending the PASS, NICK and USER commands
     pub fn register(&mut self, pass: String, nick: String, user: String) -> String {
         self.server
@@ -567,6 +600,34 @@ impl ClientC {
             .expect("server write failed when writing privmsg");
     }
 
+    /// gets the tree of servers of the network from MAP, one line per server.
+    /// Returns the text of the error if the server refuses it (i.e. the user isn't an operator)
+    pub fn get_map(&mut self) -> Result<Vec<String>, String> {
+        self.server
+            .write_all("MAP\n".as_bytes())
+            .map_err(|err| err.to_string())?;
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_from_stream().map_err(|err| err.to_string())?;
+            let numeric = match Self::reply_numeric(&line) {
+                Some(numeric) => numeric,
+                None => continue, //otros mensajes que llegan mientras tanto
+            };
+            let line = Self::strip_reply_prefix(line);
+            // se conservan los espacios del principio, que marcan la profundidad en el arbol
+            let text = match line.split_once(':') {
+                Some((_, text)) => text.trim_end().to_string(),
+                None => String::new(),
+            };
+            match numeric.as_str() {
+                "015" => lines.push(text),
+                "017" => return Ok(lines),
+                _ if numeric.starts_with('4') => return Err(text),
+                _ => {}
+            }
+        }
+    }
+
     pub fn send_quit(&mut self) {
         self.server
             .write_all("QUIT\n".as_bytes())
@@ -634,9 +695,43 @@ impl ClientC {
         self.channels.contains(&channel)
     }
 
+    /// given a line sent by the server returns the numeric of the reply (if it is one)
+    /// the line can be in legacy ("001 :text") or RFC (":server 001 nick :text") format
+    pub fn reply_numeric(line: &str) -> Option<String> {
+        let mut words = line.trim().split(' ');
+        let mut numeric = words.next()?;
+        if numeric.starts_with(':') {
+            numeric = words.next()?;
+        }
+        if numeric.len() == 3 && numeric.chars().all(|c| c.is_ascii_digit()) {
+            return Some(numeric.to_string());
+        }
+        None
+    }
+
+    /// given a reply in RFC format (":server 001 nick :text") returns it in legacy format ("001 :text")
+    fn strip_reply_prefix(message: String) -> String {
+        if !message.starts_with(':') {
+            return message;
+        }
+        let words: Vec<&str> = message.splitn(4, ' ').collect();
+        match words.as_slice() {
+            [_prefix, numeric, _target, text] => format!("{} {}", numeric, text),
+            _ => message,
+        }
+    }
+
     /// read_message reads a message from the server and returns a Received enum
     pub fn read_message(&mut self) -> Received {
         if let Ok(mut message) = self.try_read_from_stream() {
+            if let Some(origin) = message.strip_prefix("PING") {
+                //keepalive del servidor, se responde sin avisarle a la interfaz
+                let _ = Self::write_to(&mut self.server, format!("PONG{}", origin.trim_end()));
+                return Received::Unknown(String::new());
+            }
+            if Self::reply_numeric(&message).is_some() {
+                message = Self::strip_reply_prefix(message);
+            }
             if message.starts_with(':') {
                 //if the message starts with a colon, it is a command
                 message.remove(0);
@@ -658,7 +753,10 @@ impl ClientC {
                 if let Some((code, message)) = message.split_once(':') {
                     return Received::IrcErr(code.trim().to_string(), message.trim().to_string());
                 }
-            } else if message.starts_with('3') || message.starts_with('2') {
+            } else if message.starts_with('3')
+                || message.starts_with('2')
+                || message.starts_with('0')
+            {
                 //if the message starts with a 3 or 2, it is a reply
                 if let Some((code, message)) = message.split_once(':') {
                     return Received::IrcRpl(code.trim().to_string(), message.trim().to_string());
@@ -671,7 +769,12 @@ impl ClientC {
     /// Funcionalidad de la entrega anterior (CLI). No tiene todas las funcionalidades de la entrega final implementadas
     pub fn build(config: super::config_client::ConfigClient) -> Result<ClientC, Box<dyn Error>> {
         //let logger_file_path = config.log_path;
-        let server = TcpStream::connect(config.address + ":" + &config.port)?;
+        let server = Self::connect(
+            &config.address,
+            &config.port,
+            config.tls,
+            config.tls_ca_path.as_deref(),
+        )?;
 
         let channels = HashSet::new();
         Ok(ClientC {
@@ -729,12 +832,13 @@ impl ClientC {
     /// sender is the thread that sends messages to the server
     /// and listener is the thread that receives messages from the server
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        //try_clone() devuelve un nuevo TcpStream que referencia al mismo socket que el TcpStream original.
+        //try_clone() devuelve un nuevo Stream que referencia al mismo socket que el TcpStream original.
         let mut sender = self.server.try_clone()?;
         let sender = thread::spawn(move || ClientC::sender(&mut sender));
 
         let mut listener = self.server.try_clone()?;
-        let listener = thread::spawn(move || ClientC::listener(&mut listener));
+        let mut ponger = self.server.try_clone()?;
+        let listener = thread::spawn(move || ClientC::listener(&mut listener, &mut ponger));
 
         // join waits for the thread to finish
         if sender.join().is_err() {
@@ -770,10 +874,15 @@ impl ClientC {
         Ok(())
     }
 
-    /// reads from the server (from TcpStream) and prints the message to stdout
-    fn listener(listener: &mut dyn Read) -> std::io::Result<()> {
+    /// reads from the server (from TcpStream) and prints the message to stdout, answering the server PINGs
+    fn listener(listener: &mut dyn Read, ponger: &mut dyn Write) -> std::io::Result<()> {
         let reader = BufReader::new(listener);
         for line in reader.lines().flatten() {
+            // el servidor pide un PONG para saber que el cliente sigue conectado
+            if let Some(origin) = line.strip_prefix("PING") {
+                ClientC::write_to(ponger, format!("PONG{}", origin.trim_end()))?;
+                continue;
+            }
             println!("{}", line);
         }
         Ok(())
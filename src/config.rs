@@ -2,13 +2,21 @@
 #![allow(unused_variables)]
 
 use crate::app_errors;
+use crate::server::admin::AdminInfo;
+use crate::server::clients_info::DEFAULT_PING_TIMEOUT;
+use crate::server::connection::DEFAULT_SENDQ_SIZE;
+use crate::server::flood::FloodLimits;
+use crate::server::irc_name::CaseMapping;
+use crate::server::link::ConnectBlock;
+use crate::server::listener::Listen;
+use crate::server::reply::ReplyFormat;
+use crate::server::validation::{DEFAULT_CHANNEL_LEN, DEFAULT_NICK_LEN};
 use std::error::Error;
 use std::fs;
 
 pub struct Config {
     pub name: String,
-    pub address: String,
-    pub port: String,
+    pub listeners: Vec<Listen>, //addresses and ports where the server accepts connections
     pub log_path: String,
     pub operators_path: String, //path to the file with the operators of the server/s
     pub trusted_servers_path: String,
@@ -17,7 +25,24 @@ pub struct Config {
     pub parent_ip: Option<String>,
     pub parent_port: Option<String>,
     pub parent_pwd: Option<String>,
+    pub reply_format: ReplyFormat, //format of the numeric replies sent to the clients
+    pub motd_path: Option<String>, //path to the message of the day sent after registration
+    pub ping_timeout: u64, //seconds of inactivity before sending a PING, and then before dropping the connection
+    pub sendq_size: usize, //bytes that may be waiting to be sent to a connection before dropping it
+    pub nick_len: usize,   //max length of the nicks, advertised as NICKLEN
+    pub channel_len: usize, //max length of the channel names, advertised as CHANNELLEN
+    pub reserved_nicks: Vec<String>, //nicks that no user may take (i.e. the ones of the services)
+    pub casemapping: CaseMapping, //rule used to compare nicks and channel names ignoring case
+    pub klines_path: Option<String>, //path to the csv file with the K-lines (bans of user@host masks)
+    pub flood_limits: FloodLimits, //messages a user may send in a row and per second before being throttled
+    pub tls_cert_path: Option<String>, //certificate chain (PEM) of the TLS listeners
+    pub tls_key_path: Option<String>, //private key (PEM) of the TLS listeners
+    pub parent_tls: bool,          //whether the connection to the parent server uses TLS
+    pub tls_ca_path: Option<String>, //certificates (PEM) trusted when linking to other servers over TLS
+    pub connect_blocks: Vec<ConnectBlock>, //servers this one may link to with CONNECT or autoconnect
+    pub admin_info: AdminInfo,             //location, organization and email answered to ADMIN
 }
+
 /// Config parses the input arguments from the server
 /// such as the ip address, port, and log file path
 impl Config {
@@ -52,36 +77,37 @@ impl Config {
         let mut trusted_servers_path = "".to_string();
         let mut operators_path = "".to_string();
         let mut log_path = "".to_string();
+        let mut reply_format = ReplyFormat::default();
+        let mut motd_path = None;
+        let mut ping_timeout = DEFAULT_PING_TIMEOUT;
+        let mut sendq_size = DEFAULT_SENDQ_SIZE;
+        let mut nick_len = DEFAULT_NICK_LEN;
+        let mut channel_len = DEFAULT_CHANNEL_LEN;
+        let mut reserved_nicks = vec![];
+        let mut casemapping = CaseMapping::default();
+        let mut klines_path = None;
+        let mut flood_limits = FloodLimits::default();
+        let mut tls_port = None;
+        let mut tls_cert_path = None;
+        let mut tls_key_path = None;
+        let mut parent_tls = false;
+        let mut tls_ca_path = None;
+        let mut listeners = vec![];
+        let mut connect_blocks = vec![];
+        let mut admin_info = AdminInfo::default();
 
         for line in contents.lines() {
-            let l_split: Vec<String> = line
-                .trim()
-                .split(':')
-                .map(|value| value.to_string())
-                .collect();
-            if l_split.len() != 2 {
-                //si no es un par clave:valor lanza error
-                return Err(Box::new(app_errors::ApplicationError(
-                    "Invalid config format.".into(),
-                )));
-            }
-            let l_key = match l_split.get(0) {
-                Some(value) => value,
+            // solo se separa en el primer ':', el valor puede tener mas (i.e. direcciones IPv6)
+            let (l_key, l_value) = match line.trim().split_once(':') {
+                Some(pair) => pair,
                 None => {
+                    //si no es un par clave:valor lanza error
                     return Err(Box::new(app_errors::ApplicationError(
                         "Invalid config format.".into(),
-                    )))
+                    )));
                 }
             };
-            let l_value = match l_split.get(1) {
-                Some(value) => value,
-                None => {
-                    return Err(Box::new(app_errors::ApplicationError(
-                        "Invalid config format.".into(),
-                    )))
-                }
-            };
-            match l_key.as_str() {
+            match l_key {
                 "port" => port = l_value.to_string(),
                 "ip" => address = l_value.to_string(),
                 "name" => name = l_value.to_string(),
@@ -89,6 +115,34 @@ impl Config {
                 "trusted_servers_path" => trusted_servers_path = l_value.to_string(),
                 "operators_path" => operators_path = l_value.to_string(),
                 "log_path" => log_path = l_value.to_string(),
+                "reply_format" => reply_format = ReplyFormat::build(l_value)?,
+                "motd_path" => motd_path = Some(l_value.to_string()),
+                "ping_timeout" => ping_timeout = l_value.trim().parse()?,
+                "sendq_size" => sendq_size = l_value.trim().parse()?,
+                "nick_len" => nick_len = l_value.trim().parse()?,
+                "channel_len" => channel_len = l_value.trim().parse()?,
+                "casemapping" => casemapping = CaseMapping::build(l_value)?,
+                "klines_path" => klines_path = Some(l_value.to_string()),
+                "flood_burst" => flood_limits.burst = l_value.trim().parse()?,
+                "flood_rate" => flood_limits.rate = l_value.trim().parse()?,
+                "flood_excess" => flood_limits.excess = l_value.trim().parse()?,
+                "tls_port" => tls_port = Some(l_value.trim().to_string()),
+                "tls_cert_path" => tls_cert_path = Some(l_value.to_string()),
+                "tls_key_path" => tls_key_path = Some(l_value.to_string()),
+                "parent_tls" => parent_tls = l_value.trim().parse()?,
+                "tls_ca_path" => tls_ca_path = Some(l_value.to_string()),
+                "listen" => listeners.push(Listen::build(l_value)?),
+                "connect" => connect_blocks.push(ConnectBlock::build(l_value)?),
+                "admin_location" => admin_info.location = Some(l_value.trim().to_string()),
+                "admin_organization" => admin_info.organization = Some(l_value.trim().to_string()),
+                "admin_email" => admin_info.email = Some(l_value.trim().to_string()),
+                "reserved_nicks" => {
+                    reserved_nicks = l_value
+                        .split(',')
+                        .map(|nick| nick.trim().to_string())
+                        .filter(|nick| !nick.is_empty())
+                        .collect()
+                }
                 _ => {
                     return Err(Box::new(app_errors::ApplicationError(
                         "Error reading config file.".into(),
@@ -101,12 +155,21 @@ impl Config {
                 "Error: no server name provided".into(),
             )));
         }
-        if address.is_empty() {
+        // las claves ip, port y tls_port equivalen a bloques listen para cualquier conexion
+        if let Some(tls_port) = tls_port {
+            let mut listen = Listen::new(address.clone(), tls_port);
+            listen.tls = true;
+            listeners.insert(0, listen);
+        }
+        if !port.is_empty() {
+            listeners.insert(0, Listen::new(address.clone(), port));
+        }
+        if listeners.iter().any(|listen| listen.address.is_empty()) {
             return Err(Box::new(app_errors::ApplicationError(
                 "Error: no address provided".into(),
             )));
         }
-        if port.is_empty() {
+        if listeners.is_empty() {
             return Err(Box::new(app_errors::ApplicationError(
                 "Error: no port provided".into(),
             )));
@@ -126,10 +189,31 @@ impl Config {
                 "Error: no log file provided".into(),
             )));
         }
+        if ping_timeout == 0 {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Error: ping timeout must be greater than zero".into(),
+            )));
+        }
+        if nick_len == 0 || channel_len < 2 {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Error: nick and channel lengths are too short".into(),
+            )));
+        }
+        if !flood_limits.is_valid() {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Error: flood burst and rate must be greater than zero".into(),
+            )));
+        }
+        if listeners.iter().any(|listen| listen.tls)
+            && (tls_cert_path.is_none() || tls_key_path.is_none())
+        {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Error: the tls port needs a certificate and a key".into(),
+            )));
+        }
         Ok(Config {
             name,
-            address,
-            port,
+            listeners,
             log_path,
             operators_path,
             trusted_servers_path,
@@ -138,6 +222,22 @@ impl Config {
             parent_ip,
             parent_port,
             parent_pwd,
+            reply_format,
+            motd_path,
+            ping_timeout,
+            sendq_size,
+            nick_len,
+            channel_len,
+            reserved_nicks,
+            casemapping,
+            klines_path,
+            flood_limits,
+            tls_cert_path,
+            tls_key_path,
+            parent_tls,
+            tls_ca_path,
+            connect_blocks,
+            admin_info,
         })
     }
 }
@@ -209,6 +309,19 @@ mod config_test {
         assert!(config.is_err());
     }
 
+    #[test]
+    fn config_with_a_flood_rate_that_is_not_a_finite_number_returns_error() {
+        let path = std::env::temp_dir().join("irc_flood_rate_config.csv");
+        let valid = std::fs::read_to_string("tests/test_files/valid_config_file").expect("");
+        for (rate, is_ok) in [("2.5", true), ("NaN", false), ("inf", false), ("0", false)] {
+            std::fs::write(&path, format!("{}\nflood_rate:{}\n", valid.trim(), rate)).expect("");
+            let args = vec!["server".to_string(), path.display().to_string()];
+            let config = super::Config::build(args.into_iter());
+            assert_eq!(config.is_ok(), is_ok, "flood_rate:{}", rate);
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
     // #[test]
     // fn invalid_config_format_returns_error() {
     //     let config_file_test_path = test_files::invalid_config_file();
@@ -5,6 +5,8 @@ pub struct ConfigClient {
     //maybe path for log file in the future.
     pub address: String,
     pub port: String,
+    pub tls: bool,                   //whether the connection to the server uses TLS
+    pub tls_ca_path: Option<String>, //certificates (PEM) trusted when connecting over TLS
 }
 /// Config parses the input arguments from the client
 /// such as the ip address, port, and log file path
@@ -22,6 +24,28 @@ impl ConfigClient {
             None => return Err("Didn't get a port"),
         };
 
-        Ok(ConfigClient { address, port })
+        // opciones: --tls y --tls-ca {path}, que tambien activa TLS
+        let mut tls = false;
+        let mut tls_ca_path = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tls" => tls = true,
+                "--tls-ca" => {
+                    tls = true;
+                    tls_ca_path = match args.next() {
+                        Some(path) => Some(path),
+                        None => return Err("Didn't get the path of the certificates"),
+                    };
+                }
+                _ => return Err("Unknown option"),
+            }
+        }
+
+        Ok(ConfigClient {
+            address,
+            port,
+            tls,
+            tls_ca_path,
+        })
     }
 }
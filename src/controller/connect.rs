@@ -2,7 +2,7 @@ mod login;
 
 use crate::client::ClientBuilder;
 use gtk::prelude::*;
-use gtk::{Builder, Button, Entry, Label, Window};
+use gtk::{Builder, Button, CheckButton, Entry, Label, Window};
 use login::*;
 use std::cell::RefCell;
 use std::error::Error;
@@ -35,6 +35,9 @@ pub fn run_connect() -> Result<(), Box<dyn Error>> {
     let port_input: Entry = builder
         .object("port_input")
         .expect("port_input object not found");
+    let tls_checkbox: CheckButton = builder
+        .object("tls_checkbox")
+        .expect("tls_checkbox object not found");
     let connect_button: Button = builder
         .object("connect_button")
         .expect("connect_button object not found");
@@ -51,6 +54,7 @@ pub fn run_connect() -> Result<(), Box<dyn Error>> {
         let mut client_builder = ClientBuilder::new();
         client_builder.set_ip(ip_input_clone.text().to_string());
         client_builder.set_port(port_input_clone.text().to_string());
+        client_builder.set_tls(tls_checkbox.is_active());
         match client_builder.get_client() {
             Ok(client) => {
                 let mut can_close_window = can_close_window_clone.borrow_mut();
@@ -56,8 +56,8 @@ pub fn run_login(client: ClientC) {
                 nick_input.text().to_string(),
                 user_input.text().to_string(),
             );
-        match result.starts_with('2') {
-            //2xx are the http codes for success
+        match ClientC::reply_numeric(&result).as_deref() == Some("001") {
+            //001 is RPL_WELCOME, sent once the user is registered
             true => {
                 let mut can_close_window = can_close_window_clone.borrow_mut();
                 *can_close_window = true;
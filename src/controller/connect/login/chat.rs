@@ -17,6 +17,7 @@ pub fn run_chat(client: Arc<Mutex<ClientC>>) -> Result<(), Box<dyn Error>> {
     let entry_dialog_resume_file_src = include_str!("entry_dialog_resume_file.glade");
     let double_entry_dialog_src = include_str!("double_entry_dialog.glade");
     let dcc_solicitud_dialog_src = include_str!("dcc_solicitud_dialog.glade");
+    let network_map_dialog_src = include_str!("network_map_dialog.glade");
     // let dcc_chat_window_src = include_str!("dcc_chat_window.glade");
 
     // Then we call the Builder call.
@@ -195,6 +196,9 @@ pub fn run_chat(client: Arc<Mutex<ClientC>>) -> Result<(), Box<dyn Error>> {
     let servers_disconnect_from: MenuItem = builder
         .object("servers_disconnect_from")
         .expect("servers_disconnect_from object not found");
+    let servers_network_map: MenuItem = builder
+        .object("servers_network_map")
+        .expect("servers_network_map object not found");
     let channels_create_channel: MenuItem = builder
         .object("channels_create_channel")
         .expect("channels_create_channel object not found");
@@ -312,6 +316,39 @@ pub fn run_chat(client: Arc<Mutex<ClientC>>) -> Result<(), Box<dyn Error>> {
         gtk::Inhibit(true)
     });
 
+    //MAPA DE LA RED (solo operadores)
+    let client_clone = client.clone();
+    servers_network_map.connect_button_press_event(move |_, _| {
+        let network_map_builder = Builder::from_string(network_map_dialog_src);
+        let dialog: Dialog = network_map_builder
+            .object("network_map_dialog")
+            .expect("Problems opening network_map_dialog");
+        let close_button: Button = network_map_builder
+            .object("close")
+            .expect("close object not found");
+        let map_display: TextView = network_map_builder
+            .object("map_display")
+            .expect("map_display object not found");
+        let map = client_clone
+            .lock()
+            .expect("Couldn't lock client")
+            .get_map();
+        let text = match map {
+            Ok(lines) => lines.join("\n"),
+            Err(message) => message,
+        };
+        map_display
+            .buffer()
+            .expect("map_display object not found")
+            .set_text(&text);
+        dialog.show_all();
+        let dialog_clone = dialog;
+        close_button.connect_clicked(move |_| {
+            dialog_clone.close();
+        });
+        gtk::Inhibit(true)
+    });
+
     //REVISAR
     let chat_display_clone = chat_display.clone();
     let channels_conversations_changed = conversations.clone();
@@ -3,12 +3,18 @@ mod config;
 mod server;
 
 use config::Config;
+use server::password;
 use server::Server;
 use std::env;
+use std::io::stdin;
 use std::process;
 
 /// The main function for the server, which parses the command line arguments, builds the server and runs it.
 fn main() {
+    if env::args().nth(1).as_deref() == Some("mkpasswd") {
+        mkpasswd(env::args().nth(2));
+        return;
+    }
     let config = Config::build(env::args());
     let config = match config {
         Ok(config) => config,
@@ -31,3 +37,26 @@ fn main() {
         process::exit(1);
     }
 }
+
+/// Prints the hash of the password to use in the operators or trusted servers files.
+/// If it's not an argument it's read from stdin, so it doesn't stay in the shell history.
+fn mkpasswd(password: Option<String>) {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            let mut line = String::new();
+            if let Err(err) = stdin().read_line(&mut line) {
+                eprintln!("Problem reading the password: {err}");
+                process::exit(1);
+            }
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    match password::hash(&password) {
+        Ok(hash) => println!("{hash}"),
+        Err(err) => {
+            eprintln!("Problem hashing the password: {err}");
+            process::exit(1);
+        }
+    }
+}
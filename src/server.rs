@@ -1,30 +1,44 @@
+pub mod admin;
 pub mod channel;
 pub mod client_s;
 pub mod clients_info;
+pub mod connection;
+pub mod flood;
+pub mod irc_name;
+pub mod klines;
+pub mod link;
+pub mod listener;
 pub mod logger;
+pub mod mask;
+pub mod operator;
+pub mod password;
+pub mod reply;
+pub mod time;
+pub mod tls;
+pub mod validation;
 use crate::server::logger::Logger;
-use client_s::ClientS;
 pub use clients_info::ClientsInfo;
+use connection::Connection;
+use klines::Klines;
+use link::{ConnectBlock, Linker};
+use listener::Listener;
+use operator::Operator;
 
 use crate::app_errors;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 pub struct Server {
-    clients: Arc<Mutex<ClientsInfo>>,
-    listener: TcpListener,
+    listeners: Vec<Listener>,
     parent_name: Option<String>,
-    parent: Option<Arc<Mutex<TcpStream>>>,
-    password: Option<String>,
-    trusted_servers: HashMap<String, Option<String>>,
-    log: Arc<Mutex<Logger>>,
+    parent: Option<Connection>,
+    linker: Linker,
 }
 
 /// Server is the main struct of the server. Initializes new conections and allows a communication in the network.
@@ -33,184 +47,162 @@ impl Server {
     pub fn build(config: crate::config::Config) -> Result<Server, Box<dyn Error>> {
         let operators = Self::build_operators(config.operators_path)?;
         let trusted_servers = Self::build_trusted_servers(config.trusted_servers_path)?;
+        let warnings = Self::plaintext_password_warnings(&operators, &trusted_servers);
         let parent_name = config.parent_name;
         let password = config.password.clone();
-        let clients = Arc::new(Mutex::new(ClientsInfo::new(
-            config.name.clone(),
-            config.password,
-            operators,
-        )));
+        let sendq_size = config.sendq_size;
+        let mut clients_info = ClientsInfo::new(config.name.clone(), config.password, operators);
+        clients_info.set_reply_format(config.reply_format);
+        clients_info.set_motd_path(config.motd_path);
+        clients_info.set_ping_timeout(Duration::from_secs(config.ping_timeout));
+        clients_info.set_name_limits(config.nick_len, config.channel_len, config.reserved_nicks);
+        clients_info.set_casemapping(config.casemapping);
+        clients_info.set_flood_limits(config.flood_limits);
+        clients_info.set_admin_info(config.admin_info);
+        if let Some(klines_path) = config.klines_path {
+            clients_info.set_klines(Klines::load(klines_path)?);
+        }
+        let clients = Arc::new(Mutex::new(clients_info));
         let log = Arc::new(Mutex::new(Logger::build(config.log_path)));
         log.lock()
             .expect("Error creating log lock")
             .write("\n=============== SERVER EXECUTED ===============".to_string());
+        for warning in warnings {
+            log.lock().expect("Error creating log lock").write(warning);
+        }
         // arc allow multiple threads to access the same data and mutex allow only one thread to access the data at a time
-        let listener = TcpListener::bind(config.address + ":" + &config.port)?;
-        // TcpListener is a type that listens for incoming TCP connections.
-
-        let mut parent_connection = Self::connect_to_parent(config.parent_ip, config.parent_port)?;
-        //si no hay parent, parent_connection es None
-
-        Self::register_to_parent(&mut parent_connection, config.parent_pwd, config.name)?;
 
-        let mut parent = None;
-
-        if let Some(connection) = parent_connection {
-            parent = Some(Arc::new(Mutex::new(connection)))
+        // los listeners TLS usan el certificado y la clave del config
+        let tls_config = match (config.tls_cert_path, config.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some(tls::server_config(&cert_path, &key_path)?),
+            _ => None,
+        };
+        let mut listeners = vec![];
+        for listen in config.listeners {
+            listeners.push(Listener::bind(listen, tls_config.clone())?);
         }
 
+        // los enlaces con TLS (al padre o de los bloques connect) confian en los certificados de tls_ca_path
+        let link_tls = config.parent_tls || config.connect_blocks.iter().any(|block| block.tls);
+        let link_tls_config = match link_tls {
+            true => Some(tls::client_config(config.tls_ca_path.as_deref())?),
+            false => None,
+        };
+        let mut linker = Linker::new(clients, trusted_servers, log, password, sendq_size);
+        linker.set_connect_blocks(config.connect_blocks, link_tls_config);
+
+        //si no hay parent (nombre, ip y puerto), parent es None
+        let parent = match (&parent_name, config.parent_ip, config.parent_port) {
+            (Some(name), Some(ip), Some(port)) => {
+                let mut block = ConnectBlock::new(name.clone(), ip, port);
+                block.password = config.parent_pwd;
+                block.tls = config.parent_tls;
+                Some(linker.open(&block)?)
+            }
+            _ => None,
+        };
+
         Ok(Server {
-            clients,
-            listener,
+            listeners,
             parent_name,
             parent,
-            password,
-            trusted_servers,
-            log,
+            linker,
         })
     }
 
-    /// is the main loop of the server, it accepts new connections and
-    /// creates a new thread for each one
+    /// is the main loop of the server, every listener accepts new connections in its own thread
+    /// and creates a new thread for each one. The links of the connect blocks with autoconnect
+    /// are kept up in their own threads too
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut threads = vec![]; // vector of threads
-        if let Some(parent) = self.parent.clone() {
-            let clients = self.clients.clone(); //with clone create a new reference to ClientsInfo
-            self.handle_server(parent, clients)?;
+        if let (Some(parent), Some(parent_name)) = (self.parent.take(), self.parent_name.clone()) {
+            self.linker.start(parent, parent_name)?;
         }
-        // accept connections and process them, spawning a new thread for each one
-        for stream in self.listener.incoming() {
-            let stream = stream?;
-            let clients = self.clients.clone(); //with clone create a new reference to ClientsInfo
-            self.handle_client(Arc::new(Mutex::new(stream)), clients, &mut threads)?;
+        for block in self.linker.autoconnect_blocks() {
+            let linker = self.linker.clone();
+            thread::spawn(move || linker.autoconnect(block));
         }
-        // wait for all threads to finish
-        for child in threads {
-            match child.join() {
-                Ok(_) => continue,
-                Err(_) => println!("Thread panicked"),
-            }
+        let mut listeners = vec![];
+        for listener in self.listeners.drain(..) {
+            let linker = self.linker.clone();
+            listeners.push(thread::spawn(move || {
+                Self::accept_connections(listener, linker)
+            }));
         }
-        Ok(())
-    }
-
-    /// Registers to the parent server sending the PASS (if there is one) and SERVER commands
-    fn register_to_parent(
-        connection: &mut Option<TcpStream>,
-        pwd: Option<String>,
-        server_name: String,
-    ) -> Result<(), Box<dyn Error>> {
-        if let Some(connection) = connection {
-            if let Some(pwd) = pwd {
-                connection.write_all(format!("PASS {}\n", pwd).as_bytes())?; //it sends the PASS command to the parent server
-            }
-            connection.write_all(format!("SERVER {} 1 info", server_name).as_bytes())?; //it sends the SERVER command to the parent server
-            let ans = Self::read_from_stream(connection)?;
-            println!("{ans}");
-            if ans.starts_with('4') {
-                // 4xx are the http error codes for client errors (in this context the parent server is the client)
-                return Err(Box::new(app_errors::ApplicationError(ans)));
+        // wait for all threads to finish
+        for listener in listeners {
+            if listener.join().is_err() {
+                println!("Thread panicked");
             }
         }
         Ok(())
     }
 
-    /// Returns the conection to the parent server if there is one
-    fn connect_to_parent(
-        ip: Option<String>,
-        port: Option<String>,
-    ) -> Result<Option<TcpStream>, Box<dyn Error>> {
-        //si hay parent (ip y puerto) se conecta, sino devuelve None
-        if let (Some(ip), Some(port)) = (ip, port) {
-            let connection = TcpStream::connect(ip + ":" + &port)?;
-            Ok(Some(connection))
-        } else {
-            Ok(None)
-        }
-    }
-
-    /// Reads a line from the stream and returns it as a string
-    fn read_from_stream(connection: &mut TcpStream) -> Result<String, Box<dyn Error>> {
-        let mut line = String::new();
-        let mut char = [b'\n'];
-
-        while char[0] == b'\n' {
-            // read until the first non-empty line
-            if connection.read_exact(&mut char).is_err() {
-                continue;
-            }
-        }
-        while char[0] != b'\n' {
-            // read until the end of the line
-            line.push_str(&String::from_utf8(Vec::from(char))?);
-            char = [b'\n'];
-            if connection.read_exact(&mut char).is_err() {
-                continue;
+    /// accept connections from the listener and process them, spawning a new thread for each one.
+    /// A failed connection (i.e. a bad TLS handshake) doesn't stop the listener
+    fn accept_connections(listener: Listener, linker: Linker) {
+        println!("Escuchando en {}", listener.listen());
+        let mut threads = vec![]; // vector of threads
+        loop {
+            match listener.accept() {
+                Ok(connection) => Self::handle_client(connection, &linker, &mut threads),
+                Err(err) => println!(
+                    "Error accepting a connection on {}: {err}",
+                    listener.listen()
+                ),
             }
         }
-        Ok(line)
     }
 
     /// Creates a new client and runs it in a new thread
     fn handle_client(
-        &self,
-        stream: Arc<Mutex<TcpStream>>,
-        clients: Arc<Mutex<ClientsInfo>>,
+        connection: Connection,
+        linker: &Linker,
         threads: &mut Vec<JoinHandle<Result<(), std::io::Error>>>,
-    ) -> std::io::Result<()> {
-        let mut client = ClientS::new(
-            clients,
-            stream,
-            self.trusted_servers.clone(),
-            self.log.clone(),
-        )
-        .expect("Error creating a new client");
+    ) {
+        let mut client = linker
+            .client(connection)
+            .expect("Error creating a new client");
         threads.push(thread::spawn(move || client.run()));
-        Ok(())
     }
 
-    /// for every server conected to a parent server it sends the list of clients to the parent server
-    /// and creates a new thread for each one
-    fn handle_server(
-        &self,
-        stream: Arc<Mutex<TcpStream>>,
-        clients: Arc<Mutex<ClientsInfo>>,
-    ) -> std::io::Result<()> {
-        let mut client = ClientS::new(
-            clients,
-            stream,
-            self.trusted_servers.clone(),
-            self.log.clone(),
-        )
-        .expect("Error creating a new server conection");
-        client.set_parent(self.parent_name.clone(), self.password.clone());
-        thread::spawn(move || client.run());
-        Ok(())
-    }
-
-    /// Given a path to a csv file, it returns a hashmap with the operators of the server
-    fn build_operators(path_operators: String) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    /// Given a path to a csv file, it returns a hashmap with the operator blocks of the server by name
+    fn build_operators(
+        path_operators: String,
+    ) -> Result<HashMap<String, Operator>, Box<dyn Error>> {
         let contents = fs::read_to_string(path_operators)?;
         let mut operators = HashMap::new();
 
         //parse the csv file and adds the operators to the hashmap
         for line in contents.lines() {
-            let mut l_split: Vec<String> = line
-                .trim()
-                .split(',')
-                .map(|value| value.to_string())
-                .collect();
-            if l_split.len() != 2 {
-                // if the line is not in the format "operator, password"
-                return Err(Box::new(app_errors::ApplicationError(
-                    "Invalid operators format.".into(),
-                )));
-            }
-            operators.insert(l_split.remove(0), l_split.remove(0));
+            // "operator,password[,hosts[,privileges]]"
+            let operator = Operator::build(line)?;
+            operators.insert(operator.name.clone(), operator);
         }
         Ok(operators)
     }
 
+    /// Given the operators and the trusted servers returns a warning for each password that isn't hashed.
+    /// They are still accepted, so the files of older versions keep working
+    fn plaintext_password_warnings(
+        operators: &HashMap<String, Operator>,
+        trusted_servers: &HashMap<String, Option<String>>,
+    ) -> Vec<String> {
+        let mut warnings: Vec<String> = operators
+            .iter()
+            .filter(|(_, operator)| !password::is_hashed(&operator.password))
+            .map(|(name, _)| format!("Warning: the password of the operator {name} is not hashed"))
+            .collect();
+        for (name, pass) in trusted_servers {
+            if matches!(pass, Some(pass) if !password::is_hashed(pass)) {
+                warnings.push(format!(
+                    "Warning: the password of the trusted server {name} is not hashed"
+                ));
+            }
+        }
+        warnings.sort();
+        warnings
+    }
+
     /// Given a path to a csv file, it returns a hashmap with the trusted servers of the server
     /// with the format "server_name: password"
     fn build_trusted_servers(
@@ -221,23 +213,28 @@ impl Server {
 
         //parse the csv file and adds the operators to the hashmap
         for line in contents.lines() {
-            let mut l_split: Vec<String> = line
-                .trim()
-                .split(',')
-                .map(|value| value.to_string())
-                .collect();
-            if l_split.len() != 2 {
-                return Err(Box::new(app_errors::ApplicationError(
-                    "Invalid trusted servers format.".into(),
-                )));
-            }
-            let curr_name = l_split.remove(0);
+            // la contraseña puede ser un hash con comas, asi que se separa solo el nombre
+            let (curr_name, rest) = match line.trim().split_once(',') {
+                Some((name, rest)) => (name.to_string(), rest),
+                None => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "Invalid trusted servers format.".into(),
+                    )))
+                }
+            };
+            let curr_pass = match password::split_password(rest) {
+                (pass, None) => pass.to_string(),
+                (_, Some(_)) => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "Invalid trusted servers format.".into(),
+                    )))
+                }
+            };
             if curr_name.is_empty() {
                 return Err(Box::new(app_errors::ApplicationError(
                     "Invalid trusted server name.".into(),
                 )));
             }
-            let curr_pass = l_split.remove(0);
             if curr_pass.is_empty() {
                 trusted_servers.insert(curr_name, None);
             } else {
@@ -253,6 +250,16 @@ mod server_test {
     // use crate::app_errors;
     // use crate::server::clients_info::ClientsInfo;
     use crate::config::Config;
+    use crate::server::admin::AdminInfo;
+    use crate::server::clients_info::DEFAULT_PING_TIMEOUT;
+    use crate::server::connection::DEFAULT_SENDQ_SIZE;
+    use crate::server::flood::FloodLimits;
+    use crate::server::irc_name::CaseMapping;
+    use crate::server::listener::Listen;
+    use crate::server::validation::{DEFAULT_CHANNEL_LEN, DEFAULT_NICK_LEN};
+    use crate::server::reply::ReplyFormat;
+    use crate::server::operator::Privilege;
+    use crate::server::password;
     use crate::server::Server;
 
     // use std::collections::HashMap;
@@ -265,11 +272,10 @@ mod server_test {
 
     #[allow(dead_code)]
     pub fn setup_config_server() -> Config {
-        let config = Config {
+        Config {
             name: "server_uno".to_string(),
             password: Some("1111".to_string()),
-            port: "7878".to_string(),
-            address: "localhost".to_string(),
+            listeners: vec![Listen::new("localhost".to_string(), "7878".to_string())],
             log_path: "tests/test_files/log_file_1".to_string(),
             operators_path: "tests/test_files/valid_operators".to_string(),
             trusted_servers_path: "tests/test_files/valid_trusted_servers".to_string(),
@@ -277,26 +283,55 @@ mod server_test {
             parent_ip: None,
             parent_port: None,
             parent_pwd: None,
-        };
-        return config;
+            reply_format: ReplyFormat::Rfc1459,
+            motd_path: None,
+            ping_timeout: DEFAULT_PING_TIMEOUT,
+            sendq_size: DEFAULT_SENDQ_SIZE,
+            nick_len: DEFAULT_NICK_LEN,
+            channel_len: DEFAULT_CHANNEL_LEN,
+            reserved_nicks: vec![],
+            casemapping: CaseMapping::Rfc1459,
+            klines_path: None,
+            flood_limits: FloodLimits::default(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            parent_tls: false,
+            tls_ca_path: None,
+            connect_blocks: vec![],
+            admin_info: AdminInfo::default(),
+        }
     }
 
     #[allow(dead_code)]
     pub fn setup_config_server_parent() -> Config {
-        let config = Config {
+        Config {
             name: "server_dos".to_string(),
             password: Some("2222".to_string()),
-            port: "7879".to_string(),
-            address: "localhost".to_string(),
+            listeners: vec![Listen::new("localhost".to_string(), "7879".to_string())],
             log_path: "tests/test_files/log_file_2".to_string(),
             operators_path: "tests/test_files/valid_operators".to_string(),
             trusted_servers_path: "tests/test_files/valid_trusted_servers".to_string(),
             parent_name: Some("server_uno".to_string()),
             parent_ip: Some("localhost".to_string()),
             parent_port: Some("7878".to_string()),
-            parent_pwd: Some("1111".to_string()),
-        };
-        return config;
+            parent_pwd: Some("2222".to_string()),
+            reply_format: ReplyFormat::Rfc1459,
+            motd_path: None,
+            ping_timeout: DEFAULT_PING_TIMEOUT,
+            sendq_size: DEFAULT_SENDQ_SIZE,
+            nick_len: DEFAULT_NICK_LEN,
+            channel_len: DEFAULT_CHANNEL_LEN,
+            reserved_nicks: vec![],
+            casemapping: CaseMapping::Rfc1459,
+            klines_path: None,
+            flood_limits: FloodLimits::default(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            parent_tls: false,
+            tls_ca_path: None,
+            connect_blocks: vec![],
+            admin_info: AdminInfo::default(),
+        }
     }
 
     // #[test]
@@ -313,7 +348,7 @@ mod server_test {
         assert!(opers.is_ok());
         assert!(opers
             .expect("fail result")
-            .contains_key(&"nico".to_string()));
+            .contains_key("nico"));
     }
     #[test]
     fn server_builds_operators_value_is_correct() {
@@ -321,8 +356,9 @@ mod server_test {
         assert_eq!(
             opers
                 .expect("fail result")
-                .get(&"nico".to_string())
-                .expect("fail get"),
+                .get("nico")
+                .expect("fail get")
+                .password,
             "123"
         );
     }
@@ -335,12 +371,41 @@ mod server_test {
         assert_eq!(
             *trusted_servers
                 .expect("fail result")
-                .get(&"server_uno".to_string())
+                .get("server_uno")
                 .expect("fail get"),
             Some("1111".to_string())
         );
     }
 
+    #[test]
+    fn hashed_operators_are_read_from_file() {
+        let opers = Server::build_operators("tests/test_files/hashed_operators".to_string())
+            .expect("fail result");
+        let juan = opers.get("juan").expect("fail get");
+        assert!(password::verify(&juan.password, "botter"));
+        let nico = opers.get("nico").expect("fail get");
+        assert!(password::verify(&nico.password, "botter"));
+        assert_eq!(nico.hosts, vec!["*@127.0.0.1".to_string()]);
+        assert!(!nico.privileges.contains(&Privilege::Route));
+    }
+
+    #[test]
+    fn hashed_trusted_servers_are_read_from_file() {
+        let trusted_servers =
+            Server::build_trusted_servers("tests/test_files/hashed_trusted_servers".to_string())
+                .expect("fail result");
+        let stored = trusted_servers
+            .get("server_dos")
+            .expect("fail get")
+            .as_ref()
+            .expect("no password");
+        assert!(password::verify(stored, "2222"));
+        assert_eq!(
+            *trusted_servers.get("server_tres").expect("fail get"),
+            Some("3333".to_string())
+        );
+    }
+
     #[test]
     fn invalid_trusted_server_file_results_in_error() {
         let trusted_servers =
@@ -0,0 +1,69 @@
+use crate::app_errors;
+
+/// Administrative info of the server read from the config and answered to ADMIN.
+/// Every field is optional, the ones missing are sent empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AdminInfo {
+    pub location: Option<String>,     //city, state and country of the server
+    pub organization: Option<String>, //institution that runs it
+    pub email: Option<String>,        //how to contact the administrator
+}
+
+impl AdminInfo {
+    /// Returns whether the config doesn't have any administrative info
+    pub fn is_empty(&self) -> bool {
+        self.location.is_none() && self.organization.is_none() && self.email.is_none()
+    }
+
+    /// Given the name of the server returns the replies to ADMIN,
+    /// or ERR_NOADMININFO if there is no administrative info
+    pub fn replies(&self, server_name: &str) -> Vec<((i32, &'static str), Vec<String>)> {
+        if self.is_empty() {
+            return vec![(app_errors::ERR_NOADMININFO, vec![server_name.to_string()])];
+        }
+        vec![
+            (app_errors::RPL_ADMINME, vec![server_name.to_string()]),
+            (
+                app_errors::RPL_ADMINLOC1,
+                vec![self.location.clone().unwrap_or_default()],
+            ),
+            (
+                app_errors::RPL_ADMINLOC2,
+                vec![self.organization.clone().unwrap_or_default()],
+            ),
+            (
+                app_errors::RPL_ADMINEMAIL,
+                vec![self.email.clone().unwrap_or_default()],
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod admin_test {
+    use super::*;
+
+    #[test]
+    fn admin_info_is_answered_in_order() {
+        let admin = AdminInfo {
+            location: Some("Buenos Aires, Argentina".to_string()),
+            organization: None,
+            email: Some("admin@fi.uba.ar".to_string()),
+        };
+        let replies = admin.replies("server_uno");
+        let numerics: Vec<i32> = replies.iter().map(|((number, _), _)| *number).collect();
+        assert_eq!(numerics, vec![256, 257, 258, 259]);
+        assert_eq!(replies[0].1, vec!["server_uno".to_string()]);
+        assert_eq!(replies[2].1, vec!["".to_string()]);
+        assert_eq!(replies[3].1, vec!["admin@fi.uba.ar".to_string()]);
+    }
+
+    #[test]
+    fn missing_admin_info_returns_error() {
+        let replies = AdminInfo::default().replies("server_uno");
+        assert_eq!(
+            replies,
+            vec![(app_errors::ERR_NOADMININFO, vec!["server_uno".to_string()])]
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use crate::app_errors;
 use crate::server::client_s::message::command::Mode;
 use crate::server::clients_info::*;
+use crate::server::connection::Connection;
+use crate::server::irc_name::{CaseMapping, IrcName};
+use crate::server::mask;
+use crate::server::time::now;
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
 
 pub type DefaultAndError = Result<(), ((i32, &'static str), Vec<String>)>;
@@ -13,27 +16,37 @@ pub type DefaultAndError = Result<(), ((i32, &'static str), Vec<String>)>;
 pub struct Channel {
     name: String,
     topic: Option<String>,
-    users: HashMap<String, Option<Arc<Mutex<TcpStream>>>>,
-    //banned_users: HashSet<String>,
-    operators: HashSet<String>,
+    topic_setter: Option<(String, u64)>, // nick of who set the topic and when (seconds since the unix epoch)
+    users: HashMap<IrcName, Option<Arc<Mutex<Connection>>>>,
+    bans: Vec<String>,       //'nick!user@host' masks
+    exceptions: Vec<String>, //masks that can join even if they match a ban
+    operators: HashSet<IrcName>,
+    voiced: HashSet<IrcName>,
     key: Option<String>,
-    invites: HashSet<String>,
+    invites: HashSet<IrcName>,
     invited_only: bool,
     limit: Option<usize>,
     secret: bool,
+    moderated: bool,
+    topic_lock: bool,
+    no_external: bool,
+    casemapping: CaseMapping, // nicks are compared with the casemapping of the server
+    created: u64, // seconds since the unix epoch, the older channel wins when two servers link
 }
 
 impl Channel {
-    /// Given the channel name, it's key, the nick of the creator and the stream of the creator returns the corresponding
-    /// channel object.
+    /// Given the channel name, it's key, the nick of the creator, the stream of the creator and the casemapping
+    /// of the server returns the corresponding channel object.
     pub fn new(
         name: String,
         key: Option<String>,
         creator_nick: String,
-        creator_stream: Option<Arc<Mutex<TcpStream>>>,
+        creator_stream: Option<Arc<Mutex<Connection>>>,
+        casemapping: CaseMapping,
     ) -> Channel {
         let mut operators = HashSet::new();
         let mut users = HashMap::new();
+        let creator_nick = IrcName::new(&creator_nick, casemapping);
         users.insert(creator_nick.clone(), creator_stream);
         operators.insert(creator_nick);
         let topic = None;
@@ -43,41 +56,109 @@ impl Channel {
         Channel {
             name,
             topic,
+            topic_setter: None,
             users,
+            bans: Vec::new(),
+            exceptions: Vec::new(),
             operators,
+            voiced: HashSet::new(),
             key,
             invites,
             limit: None,
             invited_only,
             secret: false,
+            moderated: false,
+            topic_lock: false,
+            no_external: false,
+            casemapping,
+            created: now(),
         }
     }
 
+    /// Given self and a nick returns it as a name compared with the casemapping of the channel
+    fn irc_name(&self, nick: &str) -> IrcName {
+        IrcName::new(nick, self.casemapping)
+    }
+
     /// Given self returns a copy of the channel key
     pub fn get_key(&mut self) -> Option<String> {
         self.key.clone()
     }
 
     /// Given self returns a copy of the operators of the channel
-    pub fn get_operators(&mut self) -> HashSet<String> {
-        self.operators.clone()
+    pub fn get_operators(&mut self) -> Vec<String> {
+        self.operators.iter().map(|nick| nick.to_string()).collect()
     }
 
     /// Given self returns a vector with a copy of each member nick
     pub fn get_names(&mut self) -> Vec<String> {
         let mut names = Vec::new();
         for name in self.users.keys() {
-            names.push(name.clone());
+            names.push(name.to_string());
         }
         names
     }
 
+    /// Given self returns a copy of the voiced users of the channel
+    pub fn get_voiced(&mut self) -> Vec<String> {
+        self.voiced.iter().map(|nick| nick.to_string()).collect()
+    }
+
+    /// Given self returns a vector with each member nick preceded by '@' if they are
+    /// an operator or '+' if they have voice, as shown by NAMES
+    pub fn get_prefixed_names(&mut self) -> Vec<String> {
+        let mut names = Vec::new();
+        for name in self.users.keys() {
+            if self.operators.contains(name) {
+                names.push(format!("@{}", name));
+            } else if self.voiced.contains(name) {
+                names.push(format!("+{}", name));
+            } else {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
+    /// Given self returns when the channel was created (seconds since the unix epoch)
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Given the creation time of the same channel on another server sets it
+    pub fn set_created(&mut self, created: u64) {
+        self.created = created;
+    }
+
+    /// Given self removes every operator and voice, as the channel lost against an older one.
+    /// Returns the nicks that were operators and the ones that had voice
+    pub fn clear_privileges(&mut self) -> (Vec<String>, Vec<String>) {
+        let operators = self.get_operators();
+        let voiced = self.get_voiced();
+        self.operators.clear();
+        self.voiced.clear();
+        (operators, voiced)
+    }
+
+    /// Given self returns the nick and stream of every member connected to this server
+    pub fn local_members(&mut self) -> Vec<(String, Arc<Mutex<Connection>>)> {
+        let mut members = Vec::new();
+        for (name, stream) in self.users.iter() {
+            if let Some(stream) = stream {
+                members.push((name.to_string(), stream.clone()));
+            }
+        }
+        members
+    }
+
     /// Given self and a nick tries to remove if possible.
     /// Will also asign a new channel operator if last is removed.
     /// The new operator will be assigned arbitrarily.
-    pub fn remove_if_present(&mut self, nick: &String) {
-        self.users.remove(nick);
-        self.operators.remove(nick);
+    pub fn remove_if_present(&mut self, nick: &str) {
+        let nick = self.irc_name(nick);
+        self.users.remove(&nick);
+        self.operators.remove(&nick);
+        self.voiced.remove(&nick);
         if self.operators.is_empty() {
             if let Some((user, _stream)) = self.users.iter().next() {
                 self.operators.insert(user.clone());
@@ -85,51 +166,86 @@ impl Channel {
         }
     }
 
+    /// Given the current and the new nick of a user replaces it everywhere in the channel,
+    /// keeping their membership, operator status, voice and invitation.
+    pub fn rename(&mut self, old_nick: &str, new_nick: &str) {
+        let old_nick = self.irc_name(old_nick);
+        let new_nick = self.irc_name(new_nick);
+        if let Some(stream) = self.users.remove(&old_nick) {
+            self.users.insert(new_nick.clone(), stream);
+        }
+        for set in [&mut self.operators, &mut self.voiced, &mut self.invites] {
+            if set.remove(&old_nick) {
+                set.insert(new_nick.clone());
+            }
+        }
+    }
+
     /// Given the nick of the inited user, marks that user as invited.
-    pub fn invite(&mut self, invited: &String) -> ReplyAndError {
-        if self.users.contains_key(invited) {
+    pub fn invite(&mut self, invited: &str) -> ReplyAndError {
+        if self.users.contains_key(&self.irc_name(invited)) {
             return Err((
                 app_errors::ERR_USERONCHANNEL,
-                vec![invited.clone(), self.name.clone()],
+                vec![invited.to_string(), self.name.clone()],
             ));
         }
-        self.invites.insert(invited.clone());
+        self.invites.insert(self.irc_name(invited));
         Ok((
             app_errors::RPL_INVITING,
-            vec![self.name.clone(), invited.clone()],
+            vec![self.name.clone(), invited.to_string()],
         ))
     }
 
     /// Given self returns a copy of the topic.
     pub fn get_topic(&mut self, from: String) -> StringAndError {
-        if !self.users.contains_key(&from) {
+        if !self.users.contains_key(&self.irc_name(&from)) {
             return Err((app_errors::ERR_NOTONCHANNEL, vec![self.name.clone()]));
         }
         Ok(self.topic.clone())
     }
 
+    /// Given self returns who set the current topic and when, if there is one
+    pub fn get_topic_setter(&mut self) -> Option<(String, u64)> {
+        self.topic_setter.clone()
+    }
+
     /// Given self, the new topic and the nick of the user modifies the current topic.
-    /// Only members can modify the topic
+    /// Only members can modify the topic, and only operators if the topic is locked (+t)
     pub fn set_topic(&mut self, new_topic: String, from: String) -> StringAndError {
-        if !self.users.contains_key(&from) {
+        if !self.users.contains_key(&self.irc_name(&from)) {
             return Err((app_errors::ERR_NOTONCHANNEL, vec![self.name.clone()]));
         }
+        if self.topic_lock && !self.operators.contains(&self.irc_name(&from)) {
+            return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![self.name.clone()]));
+        }
         self.topic = Some(new_topic);
+        self.topic_setter = Some((from, now()));
         Ok(None)
     }
 
-    /// Given self, the sender's nick and the message sends the message to all memeber users.
-    /// The sender's nick must be provided to avoid them messaging themselves
+    /// Given self, the sender's nick, their mask and the message sends the message to all memeber users.
+    /// The sender's nick must be provided to avoid them messaging themselves.
+    /// Users that are not members can't send if the channel doesn't allow external messages (+n).
+    /// Banned users (that are not operators) can't send, neither can users without voice on moderated channels.
+    /// The mask is None if it was already checked by the sender's server.
     pub fn send(
         &mut self,
         from: String,
+        mask: Option<String>,
         message: String,
     ) -> Result<(), ((i32, &'static str), Vec<String>)> {
-        if !self.users.contains_key(&from) {
-            return Err((app_errors::ERR_NOTONCHANNEL, vec![self.name.clone()]));
+        let sender = self.irc_name(&from);
+        if self.no_external && !self.users.contains_key(&sender) {
+            return Err((app_errors::ERR_CANNOTSENDTOCHAN, vec![self.name.clone()]));
+        }
+        if let Some(mask) = mask {
+            let silenced = self.moderated && !self.voiced.contains(&sender);
+            if !self.operators.contains(&sender) && (silenced || self.is_banned(&mask)) {
+                return Err((app_errors::ERR_CANNOTSENDTOCHAN, vec![self.name.clone()]));
+            }
         }
         for (user, stream) in self.users.iter_mut() {
-            if *user == from {
+            if *user == sender {
                 continue;
             }
             if let Some(stream) = stream {
@@ -147,48 +263,52 @@ impl Channel {
         self.users.is_empty()
     }
 
-    /// Given self, the potential user nick, their stream and a key tries to add the user to the channel.
+    /// Given self, the potential user nick, their mask, their stream and a key tries to add the user to the channel.
     /// To be added use must provide the correct key.
     /// If the user already belongs the function will return a error.
+    /// Users matching a ban can't join unless they also match an exception or were invited,
+    /// the mask is None if it was already checked by the user's server.
     /// User must also be invited in caso of invite only channel.
     /// Additionally channel limit will be checked if aplicable.
     pub fn add_client(
         &mut self,
         nick: String,
-        stream: Option<Arc<Mutex<TcpStream>>>,
+        mask: Option<String>,
+        stream: Option<Arc<Mutex<Connection>>>,
         key: Option<String>,
     ) -> ReplyAndError {
         if self.key.is_some() && key != self.key {
-            return Err((app_errors::ERR_PASSWDMISMATCH, vec![]));
+            return Err((app_errors::ERR_BADCHANNELKEY, vec![self.name.clone()]));
         };
-        if self.users.contains_key(&nick) {
-            return Err((app_errors::ERR_NICKCOLLISION, vec![nick]));
+        let member = self.irc_name(&nick);
+        if self.users.contains_key(&member) {
+            return Err((app_errors::ERR_USERONCHANNEL, vec![nick, self.name.clone()]));
+        }
+        if let Some(mask) = mask {
+            if self.is_banned(&mask) && !self.invites.contains(&member) {
+                return Err((app_errors::ERR_BANNEDFROMCHAN, vec![self.name.clone()]));
+            }
         }
         if let Some(limit) = self.limit {
             if self.users.len() >= limit {
                 return Err((app_errors::ERR_CHANNELISFULL, vec![self.name.clone()]));
             }
         }
-        if self.invited_only && !self.invites.contains(&nick) {
+        if self.invited_only && !self.invites.contains(&member) {
             return Err((app_errors::ERR_INVITEONLYCHAN, vec![self.name.clone()]));
         }
-        self.users.insert(nick, stream);
+        self.users.insert(member, stream);
         Ok((app_errors::RPL_TOPIC, vec![]))
     }
 
-    /// Given self returns a copy of the topic of the channel
-    pub fn topic(&mut self) -> Option<String> {
-        self.topic.clone()
-    }
-
     /// Given self and a nick returns whether the user is an operator on the channel
-    pub fn is_oper(&mut self, nick: &String) -> bool {
-        self.operators.contains(nick)
+    pub fn is_oper(&mut self, nick: &str) -> bool {
+        self.operators.contains(&self.irc_name(nick))
     }
 
     /// Given self and a nick returns whether the user is present on the channel
     pub fn contains_client(&mut self, user: &str) -> bool {
-        self.users.contains_key(user)
+        self.users.contains_key(&self.irc_name(user))
     }
 
     /// Given self returns whether the channel is secret mode
@@ -201,6 +321,21 @@ impl Channel {
         self.invited_only
     }
 
+    /// Given self returns whether the channel is in moderated mode
+    pub fn is_moderated(&mut self) -> bool {
+        self.moderated
+    }
+
+    /// Given self returns whether only operators can change the topic
+    pub fn is_topic_locked(&mut self) -> bool {
+        self.topic_lock
+    }
+
+    /// Given self returns whether users that are not members can't send to the channel
+    pub fn is_no_external(&mut self) -> bool {
+        self.no_external
+    }
+
     /// Given self returns the current limit of users on the channel
     /// The limit will be none in case of unlimited access
     pub fn get_limit(&mut self) -> Option<usize> {
@@ -210,13 +345,87 @@ impl Channel {
     /// Given self and a nick, tries to add the user to the list of operators
     /// If the given user is already an operator an error will be returned
     pub fn add_oper(&mut self, nick: String) -> DefaultAndError {
-        if self.users.contains_key(&nick) {
-            self.operators.insert(nick);
+        // se guarda el nick tal como lo tiene el miembro
+        if let Some((member, _stream)) = self.users.get_key_value(&self.irc_name(&nick)) {
+            self.operators.insert(member.clone());
             return Ok(());
         }
         Err((app_errors::ERR_NOSUCHNICK, vec![nick]))
     }
 
+    /// Given self, a mode and a nick gives (+v) or takes (-v) voice to the user
+    /// If the given user is not on the channel an error will be returned
+    pub fn mode_voice(&mut self, mode: Mode, nick: String) -> DefaultAndError {
+        let nick = match self.users.get_key_value(&self.irc_name(&nick)) {
+            Some((member, _stream)) => member.clone(),
+            None => return Err((app_errors::ERR_NOSUCHNICK, vec![nick])),
+        };
+        match mode {
+            Mode::Activate(_) => self.voiced.insert(nick),
+            Mode::Deactivate(_) => self.voiced.remove(&nick),
+        };
+        Ok(())
+    }
+
+    /// Given self and the 'nick!user@host' of a user returns whether they match a ban and no exception
+    pub fn is_banned(&mut self, mask: &str) -> bool {
+        self.bans
+            .iter()
+            .any(|ban| mask::matches(ban, mask, self.casemapping))
+            && !self
+                .exceptions
+                .iter()
+                .any(|exception| mask::matches(exception, mask, self.casemapping))
+    }
+
+    /// Given self returns a copy of the ban masks
+    pub fn get_bans(&mut self) -> Vec<String> {
+        self.bans.clone()
+    }
+
+    /// Given self returns a copy of the ban exception masks
+    pub fn get_exceptions(&mut self) -> Vec<String> {
+        self.exceptions.clone()
+    }
+
+    /// Given self, a mode and a mask adds (+b) or removes (-b) the ban.
+    /// Returns the mask as it was stored.
+    pub fn mode_ban(
+        &mut self,
+        mode: Mode,
+        ban: String,
+    ) -> Result<String, ((i32, &'static str), Vec<String>)> {
+        Self::update_mask_list(&mut self.bans, mode, ban)
+    }
+
+    /// Given self, a mode and a mask adds (+e) or removes (-e) the ban exception.
+    /// Returns the mask as it was stored.
+    pub fn mode_exception(
+        &mut self,
+        mode: Mode,
+        exception: String,
+    ) -> Result<String, ((i32, &'static str), Vec<String>)> {
+        Self::update_mask_list(&mut self.exceptions, mode, exception)
+    }
+
+    /// Auxiliary function for adding or removing a mask from the bans or exceptions
+    fn update_mask_list(
+        list: &mut Vec<String>,
+        mode: Mode,
+        mask: String,
+    ) -> Result<String, ((i32, &'static str), Vec<String>)> {
+        let mask = mask::normalize(&mask);
+        match mode {
+            Mode::Activate(_) => {
+                if !list.contains(&mask) {
+                    list.push(mask.clone());
+                }
+            }
+            Mode::Deactivate(_) => list.retain(|value| *value != mask),
+        }
+        Ok(mask)
+    }
+
     /// Given self and a mode changes the channel status corresponding to the secret object
     pub fn mode_secret(&mut self, mode: Mode) -> DefaultAndError {
         match mode {
@@ -226,6 +435,77 @@ impl Channel {
         Ok(())
     }
 
+    /// Given self and a mode changes the channel status corresponding to the moderated object
+    pub fn mode_moderated(&mut self, mode: Mode) -> DefaultAndError {
+        match mode {
+            Mode::Activate(_) => self.moderated = true,
+            Mode::Deactivate(_) => self.moderated = false,
+        }
+        Ok(())
+    }
+
+    /// Given self, a mode and the key sets (+k) or removes (-k) the channel key
+    pub fn mode_key(&mut self, mode: Mode, key: Option<String>) -> DefaultAndError {
+        match mode {
+            Mode::Activate(_) => match key {
+                Some(key) => self.key = Some(key),
+                None => return Err((app_errors::ERR_NEEDMOREPARAMS, vec!["MODE +k".to_string()])),
+            },
+            Mode::Deactivate(_) => self.key = None,
+        }
+        Ok(())
+    }
+
+    /// Given self and whether the key must be shown returns the current channel modes
+    /// as a mode string followed by their arguments (e.g. "+ntkl secret 10")
+    pub fn get_mode_string(&mut self, show_key: bool) -> String {
+        let mut modes = "+".to_string();
+        let mut params = Vec::new();
+        let flags = [
+            ('i', self.invited_only),
+            ('m', self.moderated),
+            ('n', self.no_external),
+            ('s', self.secret),
+            ('t', self.topic_lock),
+        ];
+        for (flag, active) in flags {
+            if active {
+                modes.push(flag);
+            }
+        }
+        if let Some(key) = &self.key {
+            modes.push('k');
+            params.push(match show_key {
+                true => key.clone(),
+                false => "*".to_string(),
+            });
+        }
+        if let Some(limit) = self.limit {
+            modes.push('l');
+            params.push(limit.to_string());
+        }
+        params.insert(0, modes);
+        params.join(" ")
+    }
+
+    /// Given self and a mode changes whether only operators can change the topic
+    pub fn mode_topic_lock(&mut self, mode: Mode) -> DefaultAndError {
+        match mode {
+            Mode::Activate(_) => self.topic_lock = true,
+            Mode::Deactivate(_) => self.topic_lock = false,
+        }
+        Ok(())
+    }
+
+    /// Given self and a mode changes whether users that are not members can send to the channel
+    pub fn mode_no_external(&mut self, mode: Mode) -> DefaultAndError {
+        match mode {
+            Mode::Activate(_) => self.no_external = true,
+            Mode::Deactivate(_) => self.no_external = false,
+        }
+        Ok(())
+    }
+
     /// Given self and a mode changes the channel status corresponding to the mode object
     pub fn mode_invite(&mut self, mode: Mode) -> DefaultAndError {
         match mode {
@@ -261,19 +541,14 @@ impl Channel {
     }
 
     /// Auxiliary function to assist with sending channel messages.
-    /// Given the messages and the stream tries to send the message
-    fn write_message(msg: String, stream: Arc<Mutex<TcpStream>>) {
-        let mut stream = match stream.lock() {
-            Ok(stream) => stream,
-            Err(err) => {
-                eprintln!("Server error (locking stream): {err}");
-                std::process::exit(1);
+    /// Given the messages and the stream queues the message to be sent.
+    /// A member that can't keep up is disconnected by its own connection, the channel is not affected.
+    fn write_message(msg: String, stream: Arc<Mutex<Connection>>) {
+        match stream.lock() {
+            Ok(mut stream) => {
+                let _ = stream.write_all(msg.as_bytes());
             }
-        };
-
-        if let Err(err) = stream.write(msg.as_bytes()) {
-            eprintln!("Server error: {err}");
-            std::process::exit(1);
+            Err(err) => eprintln!("Server error (locking stream): {err}"),
         }
     }
 }
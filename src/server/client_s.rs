@@ -1,20 +1,29 @@
 mod client_status;
 pub mod message;
 
+use super::connection::Connection;
+use super::flood::{Flood, FloodControl};
+use super::link::Linker;
 use super::logger::Logger;
+use super::operator::Privilege;
+use super::password;
+use super::reply::{Reply, ReplyFormat};
+use super::tls::Stream;
 use super::ClientsInfo;
 use crate::app_errors;
 use client_status::ClientStatus;
 use message::command::{Command, Mode};
 use message::Message;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+// largo maximo de una linea, contando el fin de linea (RFC 1459 seccion 2.3)
+const MAX_LINE_LEN: usize = 512;
 
 #[derive(Clone)]
 /// Struct representing a conection to the server.
@@ -24,34 +33,47 @@ pub struct ClientS {
     nick: Option<String>,
     pub user: Option<String>,
     pub realname: Option<String>,
-    stream: Option<Arc<Mutex<TcpStream>>>,
+    stream: Option<Arc<Mutex<Connection>>>,
     clients: Arc<Mutex<ClientsInfo>>,
     status: ClientStatus,
     server_name: Option<String>, //if Some, is a server.
     last_nick: String,
     last_hopcount: i32,
+    last_nick_time: Option<u64>,
+    lost_nicks: HashSet<String>, //users of the other server that lost a nick collision, their lines are dropped
+    lost_channels: HashSet<String>, //channels of the other server newer than ours, the modes it sends are dropped
     trusted_servers: HashMap<String, Option<String>>,
     logger: Arc<Mutex<Logger>>,
+    local_name: String, //name of the server this connection belongs to, used as reply prefix
+    reply_format: ReplyFormat,
+    ping_timeout: Duration,
+    last_activity: Instant, //last time something was read from the connection
+    ping_sent: bool,
+    flood: FloodControl,
+    linker: Option<Linker>, //used by CONNECT, None if the server can't open links
 }
 
 impl ClientS {
     /// Given the ClientsInfo lock, the new stream, the list of trusted servers and the logger lock creates a new ClientS entity
     pub fn new(
         clients: Arc<Mutex<ClientsInfo>>,
-        stream: Arc<Mutex<TcpStream>>,
+        stream: Arc<Mutex<Connection>>,
         trusted_servers: HashMap<String, Option<String>>,
         logger: Arc<Mutex<Logger>>,
     ) -> Result<ClientS, Box<dyn Error>> {
-        match stream.lock() {
-            Ok(stream) => {
-                stream.set_read_timeout(Some(Duration::from_nanos(1)))?;
-            }
+        let (local_name, reply_format, ping_timeout, flood_limits) = match clients.lock() {
+            Ok(clients) => (
+                clients.server_name(),
+                clients.reply_format(),
+                clients.ping_timeout(),
+                clients.flood_limits(),
+            ),
             Err(_) => {
                 return Err(Box::new(app_errors::ApplicationError(
-                    "Error while locking the stream".into(),
+                    "Error while locking the clients".into(),
                 )))
             }
-        }
+        };
 
         Ok(ClientS {
             pass: None,
@@ -64,20 +86,75 @@ impl ClientS {
             server_name: None,
             last_nick: String::new(),
             last_hopcount: 0,
+            last_nick_time: None,
+            lost_nicks: HashSet::new(),
+            lost_channels: HashSet::new(),
             trusted_servers,
             logger,
+            local_name,
+            reply_format,
+            ping_timeout,
+            last_activity: Instant::now(),
+            ping_sent: false,
+            flood: FloodControl::new(flood_limits, Instant::now()),
+            linker: None,
         })
     }
 
+    /// Given the linker of the server lets the operators of this connection link it to other servers
+    pub fn set_linker(&mut self, linker: Linker) {
+        self.linker = Some(linker);
+    }
+
     /// Main loop of the client on the server.
-    /// Reads the message from stream, builds it and executes the commands
+    /// Reads the message from stream, builds it and executes the commands.
+    /// If the connection is lost (or doesn't answer the pings) it's removed from the server.
     pub fn run(&mut self) -> std::io::Result<()> {
         println!("Cliente iniciado. Esperando mensajes.");
-        while let Ok(line) = self.read_from_stream() {
+        let mut reader = match self.reader() {
+            Ok(reader) => reader,
+            Err(_) => {
+                self.drop_connection("Connection closed".to_string());
+                return Ok(());
+            }
+        };
+        // si el host tiene un K-line la conexion se rechaza apenas se acepta
+        let kline = match self.server_name {
+            Some(_) => None,
+            None => self
+                .clients
+                .lock()
+                .expect("Error: poisoned clients lock while checking klines")
+                .host_kline(&self.host()),
+        };
+        if let Some(reason) = kline {
+            self.refuse_klined(reason);
+            return Ok(());
+        }
+        let mut pending = Vec::new();
+        loop {
+            let line = match self.read_from_stream(&mut reader, &mut pending) {
+                Ok(line) => line,
+                Err(err) => {
+                    let reason = match err.downcast_ref::<app_errors::ApplicationError>() {
+                        Some(app_errors::ApplicationError(reason)) => reason.clone(),
+                        None => err.to_string(),
+                    };
+                    self.drop_connection(reason);
+                    break;
+                }
+            };
+            if !line.trim().is_empty() && !self.check_flood() {
+                self.drop_connection("Excess Flood".to_string());
+                break;
+            }
             let message = Message::build(line.clone()).expect("Error reading from stream");
-            if let Command::Invalid(_) = message.command {
-                // debería escribir en el stream indicandole al cliente que hubo un error. Los mensajes de http (como 200, 400, etc) cuando se escriban en el TcpStream van a ser indicados como si fuese un error
+            if let Command::Invalid(reply) = message.command {
                 println!("Comando inválido. Mensaje recibido: {}", line);
+                // las lineas vacias se ignoran en silencio
+                if !line.trim().is_empty() {
+                    let _ = self.execute_invalid(reply);
+                }
                 continue;
             } else {
                 self.logger
@@ -90,66 +167,191 @@ impl ClientS {
         Ok(())
     }
 
-    /// Auxiliary function for reading from stream and handling error that may occur
-    fn read_from_stream(&mut self) -> Result<String, Box<dyn Error>> {
-        let mut line = String::new();
-        let mut char = [b'\n'];
+    /// Given self returns a reader over its own handle of the connection.
+    /// Reads don't lock the stream, so other threads can keep writing to it while this one waits.
+    fn reader(&self) -> Result<BufReader<Stream>, Box<dyn Error>> {
+        let stream = self.stream.clone().ok_or("Connection closed")?;
+        let stream = match stream.lock() {
+            Ok(stream) => stream.try_clone_stream()?,
+            Err(_) => {
+                return Err(Box::new(app_errors::ApplicationError(
+                    "locking stream".into(),
+                )))
+            }
+        };
+        Ok(BufReader::new(stream))
+    }
 
-        if let Some(stream) = self.stream.clone() {
-            // lee cada caracter que escriba el usuario
-            // detiene el while cuando el usuario aprete 'enter'
-            while char[0] == b'\n' {
-                thread::sleep(Duration::from_nanos(1));
-                let stream = stream.lock();
-                if stream.is_err() {
+    /// Auxiliary function for reading a whole line from the connection (without the line ending).
+    /// The read blocks until a line arrives, only waking up when the connection must be pinged.
+    /// Bytes of an incomplete line are kept in pending until the rest arrives, a line longer than
+    /// MAX_LINE_LEN is an error. Only whole lines count as activity of the connection.
+    fn read_from_stream(
+        &mut self,
+        reader: &mut BufReader<Stream>,
+        pending: &mut Vec<u8>,
+    ) -> Result<String, Box<dyn Error>> {
+        loop {
+            // si ejecuto QUIT ya no tiene stream
+            if self.stream.is_none() {
+                return Err(Box::new(app_errors::ApplicationError(
+                    "Connection closed".into(),
+                )));
+            }
+            let timeout = self.check_idle()?;
+            reader.get_ref().set_read_timeout(Some(timeout))?;
+            // nunca se lee mas de lo que le falta a la linea para llegar al maximo
+            let limit = (MAX_LINE_LEN - pending.len()) as u64;
+            let result = reader.by_ref().take(limit).read_until(b'\n', pending);
+            match result {
+                Ok(0) => {
                     return Err(Box::new(app_errors::ApplicationError(
                         "Connection closed".into(),
-                    )));
-                }
-                // guarda lo leido del buffer en 'char'
-                if stream
-                    .expect("Error finding stream")
-                    .read_exact(&mut char)
-                    .is_err()
-                {
-                    continue;
+                    )))
                 }
-            }
-            let mut stream = match stream.lock() {
-                Ok(stream) => stream,
+                Ok(_) if pending.ends_with(b"\n") => {
+                    self.last_activity = Instant::now();
+                    self.ping_sent = false;
+                    let line = String::from_utf8_lossy(pending)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string();
+                    pending.clear();
+                    return Ok(line);
+                }
+                Ok(_) if pending.len() >= MAX_LINE_LEN => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "Line too long".into(),
+                    )))
+                }
+                Ok(_) => continue,
+                Err(err) if Self::is_timeout(&err) => continue,
                 Err(_) => {
                     return Err(Box::new(app_errors::ApplicationError(
-                        "locking stream".into(),
+                        "Connection closed".into(),
                     )))
                 }
-            };
-            // lee caracter por caracter hasta que haya un salto de linea
-            while char[0] != b'\n' {
-                line.push_str(&String::from_utf8(Vec::from(char))?);
-                char = [b'\n'];
-                if stream.read_exact(&mut char).is_err() {
-                    continue;
+            }
+        }
+    }
+
+    /// Takes a token of the flood control for the message just read, waiting for the bucket
+    /// to refill if it's empty. Returns false if the connection is flooding and must be dropped.
+    /// The links to other servers and the operators are never throttled.
+    fn check_flood(&mut self) -> bool {
+        if self.server_name.is_some() || self.status == ClientStatus::Oper {
+            return true;
+        }
+        match self.flood.check(Instant::now()) {
+            Flood::Allowed => true,
+            Flood::Throttled(wait) => {
+                thread::sleep(wait);
+                true
+            }
+            Flood::Excess => false,
+        }
+    }
+
+    /// Given a read error returns whether it only means there was nothing to read yet
+    fn is_timeout(err: &io::Error) -> bool {
+        matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+        )
+    }
+
+    /// Checks how long the connection has been idle and returns how long it can wait for the next read.
+    /// After ping_timeout a PING is sent, if nothing arrives in another ping_timeout
+    /// the connection is considered dead and an error is returned.
+    fn check_idle(&mut self) -> Result<Duration, Box<dyn Error>> {
+        let idle = self.last_activity.elapsed();
+        if idle >= self.ping_timeout * 2 {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Ping timeout".into(),
+            )));
+        }
+        let mut deadline = self.ping_timeout;
+        if idle >= self.ping_timeout {
+            if !self.ping_sent {
+                self.ping_sent = true;
+                self.write_line(format!("PING :{}", self.local_name))?;
+            }
+            deadline = self.ping_timeout * 2;
+        }
+        // un timeout de cero no es valido para el socket
+        Ok((deadline - idle).max(Duration::from_millis(1)))
+    }
+
+    /// Given the reason, removes a connection that was lost without a QUIT.
+    /// Registered users are quit from the server (and its neighbours) so their nick can be used again.
+    fn drop_connection(&mut self, mut reason: String) {
+        // si ejecuto QUIT ya no tiene stream y no hay nada que hacer
+        let stream = match self.stream.clone() {
+            Some(stream) => stream,
+            None => return,
+        };
+        // si la cerro el servidor (ej. SendQ exceeded) se usa ese motivo
+        if let Some(close_reason) = stream.lock().ok().and_then(|stream| stream.close_reason()) {
+            reason = close_reason;
+        }
+        if let Some(server_name) = self.server_name.clone() {
+            println!("Lost link with server {}: {}", server_name, reason);
+            self.clients
+                .lock()
+                .expect("Error: poisoned clients lock while dropping connection")
+                .netsplit(&server_name);
+        } else if self.is_registered() {
+            if let Some(nick) = self.nick.clone() {
+                let mut clients = self
+                    .clients
+                    .lock()
+                    .expect("Error: poisoned clients lock while dropping connection");
+                // si fue expulsado con KILL (o por una colision) ya no esta en el servidor
+                // y su nick puede ser ahora el de otro usuario
+                let quit = clients.is_connection_of(&nick, &stream)
+                    && clients
+                        .quit_client(nick.clone(), Some(reason.clone()), None)
+                        .is_ok();
+                drop(clients);
+                if quit {
+                    self.logger
+                        .lock()
+                        .expect("Error: log lock poisoned")
+                        .write(format!("{}: QUIT {}", nick, reason));
                 }
             }
-            return Ok(line);
         }
-        Err(Box::new(app_errors::ApplicationError(
-            "Connection closed".into(),
-        )))
+        let _ = self.write_line(format!("ERROR :Closing Link: {}", reason));
+        if let Ok(stream) = stream.lock() {
+            stream.shutdown();
+        }
+        self.stream = None;
+    }
+
+    /// Given the reason of the K-line that bans the connection sends ERR_YOUREBANNEDCREEP and closes it
+    fn refuse_klined(&mut self, reason: String) {
+        let _ = self.return_code((app_errors::ERR_YOUREBANNEDCREEP, vec![]));
+        self.drop_connection(format!("{} (K-lined: {})", self.host(), reason));
     }
 
     /// Function responsible of executing the correct function given a message object
     fn run_command(&mut self, message: Message) -> Result<(), Box<dyn Error>> {
+        if self.lost_collision(&message) {
+            return Ok(());
+        }
         match message.command {
             Command::Pass(pass) => self.execute_pass(pass),
-            Command::Nick(nick, hopcount) => self.execute_nick(nick, hopcount),
+            Command::Nick(nick, hopcount, nick_time) => {
+                self.execute_nick(nick, hopcount, nick_time, message.prefix)
+            }
             Command::User(username, realname) => self.execute_user(username, realname),
             Command::Privmsg(receiver, msg) => self.execute_privmsg(receiver, msg, message.prefix),
             Command::Notice(receiver, msg) => self.execute_notice(receiver, msg),
             Command::Quit(msg) => self.execute_quit(message.prefix, msg),
             Command::Oper(user, password) => self.execute_oper(user, password),
             Command::Invalid(err) => self.execute_invalid(err),
-            Command::Join(channels, keys) => self.execute_join(channels, keys, message.prefix),
+            Command::Join(channels, keys, created) => {
+                self.execute_join(channels, keys, created, message.prefix)
+            }
             Command::Names(channels) => self.execute_names(channels),
             Command::Part(channels) => self.execute_part(channels, message.prefix),
             Command::Kick(channel, user, comment) => {
@@ -161,13 +363,63 @@ impl ClientS {
             }
             Command::Topic(channel, new_topic) => self.execute_topic(channel, new_topic),
             Command::Who(mask, _) => self.execute_who(mask),
-            Command::Whois(mask) => self.execute_whois(mask),
-            Command::Server(name, hopcount, info) => self.execute_server(name, hopcount, info),
+            Command::Whois(server, nick) => {
+                self.execute_query(Command::Whois(None, nick), server, message.prefix)
+            }
+            Command::Server(name, hopcount, info) => {
+                self.execute_server(name, hopcount, info, message.prefix)
+            }
             Command::Squit(server_name, comment) => self.execute_squit(server_name, comment),
             Command::Away(msg) => self.execute_away(msg),
-            Command::Mode(channel_name, mode, params) => {
-                self.execute_mode(channel_name, mode, params, message.prefix)
+            Command::Ping(origin, target) => self.execute_ping(origin, target),
+            Command::Pong(origin, target) => self.execute_pong(origin, target),
+            Command::Mode(target, modes) => {
+                if target.starts_with('#') || target.starts_with('&') {
+                    self.execute_mode(target, modes, message.prefix)
+                } else {
+                    self.execute_user_mode(target, modes, message.prefix)
+                }
+            }
+            Command::Wallops(text) => self.execute_wallops(text, message.prefix),
+            Command::Kill(nick, comment) => self.execute_kill(nick, comment, message.prefix),
+            Command::Kline(mask, minutes, reason) => self.execute_kline(mask, minutes, reason),
+            Command::Unkline(mask) => self.execute_unkline(mask),
+            Command::Connect(target, port) => self.execute_connect(target, port),
+            Command::Links(mask) => self.execute_links(mask),
+            Command::Map => self.execute_map(),
+            Command::Version(server) => {
+                self.execute_query(Command::Version(None), server, message.prefix)
+            }
+            Command::Time(server) => {
+                self.execute_query(Command::Time(None), server, message.prefix)
+            }
+            Command::Admin(server) => {
+                self.execute_query(Command::Admin(None), server, message.prefix)
+            }
+            Command::Info(server) => {
+                self.execute_query(Command::Info(None), server, message.prefix)
+            }
+            Command::Reply(numeric, target, text) => {
+                self.execute_reply(numeric, target, text, message.prefix)
+            }
+        }
+    }
+
+    /// Given a message of a server link returns whether it's about a user or channel of the other server that
+    /// lost a collision against ours: the lines of that user and the modes the server sends for that channel
+    fn lost_collision(&self, message: &Message) -> bool {
+        let prefix = match (&self.server_name, &message.prefix) {
+            (Some(_), Some(prefix)) => prefix,
+            _ => return false,
+        };
+        if self.lost_nicks.contains(prefix) {
+            return true;
+        }
+        match &message.command {
+            Command::Mode(target, _) => {
+                self.server_name.as_ref() == Some(prefix) && self.lost_channels.contains(target)
             }
+            _ => false,
         }
     }
 
@@ -185,44 +437,85 @@ impl ClientS {
     /// Hopcount should be used only in case ClientS is a server, otherwise is always a 0.
     /// Will check if user is already registered and if the same nick is already in use
     /// and will return the appropiate response.
+    /// Registered users are renamed, keeping their channels and modes.
     /// If USER was already executed correctly will register the user
-    fn execute_nick(&mut self, new_nick: String, hopcount: i32) -> Result<(), Box<dyn Error>> {
+    fn execute_nick(
+        &mut self,
+        new_nick: String,
+        hopcount: i32,
+        nick_time: Option<u64>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
         // si se trata de un servidor
         if self.server_name.is_some() {
+            // ':viejo NICK nuevo' es un cambio de nick de un usuario de ese servidor
+            if let Some(old_nick) = prefix {
+                let mut clients = self
+                    .clients
+                    .lock()
+                    .expect("Error: poisoned clients lock during execute nick");
+                if clients.contains_client(&old_nick) {
+                    let result =
+                        clients.rename_client(old_nick, new_nick, self.server_name.clone(), nick_time);
+                    drop(clients);
+                    return match result {
+                        Ok(()) => Ok(()),
+                        Err(error) => self.return_code(error),
+                    };
+                }
+            }
+            self.lost_nicks.remove(&new_nick);
             self.last_nick = new_nick.clone();
             self.last_hopcount = hopcount;
+            self.last_nick_time = nick_time;
             self.nick = Some(new_nick);
             return Ok(());
         }
-        // si ya existe el usuario lo dropea
+        let valid_nick = self
+            .clients
+            .lock()
+            .expect("Error: poisoned clients lock during execute nick")
+            .is_valid_nick(&new_nick);
+        if !valid_nick {
+            return self.return_code((app_errors::ERR_ERRONEUSNICKNAME, vec![new_nick]));
+        }
+        // si ya esta registrado se le cambia el nick
         if self.is_registered() {
-            if let Some(nick) = self.nick.clone() {
-                self.clients
-                    .lock()
-                    .expect("Error: poisoned clients lock during execute nick")
-                    .quit_client(nick, None, self.server_name.clone())?;
-            }
-            self.status = ClientStatus::Unregistered;
+            let old_nick = self.nick.clone().ok_or("no nick")?;
+            let result = self
+                .clients
+                .lock()
+                .expect("Error: poisoned clients lock during execute nick")
+                .rename_client(old_nick, new_nick.clone(), None, None);
+            return match result {
+                Ok(()) => {
+                    self.nick = Some(new_nick);
+                    Ok(())
+                }
+                Err(error) => self.return_code(error),
+            };
         }
         // si ya existe y está conectado tira error
         if let Ok(mut client_guard) = self.clients.lock() {
             if client_guard.contains_client(&new_nick) {
-                return self.return_code((app_errors::ERR_NICKCOLLISION, vec![new_nick]));
+                return self.return_code((app_errors::ERR_NICKNAMEINUSE, vec![new_nick]));
             }
         }
         // agrega el cliente o servidor
         if let Some(_user) = self.user.clone() {
             let client_nick = new_nick.clone();
             if let Some(stream) = self.stream.clone() {
+                if !ClientsInfo::password_matches(&self.clients, &self.pass) {
+                    return self.return_code((app_errors::ERR_PASSWDMISMATCH, vec![]));
+                }
                 let result = self
                     .clients
                     .lock()
                     .expect("Error: poisoned clients lock during execute nick")
-                    .add_client(
+                    .register_client(
                         client_nick,
                         self.clone(),
                         stream,
-                        self.pass.clone(),
                         hopcount,
                         self.server_name.clone(),
                     );
@@ -230,7 +523,7 @@ impl ClientS {
                     Ok(()) => {
                         self.nick = Some(new_nick);
                         self.status = ClientStatus::Registered;
-                        self.return_code((app_errors::RPL_YOUAREIN, vec![]))?;
+                        self.send_welcome()?;
                     }
                     Err(error) => return self.return_code(error),
                 }
@@ -249,20 +542,25 @@ impl ClientS {
         if self.server_name.is_some() {
             self.user = Some(username);
             self.realname = Some(realname);
-            result = self
+            let introduced = self
                 .clients
                 .lock()
                 .expect("Error: poisoned clients lock during execute user")
-                .add_client(
+                .introduce_client(
                     self.last_nick.clone(),
                     self.clone(),
                     self.stream.clone().ok_or("no stream for execute user")?,
-                    self.pass.clone(),
                     self.last_hopcount,
-                    self.server_name.clone(),
+                    self.server_name.clone().ok_or("no server name")?,
+                    self.last_nick_time,
                 );
-            match result {
-                Ok(()) => return Ok(()),
+            match introduced {
+                Ok(true) => return Ok(()),
+                Ok(false) => {
+                    // el otro servidor mata a su usuario al recibir el nuestro
+                    self.lost_nicks.insert(self.last_nick.clone());
+                    return Ok(());
+                }
                 Err(error) => return self.return_code(error),
             }
         }
@@ -271,23 +569,35 @@ impl ClientS {
         if self.is_registered() {
             return self.return_code((app_errors::ERR_ALREADYREGISTRED, vec![]));
         }
+        let kline = self
+            .clients
+            .lock()
+            .expect("Error: poisoned clients lock while checking klines")
+            .user_kline(&username, &self.host());
+        if let Some(reason) = kline {
+            self.refuse_klined(reason);
+            return Ok(());
+        }
+        if !self.listen_accepts(false) {
+            return self.return_code((app_errors::ERR_NOPERMFORHOST, vec![]));
+        }
         self.user = Some(username);
         self.realname = Some(realname);
 
         if let Some(nick) = self.nick.clone() {
             if let Some(stream) = self.stream.clone() {
-                result = self.clients.lock().expect("locking stream").add_client(
-                    nick,
-                    self.clone(),
-                    stream,
-                    self.pass.clone(),
-                    0,
-                    self.server_name.clone(),
-                );
+                if !ClientsInfo::password_matches(&self.clients, &self.pass) {
+                    return self.return_code((app_errors::ERR_PASSWDMISMATCH, vec![]));
+                }
+                result = self
+                    .clients
+                    .lock()
+                    .expect("locking stream")
+                    .register_client(nick, self.clone(), stream, 0, self.server_name.clone());
                 match result {
                     Ok(()) => {
                         self.status = ClientStatus::Registered;
-                        self.return_code((app_errors::RPL_YOUAREIN, vec![]))?;
+                        self.send_welcome()?;
                         return Ok(());
                     }
                     Err(error) => return self.return_code(error),
@@ -322,20 +632,17 @@ impl ClientS {
         Ok(())
     }
 
-    /// Given a username, password combination will grant the current conection Oper abilities if
-    /// combination is found in the operators log.
+    /// Given the name and password of an operator block will grant the current conection Oper abilities
+    /// (with the privileges of the block) if the block allows its host and the password matches.
     fn execute_oper(&mut self, username: String, password: String) -> Result<(), Box<dyn Error>> {
-        if !self.is_registered()
-            || self.nick.clone().expect("Error obtaining oper nick") != username
-        {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+        if !self.is_registered() {
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
-        match self
-            .clients
-            .lock()
-            .expect("Error obtaining clients")
-            .oper_login(&username, &password)
-        {
+        // el nombre del bloque de operador no tiene que ser el nick
+        let nick = self.nick.clone().ok_or("Error obtaining oper nick")?;
+        let user = self.user.clone().unwrap_or_default();
+        let host = self.host();
+        match ClientsInfo::oper_login(&self.clients, &nick, &username, &password, &user, &host) {
             Ok(result) => {
                 self.status = ClientStatus::Oper;
                 self.return_code(result)
@@ -355,10 +662,10 @@ impl ClientS {
         prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() && self.server_name.is_none() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         if self.server_name.is_some() {
-            if self
+            let _ = self
                 .clients
                 .lock()
                 .expect("Error obtaining clients")
@@ -367,9 +674,7 @@ impl ClientS {
                     receiver_name,
                     msg,
                     self.server_name.clone(),
-                )
-                .is_err()
-            {}
+                );
             return Ok(());
         }
         if let Some(nick) = self.nick.clone() {
@@ -410,6 +715,7 @@ impl ClientS {
         &mut self,
         channels: Vec<String>,
         channels_keys: Vec<Option<String>>,
+        created: Option<u64>,
         prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if self.server_name.is_some() {
@@ -418,17 +724,29 @@ impl ClientS {
                 if !channel.starts_with('#') && !channel.starts_with('&') {
                     continue;
                 }
-                let result = self
-                    .clients
-                    .lock()
-                    .expect("error during locking")
-                    .join_channel(
-                        nick,
-                        None,
-                        channel.clone(),
-                        channels_keys[i].clone(),
-                        self.server_name.clone(),
-                    );
+                let mut clients = self.clients.lock().expect("error during locking");
+                // si ya existe se queda el canal mas antiguo, si es el nuestro se ignoran sus modos
+                let existed = clients.contains_channel(channel);
+                if let Some(created) = created.filter(|_| existed) {
+                    if clients.merge_channel_time(channel, created) {
+                        self.lost_channels.insert(channel.clone());
+                    } else {
+                        self.lost_channels.remove(channel);
+                    }
+                }
+                // ',' es un canal sin clave, al separar las claves queda vacia
+                let key = channels_keys[i].clone().filter(|key| !key.is_empty());
+                let result = clients.join_channel(
+                    nick,
+                    None,
+                    channel.clone(),
+                    key,
+                    self.server_name.clone(),
+                );
+                if let Some(created) = created.filter(|_| !existed) {
+                    clients.set_channel_time(channel, created);
+                }
+                drop(clients);
                 match result {
                     Ok(code) => return self.return_code(code),
                     Err(code) => return self.return_code(code),
@@ -438,7 +756,13 @@ impl ClientS {
         if let Some(nick) = self.nick.clone() {
             //recorre la lista de canales que el usuario solicito unirse
             for (i, channel) in channels.iter().enumerate() {
-                if !channel.starts_with('#') && !channel.starts_with('&') {
+                let valid_channel = self
+                    .clients
+                    .lock()
+                    .expect("error during lock")
+                    .is_valid_channel(channel);
+                if !valid_channel {
+                    self.return_code((app_errors::ERR_NOSUCHCHANNEL, vec![channel.clone()]))?;
                     continue;
                 }
                 let result = self
@@ -453,7 +777,18 @@ impl ClientS {
                         self.server_name.clone(),
                     );
                 match result {
-                    Ok(code) => return self.return_code(code),
+                    Ok(code) => {
+                        self.return_code(code)?;
+                        let topic_who_time = self
+                            .clients
+                            .lock()
+                            .expect("error during lock")
+                            .topic_who_time(channel);
+                        if let Some(topic_who_time) = topic_who_time {
+                            return self.return_code(topic_who_time);
+                        }
+                        return Ok(());
+                    }
                     Err(code) => return self.return_code(code),
                 }
             }
@@ -464,7 +799,7 @@ impl ClientS {
     /// Given a list of channels will return to the sender a list of all members for each channel
     fn execute_names(&mut self, channels: Vec<String>) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         self.clients
             .lock()
@@ -476,7 +811,7 @@ impl ClientS {
     /// Given a list of channels will return to the sender a list of all channels and their topic
     fn execute_list(&mut self, channels: Vec<String>) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         self.clients
             .lock()
@@ -493,7 +828,7 @@ impl ClientS {
         prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() && self.server_name.is_none() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         match self.clients.lock().expect("error during lock").send_invite(
             channel,
@@ -514,7 +849,7 @@ impl ClientS {
         prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() && self.server_name.is_none() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         match self
             .clients
@@ -541,7 +876,7 @@ impl ClientS {
         prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() && self.server_name.is_none() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         match self
             .clients
@@ -567,66 +902,52 @@ impl ClientS {
         new_topic: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
-        if let Some(stream) = self.stream.clone() {
-            match self
-                .clients
-                .lock()
-                .expect("Error obtaining clients during topic")
-                .topic(
-                    channel,
-                    new_topic,
-                    self.nick.clone().expect("Error executing topic"),
-                ) {
-                Ok(Some(mut topic)) => {
-                    topic.push('\n');
-                    let mut stream = match stream.lock() {
-                        Ok(stream) => stream,
-                        Err(_) => {
-                            return Err(Box::new(app_errors::ApplicationError(
-                                "locking stream".into(),
-                            )))
-                        }
-                    };
-                    stream.write_all(topic.as_bytes())?;
+        let result = self
+            .clients
+            .lock()
+            .expect("Error obtaining clients during topic")
+            .topic(
+                channel,
+                new_topic,
+                self.nick.clone().expect("Error executing topic"),
+            );
+        match result {
+            Ok(replies) => {
+                for reply in replies {
+                    self.return_code(reply)?;
                 }
-                Ok(None) => return Ok(()),
-                Err(err) => return self.return_code(err),
-            };
+                Ok(())
+            }
+            Err(err) => self.return_code(err),
         }
-        Ok(())
     }
 
     /// Returns the corresponding error of the invalid command passed
+    /// Servers are not answered, since they don't expect numeric replies.
     fn execute_invalid(
         &self,
         reply: ((i32, &'static str), Vec<String>),
     ) -> Result<(), Box<dyn Error>> {
-        let ((number, text), params) = reply;
-        let mut reply = text;
-        let mut aux;
-        for param in params {
-            aux = reply.replacen("{}", param.as_str(), 1);
-            reply = aux.as_str();
-        }
         if self.server_name.is_some() {
-        } else if let Some(stream) = self.stream.clone() {
-            let mut stream = match stream.lock() {
-                Ok(stream) => stream,
-                Err(_) => {
-                    return Err(Box::new(app_errors::ApplicationError(
-                        "locking stream".into(),
-                    )))
-                }
-            };
-            stream.write_all(format!("{} {}\n", number, reply).as_bytes())?;
+            return Ok(());
         }
-        Ok(())
+        self.return_code(reply)
     }
 
     /// Auxiliary code that return the code with the explainatory message
+    /// The reply is written in the format configured for the server, RPL_NONE is never written.
     fn return_code(&self, reply: ((i32, &'static str), Vec<String>)) -> Result<(), Box<dyn Error>> {
+        let reply = Reply::from(reply);
+        if reply.is_none() {
+            return Ok(());
+        }
+        self.write_line(reply.format(self.reply_format, &self.local_name, &self.reply_target()))
+    }
+
+    /// Given a line (without the line ending) writes it to the connection
+    fn write_line(&self, line: String) -> Result<(), Box<dyn Error>> {
         if let Some(stream) = self.stream.clone() {
             let mut stream = match stream.lock() {
                 Ok(stream) => stream,
@@ -636,18 +957,69 @@ impl ClientS {
                     )))
                 }
             };
-            let ((number, text), params) = reply;
-            let mut reply = text;
-            let mut aux;
-            for param in params {
-                aux = reply.replacen("{}", param.as_str(), 1);
-                reply = aux.as_str();
-            }
-            stream.write_all(format!("{} {}\n", number, reply).as_bytes())?;
+            stream.write_all(format!("{}\n", line).as_bytes())?;
         }
         Ok(())
     }
 
+    /// Sends the registration burst (RPL_WELCOME, ..., MOTD) to a newly registered user
+    fn send_welcome(&self) -> Result<(), Box<dyn Error>> {
+        let burst = self
+            .clients
+            .lock()
+            .expect("Error: poisoned clients lock during welcome")
+            .registration_burst(self.client_mask());
+        for reply in burst {
+            self.return_code(reply)?;
+        }
+        Ok(())
+    }
+
+    /// Given self returns the receiver used in the numeric replies:
+    /// the nick (or server name) of the connection, or '*' if it has none yet.
+    fn reply_target(&self) -> String {
+        match (self.nick.clone(), self.server_name.clone()) {
+            (Some(nick), _) => nick,
+            (None, Some(server_name)) => server_name,
+            (None, None) => "*".to_string(),
+        }
+    }
+
+    /// Given self returns the 'nick!user@host' mask of the connection
+    pub fn client_mask(&self) -> String {
+        format!(
+            "{}!{}@{}",
+            self.nick.clone().unwrap_or_default(),
+            self.user.clone().unwrap_or_default(),
+            self.host()
+        )
+    }
+
+    /// Given self returns the ip the connection comes from (or the server name if it's unknown)
+    pub fn host(&self) -> String {
+        if let Some(stream) = self.stream.clone() {
+            if let Ok(Ok(addr)) = stream.lock().map(|stream| stream.peer_addr()) {
+                return addr.ip().to_string();
+            }
+        }
+        self.local_name.clone()
+    }
+
+    /// Given whether the connection is registering as a server returns whether the listener
+    /// it came from accepts it. The connections opened by this server always do
+    fn listen_accepts(&self, server: bool) -> bool {
+        let stream = match self.stream.clone() {
+            Some(stream) => stream,
+            None => return false,
+        };
+        let stream = stream.lock().expect("Error: poisoned stream lock");
+        match stream.listen() {
+            Some(listen) if server => listen.accepts_servers(),
+            Some(listen) => listen.accepts_clients(),
+            None => true,
+        }
+    }
+
     /// Given self will check if the current conection is registered
     fn is_registered(&self) -> bool {
         ClientStatus::Unregistered != self.status
@@ -656,7 +1028,7 @@ impl ClientS {
     /// Given a mask will try to execute the who command
     fn execute_who(&self, mask: String) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         self.clients
             .lock()
@@ -665,38 +1037,50 @@ impl ClientS {
         Ok(())
     }
 
-    /// Given a mask will try to execute the who is command
-    fn execute_whois(&self, mask: String) -> Result<(), Box<dyn Error>> {
-        if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
-        }
-        self.clients
-            .lock()
-            .expect("Error obtainging clients during whois")
-            .whois(mask, self.nick.clone().expect("Error executing whois"))?;
-        Ok(())
-    }
-
     /// Given the new servername with their hopcount will try to register that server.
-    /// If the connection is a server will also relay the command with an increase in hopcount
+    /// If the connection is a server will also relay the command with an increase in hopcount,
+    /// the prefix is the server the new one is linked to
     fn execute_server(
         &mut self,
         name: String,
         hopcount: i32,
         info: String,
+        prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if self.is_registered() {
             return self.return_code((app_errors::ERR_ALREADYREGISTRED, vec![]));
         }
         if let Some(server_name) = self.server_name.clone() {
             if server_name == name {
-                return self.return_code((app_errors::ERR_SERVERCOLLISION, vec![name]));
+                return self.return_code((app_errors::ERR_ALREADYREGISTRED, vec![]));
             }
         }
+        let known = self
+            .clients
+            .lock()
+            .expect("error during lock")
+            .is_known_server(&name);
+        if known && (hopcount == 1 || self.server_name.is_some()) {
+            // llegar a un server conocido por otro camino formaria un ciclo, se corta este enlace
+            if hopcount == 1 {
+                self.return_code((app_errors::ERR_ALREADYREGISTRED, vec![]))?;
+            }
+            self.drop_connection(format!("Server {} already exists", name));
+            return Ok(());
+        }
         if hopcount == 1 {
             //registrando hijo nuevo
-            if !self.trusted_servers.contains_key(&name) {
-                return self.return_code((app_errors::ERR_UNTRUSTEDSERVER, vec![name]));
+            if !self.trusted_servers.contains_key(&name) || !self.listen_accepts(true) {
+                return self.return_code((app_errors::ERR_NOPERMFORHOST, vec![]));
+            }
+            // con contraseña propia en el archivo de confianza se usa esa, si no la del servidor
+            let matches = match (self.trusted_servers.get(&name), &self.pass) {
+                (Some(Some(stored)), Some(pass)) => password::verify(stored, pass),
+                (Some(Some(_)), None) => false,
+                _ => ClientsInfo::password_matches(&self.clients, &self.pass),
+            };
+            if !matches {
+                return self.return_code((app_errors::ERR_PASSWDMISMATCH, vec![]));
             }
             match self
                 .clients
@@ -704,31 +1088,27 @@ impl ClientS {
                 .expect("error during lock")
                 .try_add_server(
                     name.clone(),
-                    self.pass.clone(),
                     self.stream.clone(),
                     hopcount,
                     info,
                     name.clone(),
+                    self.local_name.clone(),
                 ) {
                 Ok(_) => self.server_name = Some(name),
                 Err(reply) => return self.return_code(reply),
             }
         } else {
             //registrando vecinos lejanos
-            match self
-                .clients
-                .lock()
-                .expect("error during lock")
-                .try_add_server(
-                    name,
-                    self.pass.clone(),
-                    None,
-                    hopcount,
-                    info,
-                    self.server_name.clone().ok_or_else(|| {
-                        app_errors::ApplicationError("server name not found".into())
-                    })?,
-                ) {
+            let neighbour = self
+                .server_name
+                .clone()
+                .ok_or_else(|| app_errors::ApplicationError("server name not found".into()))?;
+            let mut clients = self.clients.lock().expect("error during lock");
+            // sin prefijo (o con uno desconocido) se cuelga del vecino por el que llega
+            let uplink = prefix
+                .filter(|uplink| *uplink != self.local_name && clients.is_known_server(uplink))
+                .unwrap_or_else(|| neighbour.clone());
+            match clients.try_add_server(name, None, hopcount, info, neighbour, uplink) {
                 Ok(_) => return Ok(()),
                 Err(((code, text), _params)) => println!("{code} :{text}"), //TODO: AGREGAR PARAMETROS
             }
@@ -770,32 +1150,228 @@ impl ClientS {
         Ok(())
     }
 
-    /// Given the name and password of another server will try to connect the client to the parent server
-    pub fn set_parent(&mut self, server_name: Option<String>, password: Option<String>) {
+    /// Given the name of the server this one registered to and the password of this server
+    /// adds it to the network as a neighbour. If it was already known the link is closed,
+    /// there would be a loop in the network
+    pub fn set_parent(
+        &mut self,
+        server_name: String,
+        password: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
         self.pass = password;
-        self.server_name = server_name.clone();
-        if let Some(server_name) = server_name {
-            let mut client_guard = match self.clients.lock() {
-                Ok(guard) => guard,
-                Err(_) => panic!("locking stream"),
-            };
-            if let Err(((code, text), _params)) = client_guard.try_add_server(
+        let result = match self.clients.lock() {
+            Ok(mut clients) => clients.try_add_server(
                 server_name.clone(),
-                self.pass.clone(),
                 self.stream.clone(),
                 1,
                 "info".to_string(),
-                server_name,
-            ) {
-                println!("{code} :{text}");
+                server_name.clone(),
+                self.local_name.clone(),
+            ),
+            Err(_) => panic!("locking stream"),
+        };
+        if let Err(((code, text), _params)) = result {
+            println!("{code} :{text}");
+            let reason = format!("Server {} already exists", server_name);
+            self.drop_connection(reason.clone());
+            return Err(Box::new(app_errors::ApplicationError(reason)));
+        }
+        self.server_name = Some(server_name);
+        Ok(())
+    }
+
+    /// Given the name of a server of the connect blocks (and optionally another port) links this server to it.
+    /// Only operators with the route privilege can do it
+    fn execute_connect(
+        &mut self,
+        target: String,
+        port: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let nick = match &self.nick {
+            Some(nick) if self.is_registered() => nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let (privileged, known) = {
+            let clients = self.clients.lock().expect("Couldn't lock");
+            (
+                clients.has_privilege(&nick, Privilege::Route),
+                clients.is_known_server(&target),
+            )
+        };
+        if !privileged {
+            return self.return_code((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        let linker = self.linker.clone();
+        let mut block = match linker
+            .as_ref()
+            .and_then(|linker| linker.connect_block(&target))
+        {
+            Some(block) => block,
+            None => return self.return_code((app_errors::ERR_NOSUCHSERVER, vec![target])),
+        };
+        if known {
+            return self.write_line(format!(
+                ":{} NOTICE {} :Connect: Server {} already exists",
+                self.local_name, nick, target
+            ));
+        }
+        if let Some(port) = port {
+            block.port = port;
+        }
+        self.logger
+            .lock()
+            .expect("Error: log lock poisoned")
+            .write(format!(
+                "{}: CONNECT {} {}",
+                nick,
+                target,
+                block.connect_address()
+            ));
+        let result = linker
+            .ok_or("Error obtaining the linker")?
+            .link(&block)
+            .map(|_link| ());
+        match result {
+            Ok(()) => self.write_line(format!(
+                ":{} NOTICE {} :Connect: Linked to {}",
+                self.local_name, nick, target
+            )),
+            Err(err) => self.write_line(format!(
+                ":{} NOTICE {} :Connect: Couldn't link to {}: {}",
+                self.local_name, nick, target, err
+            )),
+        }
+    }
+
+    /// Given the mask of LINKS (optional) sends the registered user the servers of the network that match it
+    fn execute_links(&self, mask: Option<String>) -> Result<(), Box<dyn Error>> {
+        if !self.is_registered() {
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
+        }
+        let replies = self.clients.lock().expect("Couldn't lock").links(mask);
+        for reply in replies {
+            self.return_code(reply)?;
+        }
+        Ok(())
+    }
+
+    /// Sends the operator the tree of the servers of the network as seen from this one
+    fn execute_map(&self) -> Result<(), Box<dyn Error>> {
+        let nick = match &self.nick {
+            Some(nick) if self.is_registered() => nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let replies = self.clients.lock().expect("Couldn't lock").map(&nick);
+        match replies {
+            Ok(replies) => {
+                for reply in replies {
+                    self.return_code(reply)?;
+                }
+                Ok(())
             }
+            Err(err) => self.return_code(err),
         }
     }
 
+    /// Given a query (VERSION, TIME, ADMIN, INFO or WHOIS of a nick), the server it's meant for (this one if missing)
+    /// and the prefix of the message answers it. A query for another server is sent through the link it's
+    /// reached by, with the nick of who asked as prefix, and that server answers them directly.
+    fn execute_query(
+        &self,
+        query: Command,
+        server: Option<String>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.server_name.is_none() && !self.is_registered() {
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
+        }
+        // si llega por un enlace, quien pregunta es el del prefijo
+        let nick = match (&self.server_name, prefix) {
+            (None, _) => self.nick.clone().unwrap_or_default(),
+            (Some(_), Some(prefix)) => prefix,
+            (Some(_), None) => return Ok(()),
+        };
+        let clients = self.clients.lock().expect("Couldn't lock");
+        let replies = match server.filter(|server| *server != self.local_name) {
+            Some(server) => {
+                let line = match &query {
+                    Command::Whois(_, mask) => Command::Whois(Some(server.clone()), mask.clone()),
+                    Command::Version(_) => Command::Version(Some(server.clone())),
+                    Command::Time(_) => Command::Time(Some(server.clone())),
+                    Command::Admin(_) => Command::Admin(Some(server.clone())),
+                    _ => Command::Info(Some(server.clone())),
+                };
+                match clients.route_to_server(&server, format!(":{} {}", nick, line)) {
+                    Ok(()) => return Ok(()),
+                    Err(err) => vec![err],
+                }
+            }
+            None => match query {
+                Command::Whois(_, mask) => clients.whois_replies(&mask),
+                Command::Version(_) => clients.version(),
+                Command::Time(_) => clients.time(),
+                Command::Admin(_) => clients.admin(),
+                _ => clients.info(),
+            },
+        };
+        if self.server_name.is_some() {
+            clients.send_replies(&nick, replies);
+            return Ok(());
+        }
+        drop(clients);
+        for reply in replies {
+            self.return_code(reply)?;
+        }
+        Ok(())
+    }
+
+    /// Given a numeric reply sent through a link (the numeric, the nick it's for, the text and the server
+    /// that sent it as prefix) relays it towards the user, unless the user is behind that same link.
+    /// Users can't send numeric replies
+    fn execute_reply(
+        &self,
+        numeric: String,
+        target: String,
+        text: String,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let server_name = match &self.server_name {
+            Some(server_name) => server_name,
+            None => return self.return_code((app_errors::ERR_UNKNOWNCOMMAND, vec![numeric])),
+        };
+        self.clients.lock().expect("Couldn't lock").relay_reply(
+            server_name,
+            &prefix.unwrap_or(server_name.clone()),
+            &numeric,
+            &target,
+            &text,
+        );
+        Ok(())
+    }
+
+    /// Given the origin of a PING (and optionally the server it's meant for) answers with a PONG.
+    /// Can be used by servers and users, even before registering.
+    fn execute_ping(&self, origin: String, target: Option<String>) -> Result<(), Box<dyn Error>> {
+        if let Some(target) = target {
+            if target != self.local_name {
+                return self.return_code((app_errors::ERR_NOSUCHSERVER, vec![target]));
+            }
+        }
+        self.write_line(format!(
+            ":{} PONG {} :{}",
+            self.local_name, self.local_name, origin
+        ))
+    }
+
+    /// A PONG only proves the connection is alive, the idle time was already reset when reading it
+    fn execute_pong(&self, _origin: String, _target: Option<String>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
     /// Given a message (optionally), will try to execute the away message.
     fn execute_away(&mut self, msg: Option<String>) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
         }
         let nick_copy = self.nick.clone().ok_or("no nick")?;
         self.clients
@@ -835,18 +1411,305 @@ impl ClientS {
         self.return_code((app_errors::ERR_NEEDMOREPARAMS, vec!["MODE +o".to_string()]))
     }
 
-    /// Given a nick and a mode object, the mode command will try to be executed.
-    /// The posible mode options include o, l, s and i.
-    fn execute_mode(
+    /// Given a channel, a mode (+k or -k) and a key sets or removes the channel key.
+    /// The key is only required to set it.
+    fn execute_mode_key(
         &mut self,
         channel_name: String,
         mode: Mode,
         params: Option<String>,
         prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let (Mode::Activate(_), None) = (&mode, &params) {
+            return self.return_code((app_errors::ERR_NEEDMOREPARAMS, vec!["MODE +k".to_string()]));
+        }
+        let result = self.clients.lock().expect("Couldn't lock").mode_key(
+            self.nick.clone(),
+            channel_name,
+            mode,
+            params,
+            prefix,
+            self.server_name.clone(),
+        );
+        match result {
+            Ok(code) => self.return_code(code),
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given a channel, a mode (+v or -v) and a nick gives or takes voice to the user.
+    fn execute_mode_voice(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        params: Option<String>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(voiced) = params {
+            let result = self.clients.lock().expect("Couldn't lock").mode_voice(
+                self.nick.clone(),
+                channel_name,
+                mode.clone(),
+                voiced,
+                prefix,
+                self.server_name.clone(),
+            );
+            return match result {
+                Ok(rpl) => self.return_code(rpl),
+                Err(err) => self.return_code(err),
+            };
+        }
+        self.return_code((
+            app_errors::ERR_NEEDMOREPARAMS,
+            vec![format!("MODE {}", mode.to_mode_string())],
+        ))
+    }
+
+    /// Given a channel, a mode (+b, -b, +e or -e) and a mask updates the bans or ban exceptions.
+    /// Without a mask the current list is sent instead (the sign is ignored).
+    fn execute_mode_mask(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        params: Option<String>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mask = match params {
+            Some(mask) => mask,
+            None => {
+                let (Mode::Activate(list) | Mode::Deactivate(list)) = mode;
+                let replies = self
+                    .clients
+                    .lock()
+                    .expect("Couldn't lock")
+                    .mask_list(channel_name, list);
+                return match replies {
+                    Ok(replies) => {
+                        for reply in replies {
+                            self.return_code(reply)?;
+                        }
+                        Ok(())
+                    }
+                    Err(err) => self.return_code(err),
+                };
+            }
+        };
+        let result = self.clients.lock().expect("Couldn't lock").mode_mask(
+            self.nick.clone(),
+            channel_name,
+            mode,
+            mask,
+            prefix,
+            self.server_name.clone(),
+        );
+        match result {
+            Ok(code) => self.return_code(code),
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given a nick and a list of modes, each user mode will try to be executed in order.
+    /// Without modes the current modes of the user are sent instead (only to themselves).
+    fn execute_user_mode(
+        &mut self,
+        nick: String,
+        modes: Vec<(Mode, Option<String>)>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        // los servidores indican de quien es el cambio en el prefijo
+        let issuer = match (&self.server_name, prefix, &self.nick) {
+            (Some(_), Some(prefix), _) => prefix,
+            (None, _, Some(own_nick)) if self.is_registered() => own_nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        if modes.is_empty() {
+            let same_user = self
+                .clients
+                .lock()
+                .expect("Couldn't lock")
+                .is_same_name(&issuer, &nick);
+            if !same_user {
+                return self.return_code((app_errors::ERR_USERSDONTMATCH, vec![]));
+            }
+            let result = self
+                .clients
+                .lock()
+                .expect("Couldn't lock")
+                .user_modes(&nick);
+            return match result {
+                Ok(code) => self.return_code(code),
+                Err(code) => self.return_code(code),
+            };
+        }
+        for (mode, _) in modes {
+            let result = self.clients.lock().expect("Couldn't lock").mode_user(
+                issuer.clone(),
+                nick.clone(),
+                mode.clone(),
+                self.server_name.clone(),
+            );
+            match result {
+                Ok(code) => {
+                    if mode == Mode::Deactivate('o') && self.server_name.is_none() {
+                        self.status = ClientStatus::Registered;
+                    }
+                    self.return_code(code)?
+                }
+                Err(code) => self.return_code(code)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Given the text sends a WALLOPS message to the users with the +w mode.
+    /// Only operators can send it, servers relay it.
+    fn execute_wallops(
+        &mut self,
+        text: String,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let from = match (&self.server_name, prefix, &self.nick) {
+            (Some(_), Some(prefix), _) => prefix,
+            (None, _, Some(own_nick)) if self.is_registered() => own_nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let result = self.clients.lock().expect("Couldn't lock").wallops(
+            from,
+            text,
+            self.server_name.clone(),
+        );
+        match result {
+            Ok(()) => Ok(()),
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given the nick of a user and the reason removes the user from the network.
+    /// Only operators can do it, servers relay the KILL of the operators of the network.
+    fn execute_kill(
+        &mut self,
+        nick: String,
+        comment: String,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let killer = match (&self.server_name, prefix, &self.nick) {
+            (Some(_), Some(prefix), _) => prefix,
+            (None, _, Some(own_nick)) if self.is_registered() => own_nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let result = self.clients.lock().expect("Couldn't lock").kill_client(
+            killer.clone(),
+            nick.clone(),
+            comment.clone(),
+            self.server_name.clone(),
+        );
+        match result {
+            Ok(()) => {
+                self.logger
+                    .lock()
+                    .expect("Error: log lock poisoned")
+                    .write(format!("{}: KILL {} ({})", killer, nick, comment));
+                Ok(())
+            }
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given a 'user@host' mask, the minutes it lasts (permanent if None) and the reason bans
+    /// the matching connections from the server. Only operators can do it.
+    fn execute_kline(
+        &mut self,
+        mask: String,
+        minutes: Option<u64>,
+        reason: String,
+    ) -> Result<(), Box<dyn Error>> {
+        let nick = match &self.nick {
+            Some(nick) if self.is_registered() => nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let result = self.clients.lock().expect("Couldn't lock").add_kline(
+            &nick,
+            mask.clone(),
+            minutes,
+            reason.clone(),
+        );
+        match result {
+            Ok(()) => {
+                self.logger
+                    .lock()
+                    .expect("Error: log lock poisoned")
+                    .write(format!("{}: KLINE {} ({})", nick, mask, reason));
+                self.write_line(format!(
+                    ":{} NOTICE {} :Added K-line for {}",
+                    self.local_name, nick, mask
+                ))
+            }
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given a 'user@host' mask removes its K-line. Only operators can do it.
+    fn execute_unkline(&mut self, mask: String) -> Result<(), Box<dyn Error>> {
+        let nick = match &self.nick {
+            Some(nick) if self.is_registered() => nick.clone(),
+            _ => return self.return_code((app_errors::ERR_NOTREGISTERED, vec![])),
+        };
+        let result = self
+            .clients
+            .lock()
+            .expect("Couldn't lock")
+            .remove_kline(&nick, mask.clone());
+        match result {
+            Ok(true) => self.write_line(format!(
+                ":{} NOTICE {} :Removed K-line for {}",
+                self.local_name, nick, mask
+            )),
+            Ok(false) => self.write_line(format!(
+                ":{} NOTICE {} :No K-line for {}",
+                self.local_name, nick, mask
+            )),
+            Err(code) => self.return_code(code),
+        }
+    }
+
+    /// Given a channel and a list of modes with their arguments, each mode will try to be executed in order.
+    /// Without modes the current modes of the channel are sent instead.
+    fn execute_mode(
+        &mut self,
+        channel_name: String,
+        modes: Vec<(Mode, Option<String>)>,
+        prefix: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         if !self.is_registered() && self.server_name.is_none() {
-            return self.return_code((app_errors::ERR_NOLOGIN, vec![]));
+            return self.return_code((app_errors::ERR_NOTREGISTERED, vec![]));
+        }
+        if modes.is_empty() {
+            let nick = self.nick.clone().unwrap_or_default();
+            let result = self
+                .clients
+                .lock()
+                .expect("Couldn't lock")
+                .channel_modes(channel_name, &nick);
+            return match result {
+                Ok(code) => self.return_code(code),
+                Err(code) => self.return_code(code),
+            };
+        }
+        for (mode, params) in modes {
+            self.execute_channel_mode(channel_name.clone(), mode, params, prefix.clone())?;
         }
+        Ok(())
+    }
+
+    /// Given a nick and a mode object, the mode command will try to be executed.
+    /// The posible mode options include o, v, l, s, i, m, t, n, k, b and e.
+    fn execute_channel_mode(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        params: Option<String>,
+        prefix: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
         match mode {
             Mode::Activate('o') | Mode::Deactivate('o') => {
                 self.execute_mode_oper(channel_name, mode, params, prefix)
@@ -888,8 +1751,59 @@ impl ClientS {
                     Err(code) => self.return_code(code),
                 }
             }
+            Mode::Activate('m') | Mode::Deactivate('m') => {
+                match self.clients.lock().expect("Couldn't lock").mode_moderated(
+                    self.nick.clone(),
+                    channel_name,
+                    mode,
+                    prefix,
+                    self.server_name.clone(),
+                ) {
+                    Ok(code) => self.return_code(code),
+                    Err(code) => self.return_code(code),
+                }
+            }
+            Mode::Activate('t') | Mode::Deactivate('t') => {
+                match self.clients.lock().expect("Couldn't lock").mode_topic_lock(
+                    self.nick.clone(),
+                    channel_name,
+                    mode,
+                    prefix,
+                    self.server_name.clone(),
+                ) {
+                    Ok(code) => self.return_code(code),
+                    Err(code) => self.return_code(code),
+                }
+            }
+            Mode::Activate('n') | Mode::Deactivate('n') => {
+                let result = self
+                    .clients
+                    .lock()
+                    .expect("Couldn't lock")
+                    .mode_no_external(
+                        self.nick.clone(),
+                        channel_name,
+                        mode,
+                        prefix,
+                        self.server_name.clone(),
+                    );
+                match result {
+                    Ok(code) => self.return_code(code),
+                    Err(code) => self.return_code(code),
+                }
+            }
+            Mode::Activate('k') | Mode::Deactivate('k') => {
+                self.execute_mode_key(channel_name, mode, params, prefix)
+            }
+            Mode::Activate('v') | Mode::Deactivate('v') => {
+                self.execute_mode_voice(channel_name, mode, params, prefix)
+            }
+            Mode::Activate('b')
+            | Mode::Deactivate('b')
+            | Mode::Activate('e')
+            | Mode::Deactivate('e') => self.execute_mode_mask(channel_name, mode, params, prefix),
             Mode::Activate(mode) | Mode::Deactivate(mode) => {
-                self.return_code((app_errors::ERR_UNKNOWNCOMMAND, vec![mode.to_string()]))
+                self.return_code((app_errors::ERR_UNKNOWNMODE, vec![mode.to_string()]))
             }
         }
     }
@@ -1,6 +1,7 @@
 pub mod command;
 use crate::app_errors;
 use std::error::Error;
+use std::fmt;
 
 /// is the conversion of the messages received by the stream.
 /// Contains a prefix (optional) and a command
@@ -92,14 +93,12 @@ impl Message {
     }
 }
 
-impl ToString for Message {
-    /// Implementation of the ToString trait for message object
-    fn to_string(&self) -> String {
+impl fmt::Display for Message {
+    /// Implementation of the Display trait for message object
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.prefix.clone() {
-            Some(x) => {
-                format!("{}: {}", x, self.command)
-            }
-            None => self.command.to_string(),
+            Some(x) => write!(f, "{}: {}", x, self.command),
+            None => write!(f, "{}", self.command),
         }
     }
 }
@@ -107,7 +106,7 @@ impl ToString for Message {
 #[cfg(test)]
 mod message_test {
     use crate::app_errors;
-    use crate::server::client_s::message::command::Command;
+    use crate::server::client_s::message::command::{Command, Mode};
     use crate::server::client_s::message::Message;
     #[test]
     fn empty_buffer_fails() {
@@ -161,7 +160,7 @@ mod message_test {
                 assert_eq!(
                     Message {
                         prefix: None,
-                        command: Command::Nick("hola".to_string(), 0)
+                        command: Command::Nick("hola".to_string(), 0, None)
                     },
                     msg
                 );
@@ -236,7 +235,11 @@ mod message_test {
         let buffer = "JOIN &foo fubar\n".to_string();
         let expected = Message {
             prefix: None,
-            command: Command::Join(vec!["&foo".to_string()], vec![Some("fubar".to_string())]),
+            command: Command::Join(
+                vec!["&foo".to_string()],
+                vec![Some("fubar".to_string())],
+                None,
+            ),
         };
         let actual = Message::build(buffer).expect("");
         assert_eq!(actual, expected)
@@ -250,6 +253,7 @@ mod message_test {
             command: Command::Join(
                 vec!["#foo".to_string(), "&bar".to_string()],
                 vec![Some("fubar".to_string()), None],
+                None,
             ),
         };
         let actual = Message::build(buffer).expect("");
@@ -333,14 +337,156 @@ mod message_test {
         assert_eq!(actual, expected)
     }
 
+    #[test]
+    fn ping_message_builds() {
+        let buffer = "PING :server_uno\n".to_string();
+        let expected = Message {
+            prefix: None,
+            command: Command::Ping("server_uno".to_string(), None),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
     #[test]
     fn whois_message_builds() {
         let buffer = "WHOIS trillian\n".to_string();
         let expected = Message {
             prefix: None,
-            command: Command::Whois("trillian".to_string()),
+            command: Command::Whois(None, "trillian".to_string()),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn combined_mode_message_builds() {
+        let buffer = "MODE #chan +ikl-s secret 10\n".to_string();
+        let expected = Message {
+            prefix: None,
+            command: Command::Mode(
+                "#chan".to_string(),
+                vec![
+                    (Mode::Activate('i'), None),
+                    (Mode::Activate('k'), Some("secret".to_string())),
+                    (Mode::Activate('l'), Some("10".to_string())),
+                    (Mode::Deactivate('s'), None),
+                ],
+            ),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected);
+        assert_eq!(actual.to_string(), "MODE #chan +ikl-s secret 10");
+    }
+
+    #[test]
+    fn mode_arguments_are_given_in_order() {
+        let buffer = ":juan MODE #chan -l+ov-k nico pedro secret\n".to_string();
+        let expected = Message {
+            prefix: Some("juan".to_string()),
+            command: Command::Mode(
+                "#chan".to_string(),
+                vec![
+                    (Mode::Deactivate('l'), None),
+                    (Mode::Activate('o'), Some("nico".to_string())),
+                    (Mode::Activate('v'), Some("pedro".to_string())),
+                    (Mode::Deactivate('k'), Some("secret".to_string())),
+                ],
+            ),
         };
         let actual = Message::build(buffer).expect("");
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn mode_ban_without_mask_is_a_list_request() {
+        let buffer = "MODE #chan +b\n".to_string();
+        let expected = Message {
+            prefix: None,
+            command: Command::Mode("#chan".to_string(), vec![(Mode::Activate('b'), None)]),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn mode_without_modes_is_a_query() {
+        let buffer = "MODE #chan\n".to_string();
+        let expected = Message {
+            prefix: None,
+            command: Command::Mode("#chan".to_string(), vec![]),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn mode_string_without_sign_fails() {
+        let buffer = "MODE #chan ik secret\n".to_string();
+        let expected = Message {
+            prefix: None,
+            command: Command::Invalid((app_errors::ERR_UNKNOWNMODE, vec!["ik".to_string()])),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn wallops_message_builds() {
+        let buffer = ":juan WALLOPS :server en mantenimiento\n".to_string();
+        let expected = Message {
+            prefix: Some("juan".to_string()),
+            command: Command::Wallops("server en mantenimiento".to_string()),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn kill_message_builds() {
+        let buffer = ":juan KILL nico :flood de mensajes\n".to_string();
+        let expected = Message {
+            prefix: Some("juan".to_string()),
+            command: Command::Kill("nico".to_string(), "flood de mensajes".to_string()),
+        };
+        let actual = Message::build(buffer).expect("");
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn kill_without_comment_fails() {
+        let actual = Message::build("KILL nico\n".to_string()).expect("");
+        assert_eq!(
+            actual.command,
+            Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["KILL".to_string()]))
+        )
+    }
+
+    #[test]
+    fn kline_message_builds() {
+        let actual =
+            Message::build("KLINE 30 *@10.0.0.1 :flood de mensajes\n".to_string()).expect("");
+        assert_eq!(
+            actual.command,
+            Command::Kline(
+                "*@10.0.0.1".to_string(),
+                Some(30),
+                "flood de mensajes".to_string()
+            )
+        );
+        let actual = Message::build("KLINE botter@* :spam\n".to_string()).expect("");
+        assert_eq!(
+            actual.command,
+            Command::Kline("botter@*".to_string(), None, "spam".to_string())
+        );
+    }
+
+    #[test]
+    fn kline_without_reason_fails() {
+        let actual = Message::build("KLINE 30 *@10.0.0.1\n".to_string()).expect("");
+        assert_eq!(
+            actual.command,
+            Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["KLINE".to_string()]))
+        )
+    }
 }
@@ -20,6 +20,15 @@ impl Mode {
             Mode::Deactivate(c) => format!("-{c}"),
         }
     }
+
+    /// Given a mode object reference returns whether it uses the next argument of the MODE command
+    /// (o, v, b, e and k always use one, l only when it's set)
+    pub fn takes_param(&self) -> bool {
+        match self {
+            Mode::Activate(c) => "ovbekl".contains(*c),
+            Mode::Deactivate(c) => "ovbek".contains(*c),
+        }
+    }
 }
 #[derive(PartialEq, Eq, Debug, Clone)]
 /// Enum with all implemented commands with their respective inputs
@@ -27,8 +36,8 @@ impl Mode {
 pub enum Command {
     /// PASS (value)
     Pass(String),
-    /// NICK (value)
-    Nick(String, i32),
+    /// NICK (value, hopcount, when the nick was taken if it comes from a server)
+    Nick(String, i32, Option<u64>),
     /// USER ( username, realname)
     User(String, String),
     /// OPER (user, password)
@@ -39,8 +48,8 @@ pub enum Command {
     Privmsg(String, String),
     /// NOTICE (nick, text)
     Notice(String, String),
-    /// JOIN (list of channels, list of keys)
-    Join(Vec<String>, Vec<Option<String>>),
+    /// JOIN (list of channels, list of keys, when the channel was created if it comes from a server)
+    Join(Vec<String>, Vec<Option<String>>, Option<u64>),
     /// PART (list of channels)
     Part(Vec<String>),
     /// NAMES (list of channels)
@@ -51,20 +60,48 @@ pub enum Command {
     Invite(String, String),
     /// WHO (user or mask, mode (optional))
     Who(String, Option<String>),
-    /// WHOIS (server, nick)
-    Whois(String),
+    /// WHOIS (server (optional), nick)
+    Whois(Option<String>, String),
     /// TOPIC (channel, new_topic)
     Topic(String, Option<String>),
     /// SERVER (servername, hopcount, info)
     Server(String, i32, String),
     /// SQUIT (servername, comment (optional))
     Squit(String, Option<String>),
-    /// MODE (nick, mode, limit|user|ban mask (optional))
-    Mode(String, Mode, Option<String>),
+    /// MODE (channel or nick, list of modes with their limit|user|ban mask|key (optional)), no modes is a query
+    Mode(String, Vec<(Mode, Option<String>)>),
     /// KICK (channel, user, comment (optional))
     Kick(String, String, Option<String>),
     /// Away (message (optional))
     Away(Option<String>),
+    /// PING (origin, target server (optional))
+    Ping(String, Option<String>),
+    /// PONG (origin, target server (optional))
+    Pong(String, Option<String>),
+    /// WALLOPS (text)
+    Wallops(String),
+    /// KILL (nick, comment)
+    Kill(String, String),
+    /// KLINE (user@host mask, minutes (optional, permanent if missing), reason)
+    Kline(String, Option<u64>, String),
+    /// UNKLINE (user@host mask)
+    Unkline(String),
+    /// CONNECT (target server, port (optional))
+    Connect(String, Option<String>),
+    /// LINKS (server mask (optional))
+    Links(Option<String>),
+    /// MAP
+    Map,
+    /// VERSION (target server (optional))
+    Version(Option<String>),
+    /// TIME (target server (optional))
+    Time(Option<String>),
+    /// ADMIN (target server (optional))
+    Admin(Option<String>),
+    /// INFO (target server (optional))
+    Info(Option<String>),
+    /// numeric reply of another server (numeric, receiver, text), relayed towards the receiver
+    Reply(String, String, String),
     /// CODE ERROR
     Invalid(((i32, &'static str), Vec<String>)),
 }
@@ -83,8 +120,8 @@ impl Command {
         if params.is_empty() {
             return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec![]));
         }
-        return match params
-            .get(0)
+        match params
+            .first()
             .expect("Error: parameter length changed during parsing")
             .as_str()
         {
@@ -108,8 +145,52 @@ impl Command {
             "MODE" => Command::parse_mode(params),
             "KICK" => Command::parse_kick(params),
             "AWAY" => Command::parse_away(params),
+            "PING" => Command::parse_ping(params),
+            "PONG" => Command::parse_pong(params),
+            "WALLOPS" => Command::parse_wallops(params),
+            "KILL" => Command::parse_kill(params),
+            "KLINE" => Command::parse_kline(params),
+            "UNKLINE" => Command::parse_unkline(params),
+            "CONNECT" => Command::parse_connect(params),
+            "LINKS" => Command::parse_links(params),
+            "MAP" => Command::Map,
+            "VERSION" => Command::Version(Command::parse_target_server(params)),
+            "TIME" => Command::Time(Command::parse_target_server(params)),
+            "ADMIN" => Command::Admin(Command::parse_target_server(params)),
+            "INFO" => Command::Info(Command::parse_target_server(params)),
+            numeric if Command::is_numeric(numeric) => Command::parse_reply(params),
             other => Command::Invalid((app_errors::ERR_UNKNOWNCOMMAND, vec![other.to_string()])),
-        };
+        }
+    }
+
+    /// Auxiliary function for parsing the servers of PING and PONG.
+    /// The leading ':' of the last parameter is removed
+    fn parse_ping_servers(mut params: Vec<String>) -> Option<(String, Option<String>)> {
+        params.remove(0);
+        let mut servers = params
+            .into_iter()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.trim_start_matches(':').to_string());
+        let origin = servers.next()?;
+        Some((origin, servers.next()))
+    }
+
+    /// Given separated parameters in a list returns the correct ping command
+    /// Should only be called from build
+    fn parse_ping(params: Vec<String>) -> Command {
+        match Command::parse_ping_servers(params) {
+            Some((origin, target)) => Command::Ping(origin, target),
+            None => Command::Invalid((app_errors::ERR_NOORIGIN, vec![])),
+        }
+    }
+
+    /// Given separated parameters in a list returns the correct pong command
+    /// Should only be called from build
+    fn parse_pong(params: Vec<String>) -> Command {
+        match Command::parse_ping_servers(params) {
+            Some((origin, target)) => Command::Pong(origin, target),
+            None => Command::Invalid((app_errors::ERR_NOORIGIN, vec![])),
+        }
     }
 
     /// Given separated parameters in a list returns the correct away command
@@ -124,6 +205,87 @@ impl Command {
         }
     }
 
+    /// Given separated parameters in a list returns the correct wallops command
+    /// Should only be called from build
+    fn parse_wallops(mut params: Vec<String>) -> Command {
+        if params.len() < 2 {
+            return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["WALLOPS".to_string()]));
+        }
+        params.remove(0);
+        let text = params.join(" ");
+        Command::Wallops(text.strip_prefix(':').unwrap_or(&text).to_string())
+    }
+
+    /// Given separated parameters in a list returns the correct kill command, the comment is mandatory
+    /// Should only be called from build
+    fn parse_kill(mut params: Vec<String>) -> Command {
+        if params.len() < 3 {
+            return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["KILL".to_string()]));
+        }
+        params.remove(0);
+        let nick = params.remove(0);
+        let comment = params.join(" ");
+        Command::Kill(nick, comment.strip_prefix(':').unwrap_or(&comment).to_string())
+    }
+
+    /// Given separated parameters in a list returns the correct kline command,
+    /// the minutes are optional and the reason is mandatory
+    /// Should only be called from build
+    fn parse_kline(mut params: Vec<String>) -> Command {
+        params.remove(0);
+        let minutes = params.first().and_then(|value| value.parse().ok());
+        if minutes.is_some() {
+            params.remove(0);
+        }
+        if params.len() < 2 {
+            return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["KLINE".to_string()]));
+        }
+        let mask = params.remove(0);
+        let reason = params.join(" ");
+        Command::Kline(
+            mask,
+            minutes,
+            reason.strip_prefix(':').unwrap_or(&reason).to_string(),
+        )
+    }
+
+    /// Given separated parameters in a list returns the correct unkline command
+    /// Should only be called from build
+    fn parse_unkline(params: Vec<String>) -> Command {
+        match params.get(1) {
+            Some(mask) => Command::Unkline(mask.to_string()),
+            None => Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["UNKLINE".to_string()])),
+        }
+    }
+
+    /// Given separated parameters in a list returns the correct connect command, the port is optional
+    /// Should only be called from build
+    fn parse_connect(mut params: Vec<String>) -> Command {
+        match params.len() {
+            1 => Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["CONNECT".to_string()])),
+            2 => Command::Connect(params.remove(1), None),
+            _ => Command::Connect(params.remove(1), Some(params.remove(1))),
+        }
+    }
+
+    /// Given separated parameters in a list returns the correct links command.
+    /// The mask is the last parameter, a remote server before it is ignored
+    /// Should only be called from build
+    fn parse_links(mut params: Vec<String>) -> Command {
+        match params.len() {
+            1 => Command::Links(None),
+            _ => Command::Links(params.pop()),
+        }
+    }
+
+    /// Given the parameters of kline, return the string corresponding to it's irc command
+    fn kline_to_string(mask: &String, minutes: &Option<u64>, reason: &String) -> String {
+        match minutes {
+            Some(x) => format!("KLINE {} {} :{}", x, mask, reason),
+            None => format!("KLINE {} :{}", mask, reason),
+        }
+    }
+
     /// Given separated parameters in a list returns the correct kick command
     /// Should only be called from build
     fn parse_kick(mut params: Vec<String>) -> Command {
@@ -138,39 +300,53 @@ impl Command {
         Command::Kick(params.remove(0), params.remove(0), Some(params.join(" ")))
     }
 
-    /// Auxiliary function for helping identify the mode request
-    fn build_mode(mut mode: String) -> Result<Mode, String> {
-        if mode.len() == 2 {
-            match mode.remove(0) {
-                '+' => return Ok(Mode::Activate(mode.remove(0))),
-                '-' => return Ok(Mode::Deactivate(mode.remove(0))),
-                _ => return Err(mode),
+    /// Auxiliary function for helping identify the mode request.
+    /// Given a mode string (e.g. "+ikl-s") returns each of its modes, the sign applies
+    /// to the following letters until another sign appears
+    fn build_modes(modes: &str) -> Result<Vec<Mode>, String> {
+        let mut sign = match modes.chars().next() {
+            Some(sign @ ('+' | '-')) => sign,
+            _ => return Err(modes.to_string()),
+        };
+        let mut built = Vec::new();
+        for mode in modes.chars() {
+            match mode {
+                '+' | '-' => sign = mode,
+                mode if sign == '+' => built.push(Mode::Activate(mode)),
+                mode => built.push(Mode::Deactivate(mode)),
             }
         }
-        Err(mode)
+        if built.is_empty() {
+            return Err(modes.to_string());
+        }
+        Ok(built)
     }
 
     /// Given separated parameters in a list returns the correct mode command
+    /// Arguments are given to the modes that use them in order (e.g. "MODE #chan +ikl-s secret 10").
     /// Should only be called from build
     fn parse_mode(mut params: Vec<String>) -> Command {
-        let len = params.len();
-        match len {
-            4 | 3 => {
-                let channel_name = params.remove(1);
-                match Command::build_mode(params.remove(1)) {
-                    Ok(mode) => match len {
-                        4 => Command::Mode(channel_name, mode, Some(params.remove(1))),
-                        3 => Command::Mode(channel_name, mode, None),
-                        _ => Command::Invalid((
-                            app_errors::ERR_NEEDMOREPARAMS,
-                            vec!["MODE".to_string()],
-                        )),
-                    },
-                    Err(err) => Command::Invalid((app_errors::ERR_UNKNOWNMODE, vec![err])),
-                }
+        if params.len() < 2 {
+            return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["MODE".to_string()]));
+        }
+        params.remove(0);
+        let channel_name = params.remove(0);
+        let mut params = params.into_iter().filter(|value| !value.is_empty());
+        let mut modes = Vec::new();
+        if let Some(mode_string) = params.next() {
+            let built = match Command::build_modes(&mode_string) {
+                Ok(built) => built,
+                Err(err) => return Command::Invalid((app_errors::ERR_UNKNOWNMODE, vec![err])),
+            };
+            for mode in built {
+                let param = match mode.takes_param() {
+                    true => params.next(),
+                    false => None,
+                };
+                modes.push((mode, param));
             }
-            _ => Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["MODE".to_string()])),
         }
+        Command::Mode(channel_name, modes)
     }
 
     /// Given separated parameters in a list returns the correct server command
@@ -183,7 +359,11 @@ impl Command {
             Err(_) => {
                 Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["SERVER".to_string()]))
             }
-            Ok(value) => Command::Server(params.remove(1), value, params.remove(2)),
+            Ok(value) => {
+                let name = params.remove(1);
+                let info = params.remove(2);
+                Command::Server(name, value, info.trim_start_matches(':').to_string())
+            }
         }
     }
 
@@ -209,17 +389,24 @@ impl Command {
     /// Given separated parameters in a list returns the correct nick command
     /// Should only be called from build
     fn parse_nick(mut params: Vec<String>) -> Command {
-        if params.len() < 2 || params.len() > 3 {
+        if params.len() < 2 || params.len() > 4 {
             return Command::Invalid((app_errors::ERR_NONICKNAMEGIVEN, vec![]));
         }
+        // los servidores agregan cuando se tomo el nick, para resolver colisiones
+        let nick_time = params.get(3).and_then(|time| time.parse().ok());
         let mut hopcount = 0;
-        if params.len() == 3 {
-            hopcount = params
-                .remove(2)
-                .parse()
-                .expect("Error: parameter length changed during parsing nick");
+        if params.len() >= 3 {
+            hopcount = match params.remove(2).parse().ok() {
+                Some(hopcount) => hopcount,
+                None => {
+                    return Command::Invalid((
+                        app_errors::ERR_NEEDMOREPARAMS,
+                        vec!["NICK".to_string()],
+                    ))
+                }
+            };
         }
-        Command::Nick(params.remove(1), hopcount)
+        Command::Nick(params.remove(1), hopcount, nick_time)
     }
 
     /// Given separated parameters in a list returns the correct privmsg command
@@ -286,7 +473,7 @@ impl Command {
             .collect();
 
         let mut keys;
-        if params.len() == 3 {
+        if params.len() >= 3 {
             keys = params
                 .get(2)
                 .expect("Error: parameter length changed during parsing join")
@@ -300,8 +487,10 @@ impl Command {
         while channels.len() > keys.len() {
             keys.push(None);
         }
+        // los servidores agregan cuando se creo el canal, el mas antiguo gana al enlazarse
+        let created = params.get(3).and_then(|time| time.parse().ok());
 
-        Command::Join(channels, keys)
+        Command::Join(channels, keys, created)
     }
 
     /// Given separated parameters in a list returns the correct part command
@@ -397,13 +586,40 @@ impl Command {
         }
     }
 
-    /// Given separated parameters in a list returns the correct whois command
+    /// Given separated parameters in a list returns the correct whois command, the server is optional
     /// Should only be called from build
     fn parse_whois(mut params: Vec<String>) -> Command {
-        if params.len() != 2 {
-            return Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["WHOIS".to_string()]));
+        match params.len() {
+            2 => Command::Whois(None, params.remove(1)),
+            3 => Command::Whois(Some(params.remove(1)), params.remove(1)),
+            _ => Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["WHOIS".to_string()])),
+        }
+    }
+
+    /// Given separated parameters in a list returns the server a query (VERSION, TIME, ADMIN or INFO)
+    /// is meant for, if there is one
+    /// Should only be called from build
+    fn parse_target_server(params: Vec<String>) -> Option<String> {
+        params
+            .get(1)
+            .map(|server| server.trim_start_matches(':').to_string())
+            .filter(|server| !server.is_empty())
+    }
+
+    /// Given the first word of a line returns whether it's the numeric of a reply (three digits)
+    fn is_numeric(word: &str) -> bool {
+        word.len() == 3 && word.chars().all(|c| c.is_ascii_digit())
+    }
+
+    /// Given separated parameters in a list returns the reply sent by another server
+    /// Should only be called from build
+    fn parse_reply(mut params: Vec<String>) -> Command {
+        if params.len() < 2 {
+            return Command::Invalid((app_errors::ERR_UNKNOWNCOMMAND, vec![params.remove(0)]));
         }
-        Command::Whois(params.remove(1))
+        let numeric = params.remove(0);
+        let target = params.remove(0);
+        Command::Reply(numeric, target, params.join(" "))
     }
 
     /// Given the parameters of pass, return the string corresponding to it's irc command
@@ -412,8 +628,11 @@ impl Command {
     }
 
     /// Given the parameters of nick, return the string corresponding to it's irc command
-    fn nick_to_string(nick: &String, hopcount: i32) -> String {
-        format!("NICK {} {}", nick, hopcount)
+    fn nick_to_string(nick: &String, hopcount: i32, nick_time: &Option<u64>) -> String {
+        match nick_time {
+            Some(time) => format!("NICK {} {} {}", nick, hopcount, time),
+            None => format!("NICK {} {}", nick, hopcount),
+        }
     }
 
     /// Given the parameters of privmsg, return the string corresponding to it's irc command
@@ -445,7 +664,11 @@ impl Command {
     }
 
     /// Given the parameters of join, return the string corresponding to it's irc command
-    fn join_to_string(channels: Vec<String>, keys: Vec<Option<String>>) -> String {
+    fn join_to_string(
+        channels: Vec<String>,
+        keys: Vec<Option<String>>,
+        created: &Option<u64>,
+    ) -> String {
         let keys: Vec<String> = keys
             .iter()
             .filter(|&x| x.is_some())
@@ -454,7 +677,10 @@ impl Command {
                     .expect("Error: string object modified during join to string")
             })
             .collect();
-        format!("JOIN {} {}", channels.join(","), keys.join(","))
+        match created {
+            Some(time) => format!("JOIN {} {} {}", channels.join(","), keys.join(","), time),
+            None => format!("JOIN {} {}", channels.join(","), keys.join(",")),
+        }
     }
 
     /// Given the parameters of part, return the string corresponding to it's irc command
@@ -494,8 +720,19 @@ impl Command {
     }
 
     /// Given the parameters of whois, return the string corresponding to it's irc command
-    fn whois_to_string(nick: &String) -> String {
-        format!("WHOIS {}", nick)
+    fn whois_to_string(server: &Option<String>, nick: &String) -> String {
+        match server {
+            Some(server) => format!("WHOIS {} {}", server, nick),
+            None => format!("WHOIS {}", nick),
+        }
+    }
+
+    /// Given the name of a query and the server it's meant for, return the string corresponding to it's irc command
+    fn query_to_string(query: &str, server: &Option<String>) -> String {
+        match server {
+            Some(server) => format!("{} {}", query, server),
+            None => query.to_string(),
+        }
     }
 
     /// Given the parameters of server, return the string corresponding to it's irc command
@@ -512,15 +749,28 @@ impl Command {
     }
 
     /// Given the parameters of mode, return the string corresponding to it's irc command
-    fn mode_to_string(channel_name: &String, mode: &Mode, extra: &Option<String>) -> String {
-        let (sign, mode) = match mode {
-            Mode::Activate(mode) => ('+', mode),
-            Mode::Deactivate(mode) => ('-', mode),
-        };
-        match extra {
-            Some(x) => format!("MODE {} {}{} {}", channel_name, sign, mode, x),
-            None => format!("MODE {} {}{}", channel_name, sign, mode),
+    fn mode_to_string(channel_name: &String, modes: &[(Mode, Option<String>)]) -> String {
+        let mut command = format!("MODE {}", channel_name);
+        let mut mode_string = String::new();
+        let mut last_sign = None;
+        for (mode, _) in modes {
+            let (sign, mode) = match mode {
+                Mode::Activate(mode) => ('+', mode),
+                Mode::Deactivate(mode) => ('-', mode),
+            };
+            if last_sign != Some(sign) {
+                mode_string.push(sign);
+                last_sign = Some(sign);
+            }
+            mode_string.push(*mode);
+        }
+        if !mode_string.is_empty() {
+            command = format!("{} {}", command, mode_string);
         }
+        for param in modes.iter().filter_map(|(_, param)| param.as_ref()) {
+            command = format!("{} {}", command, param);
+        }
+        command
     }
 
     /// Given the parameters of mode, return the string corresponding to it's irc command
@@ -538,31 +788,57 @@ impl Command {
             None => "AWAY".to_string(),
         }
     }
+
+    /// Given the parameters of ping or pong, return the string corresponding to it's irc command
+    fn ping_to_string(command: &str, origin: &String, target: &Option<String>) -> String {
+        match target {
+            Some(x) => format!("{} {} {}", command, origin, x),
+            None => format!("{} :{}", command, origin),
+        }
+    }
 }
 impl fmt::Display for Command {
     /// Implementation of the Display trait for command
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Command::Pass(x) => write!(f, "{}", Command::pass_to_string(x)),
-            Command::Nick(x, y) => write!(f, "{}", Command::nick_to_string(x, *y)),
+            Command::Nick(x, y, z) => write!(f, "{}", Command::nick_to_string(x, *y, z)),
             Command::Privmsg(x, y) => write!(f, "{}", Command::privmsg_to_string(x, y)),
             Command::User(x, y) => write!(f, "{}", Command::user_to_string(x, y)),
             Command::Oper(x, y) => write!(f, "{}", Command::oper_to_string(x, y)),
             Command::Quit(x) => write!(f, "{}", Command::quit_to_string(x)),
             Command::Notice(x, y) => write!(f, "{}", Command::notice_to_string(x, y)),
-            Command::Join(x, y) => write!(f, "{}", Command::join_to_string(x.clone(), y.clone())),
+            Command::Join(x, y, z) => {
+                write!(f, "{}", Command::join_to_string(x.clone(), y.clone(), z))
+            }
             Command::Part(x) => write!(f, "{}", Command::part_to_string(x)),
             Command::Names(x) => write!(f, "{}", Command::names_to_string(x)),
             Command::List(x) => write!(f, "{}", Command::list_to_string(x)),
             Command::Invite(x, y) => write!(f, "{}", Command::invite_to_string(x, y)),
             Command::Who(x, y) => write!(f, "{}", Command::who_to_string(x, y)),
-            Command::Whois(x) => write!(f, "{}", Command::whois_to_string(x)),
+            Command::Whois(x, y) => write!(f, "{}", Command::whois_to_string(x, y)),
             Command::Topic(x, y) => write!(f, "{}", Command::topic_to_string(x, y)),
             Command::Server(x, y, z) => write!(f, "{}", Command::server_to_string(x, y, z)),
             Command::Squit(x, y) => write!(f, "{}", Command::squit_to_string(x, y)),
-            Command::Mode(x, y, z) => write!(f, "{}", Command::mode_to_string(x, y, z)),
+            Command::Mode(x, y) => write!(f, "{}", Command::mode_to_string(x, y)),
             Command::Kick(x, y, z) => write!(f, "{}", Command::kick_to_string(x, y, z)),
             Command::Away(x) => write!(f, "{}", Command::away_to_string(x)),
+            Command::Ping(x, y) => write!(f, "{}", Command::ping_to_string("PING", x, y)),
+            Command::Pong(x, y) => write!(f, "{}", Command::ping_to_string("PONG", x, y)),
+            Command::Wallops(x) => write!(f, "WALLOPS :{}", x),
+            Command::Kill(x, y) => write!(f, "KILL {} :{}", x, y),
+            Command::Kline(x, y, z) => write!(f, "{}", Command::kline_to_string(x, y, z)),
+            Command::Unkline(x) => write!(f, "UNKLINE {}", x),
+            Command::Connect(x, Some(y)) => write!(f, "CONNECT {} {}", x, y),
+            Command::Connect(x, None) => write!(f, "CONNECT {}", x),
+            Command::Links(Some(x)) => write!(f, "LINKS {}", x),
+            Command::Links(None) => write!(f, "LINKS"),
+            Command::Map => write!(f, "MAP"),
+            Command::Version(x) => write!(f, "{}", Command::query_to_string("VERSION", x)),
+            Command::Time(x) => write!(f, "{}", Command::query_to_string("TIME", x)),
+            Command::Admin(x) => write!(f, "{}", Command::query_to_string("ADMIN", x)),
+            Command::Info(x) => write!(f, "{}", Command::query_to_string("INFO", x)),
+            Command::Reply(x, y, z) => write!(f, "{} {} {}", x, y, z),
             Command::Invalid(_) => write!(f, ""),
         }
     }
@@ -592,7 +868,20 @@ mod command_test {
     #[test]
     fn build_params_for_nick_command_is_ok(){
         let command = Command::build(vec!["NICK".to_string(), "1234".to_string(), "1".to_string()]);
-        assert_eq!(command, Command::Nick("1234".to_string(), 1));
+        assert_eq!(command, Command::Nick("1234".to_string(), 1, None));
+    }
+
+    #[test]
+    fn build_nick_command_from_server_keeps_nick_time(){
+        let command = Command::build(vec!["NICK".to_string(), "pepe".to_string(), "2".to_string(), "1666000000".to_string()]);
+        assert_eq!(command, Command::Nick("pepe".to_string(), 2, Some(1666000000)));
+        assert_eq!(command.to_string(), "NICK pepe 2 1666000000");
+    }
+
+    #[test]
+    fn build_nick_command_with_invalid_hopcount_returns_needmoreparams(){
+        let command = Command::build(vec!["NICK".to_string(), "a".to_string(), "b".to_string(), "c".to_string()]);
+        assert_eq!(command, Command::Invalid((app_errors::ERR_NEEDMOREPARAMS, vec!["NICK".to_string()])));
     }
 
     #[test]
@@ -622,13 +911,44 @@ mod command_test {
     #[test]
     fn build_join_command_is_ok(){
         let command = Command::build(vec!["JOIN".to_string(), "#rust #wiki #algo".to_string(), "clave".to_string()]);
-        assert_eq!(command, Command::Join(vec!["#rust #wiki #algo".to_string()], vec![Some("clave".to_string())]));
+        assert_eq!(command, Command::Join(vec!["#rust #wiki #algo".to_string()], vec![Some("clave".to_string())], None));
     }    
 
     #[test]
     fn build_join_command_is_ok_2(){
         let command = Command::build(vec!["JOIN".to_string(), "#rust".to_string()]);
-        assert_eq!(command, Command::Join(vec!["#rust".to_string()], vec![None]));
+        assert_eq!(command, Command::Join(vec!["#rust".to_string()], vec![None], None));
+    }
+
+    #[test]
+    fn build_join_command_from_server_keeps_creation_time(){
+        let command = Command::build(vec!["JOIN".to_string(), "#rust".to_string(), "*".to_string(), "1666000000".to_string()]);
+        assert_eq!(command, Command::Join(vec!["#rust".to_string()], vec![Some("*".to_string())], Some(1666000000)));
+    }
+
+    #[test]
+    fn build_ping_command_removes_colon() {
+        let command = Command::build(vec!["PING".to_string(), ":server_uno".to_string()]);
+        assert_eq!(command, Command::Ping("server_uno".to_string(), None));
+    }
+
+    #[test]
+    fn build_ping_without_origin_returns_noorigin() {
+        let command = Command::build(vec!["PING".to_string()]);
+        assert_eq!(command, Command::Invalid((app_errors::ERR_NOORIGIN, vec![])));
+    }
+
+    #[test]
+    fn build_pong_command_with_target_is_ok() {
+        let command = Command::build(vec![
+            "PONG".to_string(),
+            "server_dos".to_string(),
+            "server_uno".to_string(),
+        ]);
+        assert_eq!(
+            command,
+            Command::Pong("server_dos".to_string(), Some("server_uno".to_string()))
+        );
     }
 
     #[test]
@@ -636,4 +956,74 @@ mod command_test {
         let command = Command::build(vec!["KICK".to_string(), "#rust".to_string(), "juan".to_string(), "bye".to_string()]);
         assert_eq!(command, Command::Kick("#rust".to_string(), "juan".to_string(), Some("bye".to_string())));
     }
+
+    #[test]
+    fn build_links_command_keeps_the_mask() {
+        let command = Command::build(vec!["LINKS".to_string()]);
+        assert_eq!(command, Command::Links(None));
+        let command = Command::build(vec![
+            "LINKS".to_string(),
+            "server_uno".to_string(),
+            "*.ar".to_string(),
+        ]);
+        assert_eq!(command, Command::Links(Some("*.ar".to_string())));
+    }
+
+    #[test]
+    fn build_server_command_strips_the_colon_of_the_info() {
+        let command = Command::build(vec![
+            "SERVER".to_string(),
+            "server_dos".to_string(),
+            "1".to_string(),
+            ":dos".to_string(),
+        ]);
+        assert_eq!(
+            command,
+            Command::Server("server_dos".to_string(), 1, "dos".to_string())
+        );
+        assert_eq!(command.to_string(), "SERVER server_dos 1 :dos");
+    }
+
+    #[test]
+    fn build_whois_command_keeps_the_server() {
+        let command = Command::build(vec!["WHOIS".to_string(), "juan".to_string()]);
+        assert_eq!(command, Command::Whois(None, "juan".to_string()));
+        let command = Command::build(vec![
+            "WHOIS".to_string(),
+            "server_dos".to_string(),
+            "juan".to_string(),
+        ]);
+        assert_eq!(
+            command,
+            Command::Whois(Some("server_dos".to_string()), "juan".to_string())
+        );
+        assert_eq!(command.to_string(), "WHOIS server_dos juan");
+    }
+
+    #[test]
+    fn build_queries_for_other_servers() {
+        let command = Command::build(vec!["VERSION".to_string()]);
+        assert_eq!(command, Command::Version(None));
+        let command = Command::build(vec!["TIME".to_string(), "server_dos".to_string()]);
+        assert_eq!(command, Command::Time(Some("server_dos".to_string())));
+        assert_eq!(command.to_string(), "TIME server_dos");
+    }
+
+    #[test]
+    fn build_reply_of_another_server() {
+        let command = Command::build(vec![
+            "391".to_string(),
+            "juan".to_string(),
+            "server_dos".to_string(),
+            ":2022-11-28 17:50:45 UTC".to_string(),
+        ]);
+        assert_eq!(
+            command,
+            Command::Reply(
+                "391".to_string(),
+                "juan".to_string(),
+                "server_dos :2022-11-28 17:50:45 UTC".to_string()
+            )
+        );
+    }
 }
@@ -1,33 +1,75 @@
+use super::admin::AdminInfo;
 use super::channel::Channel;
 use super::client_s::ClientS;
+use super::connection::Connection;
+use super::flood::FloodLimits;
+use super::irc_name::{CaseMapping, IrcName};
+use super::klines::{Kline, Klines};
+use super::mask;
+use super::operator::{Operator, Privilege};
+use super::password;
+use super::reply::{Reply, ReplyFormat};
+use super::time::{format_time, now};
+use super::validation::{self, DEFAULT_CHANNEL_LEN, DEFAULT_NICK_LEN};
 use crate::app_errors::{self, ApplicationError};
 use crate::server::client_s::message::command::Mode;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fs;
 use std::io::Write;
-use std::net::TcpStream;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// information sent to the clients in the registration burst (RPL_YOURHOST, RPL_MYINFO, RPL_ISUPPORT)
+const SERVER_VERSION: &str = concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"));
+const USER_MODES: &str = "aiow";
+const CHANNEL_MODES: &str = "beiklmnostv";
+const ISUPPORT: [&str; 4] = [
+    "CHANTYPES=#&",
+    "PREFIX=(ov)@+",
+    "CHANMODES=be,k,l,imnst",
+    "EXCEPTS=e",
+];
 
 #[derive(Clone)]
-pub struct ForeignServer(Option<Arc<Mutex<TcpStream>>>, i32, String, String); // stream, hopcount (distance), name, 1st_server_in_path
-pub struct ForeignClient(Arc<Mutex<TcpStream>>, i32, Option<String>, Option<String>); // stream, hopcount, server_name, away_msg
+pub struct ForeignServer(Option<Arc<Mutex<Connection>>>, i32, String, String, String); // stream, hopcount (distance), info, 1st_server_in_path, uplink
+pub struct ForeignClient(Arc<Mutex<Connection>>, i32, Option<String>, Option<String>); // stream, hopcount, server_name, away_msg
 
 pub struct ClientsInfo {
     server_name: String,
-    users: HashMap<String, ClientS>,
-    streams: HashMap<String, ForeignClient>,
-    channels: HashMap<String, Channel>,
-    server_operators: HashMap<String, String>,
-    active_opers: HashSet<String>,
+    users: HashMap<IrcName, ClientS>,
+    streams: HashMap<IrcName, ForeignClient>,
+    channels: HashMap<IrcName, Channel>,
+    server_operators: HashMap<String, Operator>,
+    active_opers: HashMap<IrcName, HashSet<Privilege>>, // privileges of each oper, none for the ones of other servers
+    user_modes: HashMap<IrcName, HashSet<char>>, // 'i' (invisible) and 'w' (wallops) of each user
+    nick_times: HashMap<IrcName, u64>, // when each user took their nick, the older one wins a collision
     server_password: Option<String>,
     servers: HashMap<String, ForeignServer>,
+    reply_format: ReplyFormat,
+    motd_path: Option<String>,
+    created: u64, // seconds since the unix epoch
+    ping_timeout: Duration,
+    nick_len: usize,
+    channel_len: usize,
+    reserved_nicks: Vec<String>,
+    casemapping: CaseMapping,
+    klines: Klines,
+    flood_limits: FloodLimits,
+    admin_info: AdminInfo,
 }
 
+// seconds of inactivity before a connection is pinged, used if the config doesn't say otherwise
+pub const DEFAULT_PING_TIMEOUT: u64 = 120;
+
 // new error codes (that are too long to be written in the code)
 pub type DefaultAndError = Result<(), ((i32, &'static str), Vec<String>)>;
 pub type ReplyAndError =
     Result<((i32, &'static str), Vec<String>), ((i32, &'static str), Vec<String>)>;
 pub type StringAndError = Result<Option<String>, ((i32, &'static str), Vec<String>)>;
+pub type RepliesAndError =
+    Result<Vec<((i32, &'static str), Vec<String>)>, ((i32, &'static str), Vec<String>)>;
+pub type BoolAndError = Result<bool, ((i32, &'static str), Vec<String>)>;
 
 /// clients info is the responsible of storing all the information about the clients, channels and servers
 /// and allow all the comunication between a server and its clients.
@@ -36,7 +78,7 @@ impl ClientsInfo {
     pub fn new(
         server_name: String,
         server_password: Option<String>,
-        server_operators: HashMap<String, String>,
+        server_operators: HashMap<String, Operator>,
     ) -> ClientsInfo {
         ClientsInfo {
             server_name,
@@ -44,10 +86,229 @@ impl ClientsInfo {
             streams: HashMap::new(),
             channels: HashMap::new(),
             server_operators,
-            active_opers: HashSet::new(),
+            active_opers: HashMap::new(),
+            user_modes: HashMap::new(),
+            nick_times: HashMap::new(),
             server_password,
             servers: HashMap::new(),
+            reply_format: ReplyFormat::default(),
+            motd_path: None,
+            created: now(),
+            ping_timeout: Duration::from_secs(DEFAULT_PING_TIMEOUT),
+            nick_len: DEFAULT_NICK_LEN,
+            channel_len: DEFAULT_CHANNEL_LEN,
+            reserved_nicks: vec![],
+            casemapping: CaseMapping::default(),
+            klines: Klines::default(),
+            flood_limits: FloodLimits::default(),
+            admin_info: AdminInfo::default(),
+        }
+    }
+
+    /// Given the format read from the config sets the format of the numeric replies
+    pub fn set_reply_format(&mut self, reply_format: ReplyFormat) {
+        self.reply_format = reply_format;
+    }
+
+    pub fn reply_format(&self) -> ReplyFormat {
+        self.reply_format
+    }
+
+    pub fn server_name(&self) -> String {
+        self.server_name.clone()
+    }
+
+    /// Given the path read from the config sets the file with the message of the day
+    pub fn set_motd_path(&mut self, motd_path: Option<String>) {
+        self.motd_path = motd_path;
+    }
+
+    /// Given the timeout read from the config sets how long a connection may be idle
+    /// before being pinged (and, after the ping, before being dropped)
+    pub fn set_ping_timeout(&mut self, ping_timeout: Duration) {
+        self.ping_timeout = ping_timeout;
+    }
+
+    pub fn ping_timeout(&self) -> Duration {
+        self.ping_timeout
+    }
+
+    /// Given the lengths and reserved nicks read from the config sets the limits
+    /// checked on the nicks and channel names chosen by the local users
+    pub fn set_name_limits(&mut self, nick_len: usize, channel_len: usize, reserved: Vec<String>) {
+        self.nick_len = nick_len;
+        self.channel_len = channel_len;
+        self.reserved_nicks = reserved;
+    }
+
+    /// Given the casemapping read from the config sets how nicks and channel names are compared
+    pub fn set_casemapping(&mut self, casemapping: CaseMapping) {
+        self.casemapping = casemapping;
+    }
+
+    /// Given the limits read from the config sets how fast the local users may send messages
+    pub fn set_flood_limits(&mut self, flood_limits: FloodLimits) {
+        self.flood_limits = flood_limits;
+    }
+
+    /// Given the administrative info read from the config sets the one answered to ADMIN
+    pub fn set_admin_info(&mut self, admin_info: AdminInfo) {
+        self.admin_info = admin_info;
+    }
+
+    pub fn flood_limits(&self) -> FloodLimits {
+        self.flood_limits
+    }
+
+    /// Given the K-lines read from the file of the config sets the connections banned from the server
+    pub fn set_klines(&mut self, klines: Klines) {
+        self.klines = klines;
+    }
+
+    /// Given the host of a new connection returns the reason of the K-line that bans any user from it
+    pub fn host_kline(&self, host: &str) -> Option<String> {
+        self.klines
+            .find_host(host, self.casemapping)
+            .map(|kline| kline.reason.clone())
+    }
+
+    /// Given the username and host of a user being registered returns the reason of the K-line that bans them
+    pub fn user_kline(&self, user: &str, host: &str) -> Option<String> {
+        self.klines
+            .find(&format!("{}@{}", user, host), self.casemapping)
+            .map(|kline| kline.reason.clone())
+    }
+
+    /// Given the operator that sends it, the 'user@host' mask, the minutes it lasts (permanent if None)
+    /// and the reason adds a K-line and saves it to the file. A mask without '@' bans any user of the host.
+    /// If the file can't be written the K-line is still in force until the server restarts and ERR_FILEERROR
+    /// is returned.
+    pub fn add_kline(
+        &mut self,
+        oper: &str,
+        mask: String,
+        minutes: Option<u64>,
+        reason: String,
+    ) -> DefaultAndError {
+        if !self.has_privilege(oper, Privilege::Kline) {
+            return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        let kline = Kline {
+            mask: Self::kline_mask(mask),
+            reason,
+            expiry: minutes.map(|minutes| now() + minutes * 60),
+        };
+        self.klines
+            .add(kline, self.casemapping)
+            .map_err(|err| self.kline_save_error(err))
+    }
+
+    /// Given the operator that sends it and a 'user@host' mask removes its K-line and saves the file.
+    /// Returns whether there was a K-line with that mask, or ERR_FILEERROR if the file can't be written.
+    pub fn remove_kline(&mut self, oper: &str, mask: String) -> BoolAndError {
+        if !self.has_privilege(oper, Privilege::Kline) {
+            return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        self.klines
+            .remove(&Self::kline_mask(mask), self.casemapping)
+            .map_err(|err| self.kline_save_error(err))
+    }
+
+    /// Given the error saving the K-lines logs it and returns the reply for the operator
+    fn kline_save_error(&self, err: Box<dyn Error>) -> ((i32, &'static str), Vec<String>) {
+        println!("Error saving the klines: {}", err);
+        let path = self.klines.path().unwrap_or_default().to_string();
+        (app_errors::ERR_FILEERROR, vec!["write".to_string(), path])
+    }
+
+    /// Given the mask of a K-line as written by the operator returns it in the 'user@host' form
+    fn kline_mask(mask: String) -> String {
+        if mask.contains('@') {
+            mask
+        } else {
+            format!("*@{}", mask)
+        }
+    }
+
+    /// Given a nick or channel name returns it as the key used by the maps of clients and channels
+    fn irc_name(&self, name: &str) -> IrcName {
+        IrcName::new(name, self.casemapping)
+    }
+
+    /// Given two nicks or channel names returns whether they are the same for the casemapping
+    pub fn is_same_name(&self, name: &str, other: &str) -> bool {
+        self.irc_name(name) == self.irc_name(other)
+    }
+
+    /// Given a nick chosen by a local user returns whether it follows the RFC grammar,
+    /// fits in NICKLEN and isn't one of the reserved nicks
+    pub fn is_valid_nick(&self, nick: &str) -> bool {
+        validation::is_valid_nick(nick, self.nick_len)
+            && !validation::is_reserved_nick(nick, &self.reserved_nicks, self.casemapping)
+    }
+
+    /// Given a channel name chosen by a local user returns whether it follows the RFC grammar
+    /// and fits in CHANNELLEN
+    pub fn is_valid_channel(&self, channel: &str) -> bool {
+        validation::is_valid_channel(channel, self.channel_len)
+    }
+
+    /// Given the 'nick!user@host' mask of a newly registered user returns the replies
+    /// that must be sent to them: RPL_WELCOME to RPL_ISUPPORT followed by the MOTD
+    pub fn registration_burst(&self, mask: String) -> Vec<((i32, &'static str), Vec<String>)> {
+        let mut burst = vec![
+            (app_errors::RPL_WELCOME, vec![mask]),
+            (
+                app_errors::RPL_YOURHOST,
+                vec![self.server_name.clone(), SERVER_VERSION.to_string()],
+            ),
+            (
+                app_errors::RPL_CREATED,
+                vec![format_time(self.created)],
+            ),
+            (
+                app_errors::RPL_MYINFO,
+                vec![
+                    self.server_name.clone(),
+                    SERVER_VERSION.to_string(),
+                    USER_MODES.to_string(),
+                    CHANNEL_MODES.to_string(),
+                ],
+            ),
+            (app_errors::RPL_ISUPPORT, vec![self.isupport()]),
+        ];
+        burst.append(&mut self.motd());
+        burst
+    }
+
+    /// Returns the tokens advertised in RPL_ISUPPORT, the lengths depend on the config
+    fn isupport(&self) -> String {
+        format!(
+            "{} CASEMAPPING={} NICKLEN={} CHANNELLEN={}",
+            ISUPPORT.join(" "),
+            self.casemapping.name(),
+            self.nick_len,
+            self.channel_len
+        )
+    }
+
+    /// Returns the MOTD replies, the file is read every time so it can be edited while the server runs.
+    /// If there is no MOTD file (or it can't be read) ERR_NOMOTD is returned.
+    pub fn motd(&self) -> Vec<((i32, &'static str), Vec<String>)> {
+        let contents = match &self.motd_path {
+            Some(path) => fs::read_to_string(path),
+            None => return vec![(app_errors::ERR_NOMOTD, vec![])],
+        };
+        let contents = match contents {
+            Ok(contents) => contents,
+            Err(_) => return vec![(app_errors::ERR_NOMOTD, vec![])],
+        };
+        let mut motd = vec![(app_errors::RPL_MOTDSTART, vec![self.server_name.clone()])];
+        for line in contents.lines() {
+            motd.push((app_errors::RPL_MOTD, vec![line.to_string()]));
         }
+        motd.push((app_errors::RPL_ENDOFMOTD, vec![]));
+        motd
     }
 
     pub fn send_privmsg(
@@ -61,18 +322,23 @@ impl ClientsInfo {
         // Ej. enviar a todos los  $*.fi.uba el mensaje 'server en mantenimietno'
         if to.starts_with('&') | to.starts_with('#') {
             // if it is a channel
-            if !self.channels.contains_key(&to) {
+            if !self.channels.contains_key(&self.irc_name(&to)) {
                 return Err((app_errors::ERR_NOSUCHNICK, vec![to]));
             }
+            // los bans y el modo moderado para usuarios de otros servidores los controla su servidor
+            let mask = match server_name {
+                Some(_) => None,
+                None => self.client_mask(&from),
+            };
             let channel = self
                 .channels
-                .get_mut(&to)
+                .get_mut(&self.irc_name(&to))
                 .expect("Error getting reciver message during privmsg");
-            channel.send(from.clone(), msg.clone())?;
+            channel.send(from.clone(), mask, msg.clone())?;
 
             //INFORMO A LOS VECINOS
             for (neighbour_name, server) in self.servers.iter() {
-                let ForeignServer(stream, _hopcount, _info, _path) = server;
+                let ForeignServer(stream, _hopcount, _info, _path, _uplink) = server;
                 if server_name.is_some()
                     && *neighbour_name == server_name.clone().expect("Error: server name is none")
                 {
@@ -89,19 +355,20 @@ impl ClientsInfo {
             return Ok(());
         }
         // si no tiene destinatario
-        if !self.users.contains_key(&to) {
+        if !self.users.contains_key(&self.irc_name(&to)) {
             return Err((app_errors::ERR_NOSUCHNICK, vec![to]));
         }
 
         // 
-        let ForeignClient(user, _hopcount, _server, away_msg) = match self.streams.get(&to) {
-            Some(user) => user,
-            None => return Err((app_errors::ERR_NOSUCHNICK, vec![to])),
-        };
+        let ForeignClient(user, _hopcount, _server, away_msg) =
+            match self.streams.get(&self.irc_name(&to)) {
+                Some(user) => user,
+                None => return Err((app_errors::ERR_NOSUCHNICK, vec![to])),
+            };
 
         Self::write_message(format!(":{} PRIVMSG {} {}\n", from, to, msg), user.clone());
         if let Some(away_msg) = away_msg {
-            let ForeignClient(origin, _, _, _) = match self.streams.get(&from) {
+            let ForeignClient(origin, _, _, _) = match self.streams.get(&self.irc_name(&from)) {
                 Some(origin) => origin,
                 None => return Err((app_errors::ERR_NOSUCHNICK, vec![from])),
             };
@@ -113,46 +380,198 @@ impl ClientsInfo {
         Ok(())
     }
 
-    pub fn contains_client(&mut self, nick: &String) -> bool {
-        self.streams.contains_key(nick)
+    /// Given a nick returns the 'nick!user@host' mask of the user.
+    /// The host of users of other servers is the name of the server they come from.
+    pub fn client_mask(&self, nick: &String) -> Option<String> {
+        let ForeignClient(_stream, hopcount, server, _away_msg) =
+            self.streams.get(&self.irc_name(nick))?;
+        let client = self.users.get(&self.irc_name(nick))?;
+        let host = match hopcount {
+            0 => client.host(),
+            _ => server.clone().unwrap_or_default(),
+        };
+        Some(format!(
+            "{}!{}@{}",
+            nick,
+            client.user.clone().unwrap_or_default(),
+            host
+        ))
+    }
+
+    pub fn contains_client(&mut self, nick: &str) -> bool {
+        self.streams.contains_key(&self.irc_name(nick))
     }
 
-    pub fn contains_channel(&mut self, name: &String) -> bool {
-        self.channels.contains_key(name)
+    /// Given a nick and a connection returns whether the user with that nick is the one of the connection
+    pub fn is_connection_of(&self, nick: &str, stream: &Arc<Mutex<Connection>>) -> bool {
+        matches!(
+            self.streams.get(&self.irc_name(nick)),
+            Some(ForeignClient(client_stream, ..)) if Arc::ptr_eq(client_stream, stream)
+        )
+    }
+
+    pub fn contains_channel(&mut self, name: &str) -> bool {
+        self.channels.contains_key(&self.irc_name(name))
+    }
+
+    /// Given the clients and the password sent by a connection returns whether it's the one of the server
+    /// (if it has one). It's verified without holding the lock, a hash takes a while to check
+    pub fn password_matches(clients: &Mutex<ClientsInfo>, password: &Option<String>) -> bool {
+        let stored = clients
+            .lock()
+            .expect("Error: poisoned clients lock while checking the password")
+            .server_password
+            .clone();
+        match (stored, password) {
+            (None, _) => true,
+            (Some(stored), Some(password)) => password::verify(&stored, password),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Given the clients, the nick of who sends OPER, the name and password of the operator block and the user
+    /// and host of the connection makes the user an operator with the privileges of the block.
+    /// The block must allow the host (ERR_NOOPERHOST otherwise) and the password must match, it's verified
+    /// without holding the lock (a hash takes a while to check) and the lock is taken again to apply the result.
+    pub fn oper_login(
+        clients: &Mutex<ClientsInfo>,
+        nick: &str,
+        name: &str,
+        password: &str,
+        user: &str,
+        host: &str,
+    ) -> ReplyAndError {
+        let stored = clients
+            .lock()
+            .expect("Error: poisoned clients lock during OPER")
+            .oper_password(name, user, host)?;
+        if !password::verify(&stored, password) {
+            return Err((app_errors::ERR_PASSWDMISMATCH, vec![]));
+        }
+        clients
+            .lock()
+            .expect("Error: poisoned clients lock during OPER")
+            .grant_oper(nick, name)
     }
 
-    pub fn oper_login(&mut self, nick: &String, password: &String) -> ReplyAndError {
-        if let Some(pass) = self.server_operators.get(nick) {
-            if password == pass {
-                self.active_opers.insert(nick.clone());
-                return Ok((app_errors::RPL_YOUREOPER, vec![]));
+    /// Given the name of an operator block and the user and host of a connection returns the password of the block
+    /// (hashed or plaintext), ERR_NOOPERHOST if there is no such block or it doesn't allow the host
+    fn oper_password(
+        &self,
+        name: &str,
+        user: &str,
+        host: &str,
+    ) -> Result<String, ((i32, &'static str), Vec<String>)> {
+        match self.server_operators.get(name) {
+            Some(operator) if operator.allows_host(user, host, self.casemapping) => {
+                Ok(operator.password.clone())
             }
+            _ => Err((app_errors::ERR_NOOPERHOST, vec![])),
         }
-        Err((app_errors::ERR_PASSWDMISMATCH, vec![]))
     }
 
-    pub fn add_client(
+    /// Given a nick and the name of the operator block whose password it sent makes it an operator
+    /// with the privileges of the block
+    fn grant_oper(&mut self, nick: &str, name: &str) -> ReplyAndError {
+        let privileges = match self.server_operators.get(name) {
+            Some(operator) => operator.privileges.clone(),
+            None => return Err((app_errors::ERR_NOOPERHOST, vec![])),
+        };
+        self.active_opers.insert(self.irc_name(nick), privileges);
+        self.notify_servers(format!(":{} MODE {} +o\n", nick, nick), None)?;
+        Ok((app_errors::RPL_YOUREOPER, vec![]))
+    }
+
+    /// Given a nick returns whether it's an operator of this server with the privilege
+    pub fn has_privilege(&self, nick: &str, privilege: Privilege) -> bool {
+        self.active_opers
+            .get(&self.irc_name(nick))
+            .is_some_and(|privileges| privileges.contains(&privilege))
+    }
+
+    /// Given a user whose password was already verified, its hopcount and the server it comes from
+    /// (if it isn't local) adds it to the server. ERR_NICKNAMEINUSE (or ERR_NICKCOLLISION) if the nick is taken
+    pub fn register_client(
         &mut self,
         nick: String,
         client: ClientS,
-        stream: Arc<Mutex<TcpStream>>,
-        password: Option<String>,
+        stream: Arc<Mutex<Connection>>,
         hopcount: i32,
         server_name: Option<String>,
     ) -> DefaultAndError {
-        if self.server_password.is_some() && password != self.server_password {
-            return Err((app_errors::ERR_PASSWDMISMATCH, vec![]));
-        };
         if self.contains_client(&nick) {
-            return Err((app_errors::ERR_NICKCOLLISION, vec![nick]));
+            if server_name.is_some() {
+                return Err((app_errors::ERR_NICKCOLLISION, vec![nick]));
+            }
+            return Err((app_errors::ERR_NICKNAMEINUSE, vec![nick]));
+        }
+        self.insert_client(nick, client, stream, hopcount, server_name, now());
+        Ok(())
+    }
+
+    /// Given a user that another server introduces (its nick, the link it comes through and when it took the nick)
+    /// adds them to this server. If the nick is already used the older one wins, the newer is killed on the side of
+    /// the network that has it (each server kills its own, so the link isn't told). If both are as old, or the other
+    /// server didn't say when, both are killed: neither side can tell which one to keep and the other server does
+    /// the same, so both sides stay with the same users. Returns whether the user was added.
+    pub fn introduce_client(
+        &mut self,
+        nick: String,
+        client: ClientS,
+        stream: Arc<Mutex<Connection>>,
+        hopcount: i32,
+        server_name: String,
+        nick_time: Option<u64>,
+    ) -> BoolAndError {
+        if self.contains_client(&nick) {
+            let (ours_wins, theirs_wins) = self.collision_winners(&nick, nick_time);
+            println!("Nick collision: {}", nick);
+            if !ours_wins {
+                self.kill_client(
+                    self.server_name.clone(),
+                    nick.clone(),
+                    "Nick collision".to_string(),
+                    Some(server_name.clone()),
+                )?;
+            }
+            if !theirs_wins {
+                return Ok(false);
+            }
+        }
+        let nick_time = nick_time.unwrap_or_else(now);
+        self.insert_client(nick, client, stream, hopcount, Some(server_name), nick_time);
+        Ok(true)
+    }
+
+    /// Given a nick in use here and when another server says its user took it returns whether ours is older
+    /// and whether theirs is. Neither wins if both are as old or the other server didn't say when.
+    fn collision_winners(&self, nick: &str, nick_time: Option<u64>) -> (bool, bool) {
+        let ours = self.nick_times.get(&self.irc_name(nick)).copied();
+        match (ours, nick_time) {
+            (Some(ours), Some(theirs)) => (ours < theirs, theirs < ours),
+            _ => (false, false),
         }
-        self.users.insert(nick.clone(), client.clone());
+    }
+
+    /// Given a user whose nick is free and when they took it adds them and tells the neighbour servers
+    /// (except the one the user comes through) with NICK and USER
+    fn insert_client(
+        &mut self,
+        nick: String,
+        client: ClientS,
+        stream: Arc<Mutex<Connection>>,
+        hopcount: i32,
+        server_name: Option<String>,
+        nick_time: u64,
+    ) {
+        self.users.insert(self.irc_name(&nick), client.clone());
         self.streams.insert(
-            nick.clone(),
+            self.irc_name(&nick),
             ForeignClient(stream, hopcount, server_name.clone(), None),
         );
+        self.nick_times.insert(self.irc_name(&nick), nick_time);
         for (neighbour_name, foreign_server) in self.servers.iter_mut() {
-            let ForeignServer(stream, hopcount, _info, _path) = foreign_server;
+            let ForeignServer(stream, hopcount, _info, _path, _uplink) = foreign_server;
 
             if let Some(stream) = stream {
                 if let Some(sender) = server_name.clone() {
@@ -165,10 +584,11 @@ impl ClientsInfo {
                     .expect("Error: server stream is none")
                     .write_all(
                         format!(
-                            ":{} NICK {} {}\n",
+                            ":{} NICK {} {} {}\n",
                             self.server_name.clone(),
                             nick,
-                            *hopcount + 1
+                            *hopcount + 1,
+                            nick_time
                         )
                         .as_bytes(),
                     )
@@ -194,13 +614,20 @@ impl ClientsInfo {
                     .expect("Error writing to server");
             }
         }
-        Ok(())
     }
 
     pub fn names(&mut self, channels: Vec<String>, from: String) {
+        let hidden = self.hidden_from(&from);
+        let casemapping = self.casemapping;
+        let is_hidden = |name: &String| {
+            hidden.contains(&IrcName::new(
+                name.trim_start_matches(['@', '+']),
+                casemapping,
+            ))
+        };
         let ForeignClient(stream, _hopcount, _server, _away_msg) = self
             .streams
-            .get_mut(&from)
+            .get_mut(&self.irc_name(&from))
             .expect("Error obtainging streams");
         if channels.is_empty() {
             //TODO: refactor
@@ -208,7 +635,8 @@ impl ClientsInfo {
                 if channel.is_secret() && !channel.contains_client(&from) {
                     continue;
                 }
-                let names = channel.get_names();
+                let mut names = channel.get_prefixed_names();
+                names.retain(|name| !is_hidden(name));
                 Self::write_message(
                     format!("{}: {};", channel_name, names.join(" ")),
                     stream.clone(),
@@ -218,9 +646,13 @@ impl ClientsInfo {
             return;
         }
         for channel_name in channels {
-            match self.channels.get_mut(&channel_name) {
+            match self
+                .channels
+                .get_mut(&IrcName::new(&channel_name, casemapping))
+            {
                 Some(channel) => {
-                    let names = channel.get_names();
+                    let mut names = channel.get_prefixed_names();
+                    names.retain(|name| !is_hidden(name));
                     Self::write_message(
                         format!("{}: {};", channel_name, names.join(" ")),
                         stream.clone(),
@@ -235,7 +667,7 @@ impl ClientsInfo {
     pub fn list(&mut self, channels: Vec<String>, from: String) {
         let ForeignClient(stream, _hopcount, _server, _away_msg) = self
             .streams
-            .get_mut(&from)
+            .get_mut(&self.irc_name(&from))
             .expect("Error obtainging streams");
         if channels.is_empty() {
             for (channel_name, channel) in self.channels.iter_mut() {
@@ -246,7 +678,10 @@ impl ClientsInfo {
             return;
         }
         for channel_name in channels {
-            match self.channels.get_mut(&channel_name) {
+            match self
+                .channels
+                .get_mut(&IrcName::new(&channel_name, self.casemapping))
+            {
                 Some(channel) => {
                     if let Ok(Some(topic)) = channel.get_topic(from.clone()) {
                         Self::write_message(
@@ -278,20 +713,21 @@ impl ClientsInfo {
         if let Some(comment) = comment {
             unwrapped_comment = comment;
         }
-        match self.channels.get_mut(&channel_name) {
+        match self.channels.get_mut(&self.irc_name(&channel_name)) {
             Some(channel) => {
                 if sender.is_none() {
                     if let Some(nick) = nick {
                         if !channel.is_oper(&nick) {
-                            return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                            return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
                         }
                     }
                 }
                 if !channel.contains_client(&kicked) {
                     return Err((app_errors::ERR_NOSUCHNICK, vec![kicked]));
                 }
-                if let Some(ForeignClient(kicked_stream, kicked_hopcount, _, _)) =
-                    self.streams.get_mut(&kicked)
+                if let Some(ForeignClient(kicked_stream, kicked_hopcount, _, _)) = self
+                    .streams
+                    .get_mut(&IrcName::new(&kicked, self.casemapping))
                 {
                     if *kicked_hopcount == 0 {
                         kicked_stream
@@ -309,23 +745,21 @@ impl ClientsInfo {
                 }
                 channel.remove_if_present(&kicked);
                 if channel.is_empty() {
-                    self.channels.remove(&channel_name);
+                    self.channels.remove(&self.irc_name(&channel_name));
                 }
-                if let Err(code) = self.notify_servers(
+                self.notify_servers(
                     format!(
                         ":{} KICK {} {} {}\n",
                         unwrapped_prefix, channel_name, kicked, unwrapped_comment
                     ),
                     sender,
-                ) {
-                    return Err(code);
-                }
+                )?;
             }
             None => {
                 return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]));
             }
         }
-        Ok((app_errors::RPL_SUCCESS, vec![]))
+        Ok((app_errors::RPL_NONE, vec![]))
     }
 
     pub fn send_invite(
@@ -336,7 +770,7 @@ impl ClientsInfo {
         prefix: Option<String>,
         sender: Option<String>,
     ) -> ReplyAndError {
-        if !self.users.contains_key(&invited) {
+        if !self.users.contains_key(&self.irc_name(&invited)) {
             return Err((app_errors::ERR_NOSUCHNICK, vec![invited]));
         }
         let unwrapped_prefix;
@@ -345,19 +779,19 @@ impl ClientsInfo {
         } else {
             unwrapped_prefix = nick.clone().expect("No nick 'KICK'");
         }
-        match self.channels.get_mut(&channel_name) {
+        match self.channels.get_mut(&self.irc_name(&channel_name)) {
             Some(channel) => {
                 if sender.is_none() {
                     if let Some(nick) = nick {
                         if !channel.is_oper(&nick) {
-                            return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                            return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
                         }
                     }
                 }
                 match channel.invite(&invited) {
                     Ok(code) => {
                         if let Some(ForeignClient(invited_stream, invited_hopcount, _, _)) =
-                            self.streams.get_mut(&invited)
+                            self.streams.get_mut(&self.irc_name(&invited))
                         {
                             if *invited_hopcount == 0 {
                                 invited_stream
@@ -391,50 +825,72 @@ impl ClientsInfo {
         }
     }
 
+    /// Given the channel, the new topic (if any) and the nick of the user changes the topic of the channel
+    /// or returns the replies with the current topic and who set it.
     pub fn topic(
         &mut self,
         channel_name: String,
         new_topic: Option<String>,
         from: String,
-    ) -> StringAndError {
-        if let Some(channel) = self.channels.get_mut(&channel_name) {
-            match new_topic {
-                Some(new_topic) => channel.set_topic(new_topic, from),
-                None => channel.get_topic(from),
+    ) -> RepliesAndError {
+        let channel = match self.channels.get_mut(&self.irc_name(&channel_name)) {
+            Some(channel) => channel,
+            None => return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name])),
+        };
+        if let Some(new_topic) = new_topic {
+            channel.set_topic(new_topic, from)?;
+            return Ok(vec![]);
+        }
+        match channel.get_topic(from)? {
+            Some(topic) => {
+                let mut replies = vec![(app_errors::RPL_TOPIC, vec![channel_name.clone(), topic])];
+                if let Some(topic_who_time) = self.topic_who_time(&channel_name) {
+                    replies.push(topic_who_time);
+                }
+                Ok(replies)
             }
-        } else {
-            Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+            None => Ok(vec![(app_errors::RPL_NOTOPIC, vec![channel_name])]),
         }
     }
+
+    /// Given the channel returns the RPL_TOPICWHOTIME reply with who set its topic and when,
+    /// if it has a topic
+    pub fn topic_who_time(
+        &mut self,
+        channel_name: &str,
+    ) -> Option<((i32, &'static str), Vec<String>)> {
+        let (setter, time) = self
+            .channels
+            .get_mut(&self.irc_name(channel_name))?
+            .get_topic_setter()?;
+        Some((
+            app_errors::RPL_TOPICWHOTIME,
+            vec![channel_name.to_string(), setter, time.to_string()],
+        ))
+    }
     pub fn quit_client(
         &mut self,
         nick: String,
         message: Option<String>,
         issuer: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
-        if !self.streams.contains_key(&nick) {
+        if !self.streams.contains_key(&self.irc_name(&nick)) {
             return Err(Box::new(ApplicationError("Client not found.".into())));
         }
-        self.streams.remove_entry(&nick);
-        self.users.remove_entry(&nick);
         println!("Quitting client {}", nick);
-        let mut empty_chans = Vec::new();
-        for (channel_name, channel) in self.channels.iter_mut() {
-            channel.remove_if_present(&nick);
-            if channel.is_empty() {
-                empty_chans.push(channel_name.clone());
-            }
-        }
-        for channel_name in empty_chans {
-            self.channels.remove(&channel_name);
-        }
         let mut msg = String::new();
         if let Some(message) = message {
             msg = message;
         }
+        self.remove_client(&nick, &msg);
         for (_neighbour_name, foreign_server) in self.servers.iter_mut() {
-            let ForeignServer(neighbour_stream, _neighbour_hopcount, _neighbour_info, path) =
-                foreign_server;
+            let ForeignServer(
+                neighbour_stream,
+                _neighbour_hopcount,
+                _neighbour_info,
+                path,
+                _uplink,
+            ) = foreign_server;
             if let Some(neighbour_stream) = neighbour_stream {
                 if issuer.is_some()
                     && *path
@@ -455,37 +911,217 @@ impl ClientsInfo {
         Ok(())
     }
 
+    /// Given the nick of a user that leaves the network and the message removes them from this server.
+    /// The local users sharing a channel with them are told with a QUIT, the channels left empty are removed.
+    fn remove_client(&mut self, nick: &str, msg: &str) {
+        let quitting = self.irc_name(nick);
+        self.streams.remove_entry(&quitting);
+        self.users.remove_entry(&quitting);
+        self.user_modes.remove(&quitting);
+        self.active_opers.remove(&quitting);
+        self.nick_times.remove(&quitting);
+        // avisa a los usuarios locales que comparten algun canal con el cliente
+        let mut notified = HashSet::new();
+        let mut empty_chans = Vec::new();
+        for (channel_name, channel) in self.channels.iter_mut() {
+            if channel.contains_client(nick) {
+                for (member, stream) in channel.local_members() {
+                    if IrcName::new(&member, self.casemapping) != quitting
+                        && notified.insert(member)
+                    {
+                        Self::write_message(format!(":{} QUIT :{}\n", nick, msg), stream);
+                    }
+                }
+            }
+            channel.remove_if_present(nick);
+            if channel.is_empty() {
+                empty_chans.push(channel_name.clone());
+            }
+        }
+        for channel_name in empty_chans {
+            self.channels.remove(&channel_name);
+        }
+    }
+
+    /// Given the operator (or server) that sends it, the nick of the user and the reason removes the user
+    /// from the network. A local user is sent an ERROR and their connection is closed, the local users
+    /// sharing a channel with them see a QUIT and the KILL is relayed to the neighbour servers
+    /// (except the one it comes from) until it reaches the server of the user.
+    pub fn kill_client(
+        &mut self,
+        killer: String,
+        nick: String,
+        reason: String,
+        sender: Option<String>,
+    ) -> DefaultAndError {
+        if sender.is_none() && !self.has_privilege(&killer, Privilege::Kill) {
+            return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        if nick == self.server_name || self.servers.contains_key(&nick) {
+            return Err((app_errors::ERR_CANTKILLSERVER, vec![]));
+        }
+        let (victim, ForeignClient(stream, hopcount, _server, _away_msg)) =
+            match self.streams.get_key_value(&self.irc_name(&nick)) {
+                Some(client) => client,
+                None => return Err((app_errors::ERR_NOSUCHNICK, vec![nick])),
+            };
+        let victim = victim.to_string();
+        let msg = format!("Killed ({} ({}))", killer, reason);
+        if *hopcount == 0 {
+            let stream = stream.clone();
+            Self::write_message(
+                format!("ERROR :Closing Link: {} ({})\n", victim, msg),
+                stream.clone(),
+            );
+            // el hilo del usuario termina al ver la conexion cerrada
+            if let Ok(connection) = stream.lock() {
+                connection.shutdown();
+            };
+        }
+        println!("Killing client {}", victim);
+        self.remove_client(&victim, &msg);
+        self.notify_servers(format!(":{} KILL {} :{}\n", killer, victim, reason), sender)
+    }
+
+    /// Given the current and the new nick of a user renames them keeping their channels,
+    /// modes and away message. Everyone sharing a channel with the user (and the user) is told
+    /// with ':old NICK new' and the neighbour servers, except the one it comes from, with
+    /// ':old NICK new hopcount time' so every server gives the new nick the same age.
+    /// A rename that comes from another server (the sender) onto a nick in use here is resolved as in
+    /// introduce_client: the older one wins and each server kills its own, or both if neither is older.
+    pub fn rename_client(
+        &mut self,
+        old_nick: String,
+        new_nick: String,
+        sender: Option<String>,
+        nick_time: Option<u64>,
+    ) -> DefaultAndError {
+        let hopcount = match self.streams.get(&self.irc_name(&old_nick)) {
+            Some(ForeignClient(_stream, hopcount, _server, _away_msg)) => *hopcount,
+            None => return Err((app_errors::ERR_NOSUCHNICK, vec![old_nick])),
+        };
+        // un usuario puede cambiar solo las mayusculas de su nick
+        let case_change = self.is_same_name(&old_nick, &new_nick);
+        if !case_change && self.streams.contains_key(&self.irc_name(&new_nick)) {
+            let server_name = match sender.clone() {
+                Some(server_name) => server_name,
+                None => return Err((app_errors::ERR_NICKNAMEINUSE, vec![new_nick])),
+            };
+            let (ours_wins, theirs_wins) = self.collision_winners(&new_nick, nick_time);
+            println!("Nick collision: {}", new_nick);
+            if !ours_wins {
+                self.kill_client(
+                    self.server_name.clone(),
+                    new_nick.clone(),
+                    "Nick collision".to_string(),
+                    Some(server_name.clone()),
+                )?;
+            }
+            if !theirs_wins {
+                // el otro servidor mata al suyo, aca se lo saca con el nick que tenia
+                return self.kill_client(
+                    self.server_name.clone(),
+                    old_nick,
+                    "Nick collision".to_string(),
+                    Some(server_name),
+                );
+            }
+        }
+        let nick_time = nick_time.unwrap_or_else(now);
+        let message = format!(":{} NICK {}\n", old_nick, new_nick);
+
+        // avisa al usuario y a los usuarios locales que comparten algun canal con el
+        let mut notified = HashSet::new();
+        if let Some(ForeignClient(stream, 0, _server, _away_msg)) =
+            self.streams.get(&self.irc_name(&old_nick))
+        {
+            notified.insert(self.irc_name(&old_nick));
+            Self::write_message(message.clone(), stream.clone());
+        }
+        for channel in self.channels.values_mut() {
+            if channel.contains_client(&old_nick) {
+                for (member, stream) in channel.local_members() {
+                    if notified.insert(IrcName::new(&member, self.casemapping)) {
+                        Self::write_message(message.clone(), stream);
+                    }
+                }
+            }
+            // las invitaciones pendientes estan en canales donde todavia no entro
+            channel.rename(&old_nick, &new_nick);
+        }
+
+        if let Some(client) = self.streams.remove(&self.irc_name(&old_nick)) {
+            self.streams.insert(self.irc_name(&new_nick), client);
+        }
+        if let Some(user) = self.users.remove(&self.irc_name(&old_nick)) {
+            self.users.insert(self.irc_name(&new_nick), user);
+        }
+        if let Some(modes) = self.user_modes.remove(&self.irc_name(&old_nick)) {
+            self.user_modes.insert(self.irc_name(&new_nick), modes);
+        }
+        if let Some(privileges) = self.active_opers.remove(&self.irc_name(&old_nick)) {
+            self.active_opers
+                .insert(self.irc_name(&new_nick), privileges);
+        }
+        self.nick_times.remove(&self.irc_name(&old_nick));
+        self.nick_times.insert(self.irc_name(&new_nick), nick_time);
+        self.notify_servers(
+            format!(
+                ":{} NICK {} {} {}\n",
+                old_nick,
+                new_nick,
+                hopcount + 1,
+                nick_time
+            ),
+            sender,
+        )
+    }
+
     pub fn join_channel(
         &mut self,
         user_nick: String,
-        user_stream: Option<Arc<Mutex<TcpStream>>>,
+        user_stream: Option<Arc<Mutex<Connection>>>,
         channel_name: String,
         key: Option<String>,
         server_name: Option<String>,
     ) -> ReplyAndError {
         if !self.contains_channel(&channel_name) {
-            self.channels.insert(
+            let channel = Channel::new(
                 channel_name.clone(),
-                Channel::new(channel_name.clone(), key, user_nick.clone(), user_stream),
+                key,
+                user_nick.clone(),
+                user_stream,
+                self.casemapping,
             );
+            self.channels.insert(self.irc_name(&channel_name), channel);
             // return Ok((
             //     app_errors::RPL_TOPIC,
             //     vec![channel_name.clone(), "".to_string()],
             // ));
         } else {
+            // los bans de usuarios de otros servidores los controla su servidor
+            let mask = match server_name {
+                Some(_) => None,
+                None => self.client_mask(&user_nick),
+            };
             let channel = self
                 .channels
-                .get_mut(&channel_name)
+                .get_mut(&self.irc_name(&channel_name))
                 .expect("Error retrieving channel during join channel");
-            channel.add_client(user_nick.clone(), user_stream, key)?;
+            // la clave tambien la controla el servidor del usuario, el JOIN que llega no la incluye
+            let key = match server_name {
+                Some(_) => channel.get_key(),
+                None => key,
+            };
+            channel.add_client(user_nick.clone(), mask, user_stream, key)?;
         }
         let channel = self
             .channels
-            .get_mut(&channel_name)
+            .get_mut(&self.irc_name(&channel_name))
             .expect("Error retrieving channel during join channel");
         //INFORMO A LOS VECINOS
         for (neighbour_name, server) in self.servers.iter() {
-            let ForeignServer(stream, _hopcount, _info, _path) = server;
+            let ForeignServer(stream, _hopcount, _info, _path, _uplink) = server;
             if server_name.is_some()
                 && *neighbour_name
                     == server_name
@@ -498,23 +1134,67 @@ impl ClientsInfo {
                 stream
                     .lock()
                     .expect("Error: stream is none during join channel")
-                    .write_all(format!(":{} JOIN {}\n", user_nick, channel_name).as_bytes())
+                    .write_all(
+                        format!(
+                            ":{} JOIN {} * {}\n",
+                            user_nick,
+                            channel_name,
+                            channel.created()
+                        )
+                        .as_bytes(),
+                    )
                     .expect("Error writing to server");
             }
         }
-        Ok((
-            app_errors::RPL_TOPIC,
-            vec![
-                channel_name,
-                match channel
-                    .get_topic(user_nick)
-                    .expect("Error obtaining channel topic")
-                {
-                    Some(x) => x,
-                    None => "".to_string(),
-                },
-            ],
-        ))
+        match channel
+            .get_topic(user_nick)
+            .expect("Error obtaining channel topic")
+        {
+            Some(topic) => Ok((app_errors::RPL_TOPIC, vec![channel_name, topic])),
+            None => Ok((app_errors::RPL_NOTOPIC, vec![channel_name])),
+        }
+    }
+
+    /// Given a channel of this server and when another server says it was created keeps the older channel.
+    /// If this one is newer its operators and voices are dropped (the local members see them removed) and it
+    /// takes the older time. Returns whether this one is older, so the modes the other server sends for it are ignored.
+    pub fn merge_channel_time(&mut self, channel_name: &str, created: u64) -> bool {
+        let server_name = self.server_name.clone();
+        let channel = match self.channels.get_mut(&self.irc_name(channel_name)) {
+            Some(channel) => channel,
+            None => return false,
+        };
+        if created >= channel.created() {
+            return created > channel.created();
+        }
+        channel.set_created(created);
+        let (operators, voiced) = channel.clear_privileges();
+        let mut modes = Vec::new();
+        for operator in operators {
+            modes.push(format!(
+                ":{} MODE {} -o {}\n",
+                server_name, channel_name, operator
+            ));
+        }
+        for voiced in voiced {
+            modes.push(format!(
+                ":{} MODE {} -v {}\n",
+                server_name, channel_name, voiced
+            ));
+        }
+        for (_member, stream) in channel.local_members() {
+            for mode in modes.iter() {
+                Self::write_message(mode.clone(), stream.clone());
+            }
+        }
+        false
+    }
+
+    /// Given a channel just created by a user of another server and when it was created there sets its time
+    pub fn set_channel_time(&mut self, channel_name: &str, created: u64) {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(channel_name)) {
+            channel.set_created(created);
+        }
     }
 
     pub fn part(
@@ -530,36 +1210,35 @@ impl ClientsInfo {
             nick.ok_or((app_errors::ERR_NEEDMOREPARAMS, vec!["PART".to_string()]))?
         };
         for channel_name in channels.iter() {
-            if !self.channels.contains_key(channel_name) {
+            if !self.channels.contains_key(&self.irc_name(channel_name)) {
                 return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name.clone()]));
             }
         }
         for channel_name in channels {
-            match self.channels.get_mut(&channel_name) {
+            match self.channels.get_mut(&self.irc_name(&channel_name)) {
                 Some(channel) => {
                     channel.remove_if_present(&to_remove);
                     if channel.is_empty() {
-                        self.channels.remove(&channel_name);
+                        self.channels.remove(&self.irc_name(&channel_name));
                     }
-                    if let Err(code) = self.notify_servers(
+                    self.notify_servers(
                         format!(":{} PART {}\n", to_remove, channel_name),
                         sender.clone(),
-                    ) {
-                        return Err(code);
-                    }
+                    )?;
                 }
                 None => {
                     return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]));
                 }
             }
         }
-        Ok((app_errors::RPL_SUCCESS, vec![]))
+        Ok((app_errors::RPL_NONE, vec![]))
     }
 
     pub fn who(&mut self, mut mask: String, from: String) -> Result<(), Box<dyn Error>> {
+        let hidden = self.hidden_from(&from);
         let ForeignClient(stream, _hopcount, _server, _away_msg) = self
             .streams
-            .get_mut(&from)
+            .get_mut(&self.irc_name(&from))
             .expect("Error obtaining streams during who");
         let mut stream = match stream.lock() {
             Ok(stream) => stream,
@@ -573,7 +1252,7 @@ impl ClientsInfo {
             if mask.starts_with('*') {
                 mask.remove(0);
                 for (user, client) in self.users.iter() {
-                    if user.ends_with(mask.as_str()) {
+                    if user.as_str().ends_with(mask.as_str()) && !hidden.contains(user) {
                         stream.write_all(
                             format!(
                                 "{} {}: {};",
@@ -588,7 +1267,7 @@ impl ClientsInfo {
             } else if mask.ends_with('*') {
                 mask.remove(mask.len() - 1);
                 for (user, client) in self.users.iter() {
-                    if user.starts_with(mask.as_str()) {
+                    if user.as_str().starts_with(mask.as_str()) && !hidden.contains(user) {
                         stream.write_all(
                             format!(
                                 "{} {}: {};",
@@ -603,7 +1282,7 @@ impl ClientsInfo {
             }
         }
         for (user, client) in self.users.iter() {
-            if user.as_str() == mask.as_str() {
+            if user.as_str() == mask.as_str() && !hidden.contains(user) {
                 stream.write_all(
                     format!(
                         "{} {}: {};",
@@ -620,91 +1299,30 @@ impl ClientsInfo {
         Ok(())
     }
 
-    pub fn whois(&mut self, mask: String, from: String) -> Result<(), Box<dyn Error>> {
-        let ForeignClient(stream, _hopcount, _server, _away_msg) = self
-            .streams
-            .get_mut(&from)
-            .expect("Error obtaining streams during whois");
-        let mut stream = match stream.lock() {
-            Ok(stream) => stream,
-            Err(_) => {
-                return Err(Box::new(app_errors::ApplicationError(
-                    "locking stream".into(),
-                )))
-            }
-        };
-
-        if self.channels.contains_key(&mask) {
-            stream.write_all(
-                format!(
-                    "{} : {}\n",
-                    mask,
-                    match self
-                        .channels
-                        .get_mut(&mask)
-                        .expect("Error getting channel during whois")
-                        .topic()
-                    {
-                        Some(x) => x,
-                        None => "".to_string(),
-                    }
-                )
-                .as_bytes(),
-            )?;
-            return Ok(());
-        }
-        if self.users.contains_key(&mask) {
-            stream.write_all(
-                format!(
-                    "{} {}: {}\n",
-                    self.users
-                        .get_mut(&mask)
-                        .expect("Error getting user during whois")
-                        .user
-                        .clone()
-                        .expect("Error: user not found during whois"),
-                    mask,
-                    self.users
-                        .get_mut(&mask)
-                        .expect("Error getting user during whois")
-                        .realname
-                        .clone()
-                        .expect("Error: realname not found during whois"),
-                )
-                .as_bytes(),
-            )?;
-            return Ok(());
-        }
-        stream.write_all("431 :No nickname given".as_bytes())?;
-        Ok(())
-    }
-
     // escribe mensajes en el stream (ej. cuando se envia PRIVMSG a otro usuario)
-    fn write_message(msg: String, stream: Arc<Mutex<TcpStream>>) {
-        let mut stream = match stream.lock() {
-            Ok(stream) => stream,
-            Err(_) => panic!("locking stream"), //mejor panic que return silencioso (por ahora)
-        };
-        if let Err(err) = stream.write(msg.as_bytes()) {
-            eprintln!("Server error: {err}");
-            std::process::exit(1);
+    fn write_message(msg: String, stream: Arc<Mutex<Connection>>) {
+        match stream.lock() {
+            Ok(mut stream) => {
+                let _ = stream.write_all(msg.as_bytes());
+            }
+            Err(err) => eprintln!("Server error (locking stream): {err}"),
         }
     }
 
+    /// Given a server of the network, its stream (if it's a neighbour), hopcount and info, the neighbour it's reached
+    /// through and the server it's linked to adds it and tells the other neighbours with ':uplink SERVER'.
+    /// A new neighbour also gets the servers, users and channels of the network.
     pub fn try_add_server(
         &mut self,
         name: String,
-        pass: Option<String>,
-        stream: Option<Arc<Mutex<TcpStream>>>,
+        stream: Option<Arc<Mutex<Connection>>>,
         hopcount: i32,
         info: String,
         server_name: String,
+        uplink: String,
     ) -> ReplyAndError {
-        if self.server_password.is_some() && pass != self.server_password {
-            return Err((app_errors::ERR_PASSWDMISMATCH, vec![]));
-        }
-        if self.servers.contains_key(&name) {
-            return Err((app_errors::ERR_SERVERCOLLISION, vec![name]));
+        if self.is_known_server(&name) {
+            return Err((app_errors::ERR_ALREADYREGISTRED, vec![]));
         }
         // if hopcount == 1 {
         if let Some(stream) = stream.clone() {
@@ -713,18 +1331,32 @@ impl ClientsInfo {
                     .lock()
                     .expect("Error: server lock poisoned during try add server");
                 // envía un mensaje al padre indicando, que se pudo conectar
+                let welcome = Reply::build(app_errors::RPL_WELCOME, vec![name.clone()]);
                 stream
-                    .write_all("200 :Succesfully Connected\n".as_bytes())
-                    .expect("Error writing to server");
-                println!("Registrando nuevo server: {}", name);
-                // broadcast comando SERVER a todos los servidores de la red, acerca del nuevo server
+                    .write_all(
+                        format!(
+                            "{}\n",
+                            welcome.format(self.reply_format, &self.server_name, &name)
+                        )
+                        .as_bytes(),
+                    )
+                    .expect("Error writing to server");
+                println!("Registrando nuevo server: {}", name);
+                // broadcast comando SERVER a todos los servidores de la red, acerca del nuevo server
+                // el prefijo es el servidor al que esta enlazado, para que el nuevo conozca el arbol de la red
                 for (neighbour_name, foreign_server) in self.servers.iter_mut() {
-                    let ForeignServer(neighbour_stream, neighbour_hopcount, neighbour_info, _path) =
-                        foreign_server;
+                    let ForeignServer(
+                        neighbour_stream,
+                        neighbour_hopcount,
+                        neighbour_info,
+                        _path,
+                        neighbour_uplink,
+                    ) = foreign_server;
                     stream
                         .write_all(
                             format!(
-                                "SERVER {} {} {}\n",
+                                ":{} SERVER {} {} {}\n",
+                                neighbour_uplink,
                                 neighbour_name,
                                 (*neighbour_hopcount + 1),
                                 neighbour_info
@@ -737,7 +1369,14 @@ impl ClientsInfo {
                             .lock()
                             .expect("Error: neighbour stream poisoned during try add server")
                             .write_all(
-                                format!("SERVER {} {} {}\n", name, (hopcount + 1), info).as_bytes(),
+                                format!(
+                                    ":{} SERVER {} {} {}\n",
+                                    uplink,
+                                    name,
+                                    (hopcount + 1),
+                                    info
+                                )
+                                .as_bytes(),
                             )
                             .expect("Error writing to server");
                     }
@@ -746,14 +1385,16 @@ impl ClientsInfo {
                 for (nick, ForeignClient(_stream, hopcount, _server, _away_msg)) in
                     self.streams.iter_mut()
                 {
-                    // NICK para indicar que tan lejos esta el usuario de su servidor
+                    // NICK para indicar que tan lejos esta el usuario de su servidor y desde cuando tiene el nick
+                    let nick_time = self.nick_times.get(nick).copied().unwrap_or_else(now);
                     stream
                         .write_all(
                             format!(
-                                ":{} NICK {} {}\n",
+                                ":{} NICK {} {} {}\n",
                                 self.server_name.clone(),
                                 nick,
-                                *hopcount + 1
+                                *hopcount + 1,
+                                nick_time
                             )
                             .as_bytes(),
                         )
@@ -778,18 +1419,34 @@ impl ClientsInfo {
                             .as_bytes(),
                         )
                         .expect("Error writing to server");
+                    // MODE con los modos del usuario (invisible, wallops, operador)
+                    let modes = Self::mode_string(
+                        self.user_modes.get(nick),
+                        self.active_opers.contains_key(nick),
+                        false,
+                    );
+                    if modes.len() > 1 {
+                        stream
+                            .write_all(format!(":{} MODE {} {}\n", nick, nick, modes).as_bytes())
+                            .expect("Error writing to server");
+                    }
                 }
                 // broadcast de los canales, y operadores ?
                 for (channel_name, channel) in self.channels.iter_mut() {
                     let operators = channel.get_operators();
-                    let mut key = String::new();
-                    if let Some(channel_key) = channel.get_key() {
-                        key = channel_key;
-                    }
+                    // ',' si no tiene clave (no puede ser una), asi siempre le sigue cuando se creo el canal
+                    let key = channel.get_key().unwrap_or_else(|| ",".to_string());
                     for nick in channel.get_names() {
                         stream
                             .write_all(
-                                format!(":{} JOIN {} {}\n", nick, channel_name, key).as_bytes(),
+                                format!(
+                                    ":{} JOIN {} {} {}\n",
+                                    nick,
+                                    channel_name,
+                                    key,
+                                    channel.created()
+                                )
+                                .as_bytes(),
                             )
                             .expect("Error writing to server");
                     }
@@ -806,6 +1463,19 @@ impl ClientsInfo {
                             )
                             .expect("Error writing to server");
                     }
+                    for voiced in channel.get_voiced() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +v {}\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                    voiced
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
                     if let Some(limit) = channel.get_limit() {
                         stream
                             .write_all(
@@ -831,6 +1501,42 @@ impl ClientsInfo {
                             )
                             .expect("Error writing to server");
                     }
+                    if channel.is_moderated() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +m\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
+                    if channel.is_topic_locked() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +t\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
+                    if channel.is_no_external() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +n\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
                     if channel.is_invite_only() {
                         stream
                             .write_all(
@@ -843,12 +1549,44 @@ impl ClientsInfo {
                             )
                             .expect("Error writing to server");
                     }
+                    for ban in channel.get_bans() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +b {}\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                    ban
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
+                    for exception in channel.get_exceptions() {
+                        stream
+                            .write_all(
+                                format!(
+                                    ":{} MODE {} +e {}\n",
+                                    self.server_name.clone(),
+                                    channel_name,
+                                    exception
+                                )
+                                .as_bytes(),
+                            )
+                            .expect("Error writing to server");
+                    }
                 }
             }
         } else {
             for (
                 neighbour_name,
-                ForeignServer(neighbour_stream, _neighbour_hopcount, _neighbour_info, _path),
+                ForeignServer(
+                    neighbour_stream,
+                    _neighbour_hopcount,
+                    _neighbour_info,
+                    _path,
+                    _uplink,
+                ),
             ) in self.servers.iter_mut()
             {
                 if let Some(neighbour_stream) = neighbour_stream {
@@ -859,16 +1597,306 @@ impl ClientsInfo {
                         .lock()
                         .expect("Error: neighbour lock poisoned during try add server")
                         .write_all(
-                            format!("SERVER {} {} {}\n", name, (hopcount + 1), info).as_bytes(),
+                            format!(":{} SERVER {} {} {}\n", uplink, name, (hopcount + 1), info)
+                                .as_bytes(),
                         )
                         .expect("Error writing to server");
                 }
             }
         }
         // agrega el nuevo server
-        self.servers
-            .insert(name, ForeignServer(stream, hopcount, info, server_name));
-        Ok((app_errors::RPL_SUCCESS, vec![]))
+        self.servers.insert(
+            name,
+            ForeignServer(stream, hopcount, info, server_name, uplink),
+        );
+        Ok((app_errors::RPL_NONE, vec![]))
+    }
+
+    /// Given the name of a server returns whether it's this one or one of the network.
+    /// A server must never be added twice, a second path to it would make a loop
+    pub fn is_known_server(&self, name: &str) -> bool {
+        name == self.server_name || self.servers.contains_key(name)
+    }
+
+    /// Given the mask of LINKS (every server if None) returns a RPL_LINKS for this server and each server of the network
+    /// whose name matches it, closest first, and the RPL_ENDOFLINKS. Each one shows the server it's linked to
+    pub fn links(&self, mask: Option<String>) -> Vec<((i32, &'static str), Vec<String>)> {
+        let mask = mask.unwrap_or_else(|| "*".to_string());
+        let mut servers: Vec<(&String, &i32, &String, &String)> = self
+            .servers
+            .iter()
+            .map(
+                |(name, ForeignServer(_stream, hopcount, info, _path, uplink))| {
+                    (name, hopcount, info, uplink)
+                },
+            )
+            .collect();
+        servers.sort_by_key(|(name, hopcount, _info, _uplink)| (**hopcount, name.to_string()));
+        let own_info = SERVER_VERSION.to_string();
+        let mut replies = Vec::new();
+        for (name, hopcount, info, uplink) in
+            [(&self.server_name, &0, &own_info, &self.server_name)]
+                .into_iter()
+                .chain(servers)
+        {
+            if !mask::matches(&mask, name, self.casemapping) {
+                continue;
+            }
+            replies.push((
+                app_errors::RPL_LINKS,
+                vec![
+                    name.clone(),
+                    uplink.clone(),
+                    hopcount.to_string(),
+                    info.clone(),
+                ],
+            ));
+        }
+        replies.push((app_errors::RPL_ENDOFLINKS, vec![mask]));
+        replies
+    }
+
+    /// Given the nick of an operator returns the RPL_MAPs that draw the network from this server and the RPL_MAPEND.
+    /// Each server hangs from the one it's linked to, indented one level more than it
+    pub fn map(&self, nick: &str) -> RepliesAndError {
+        if !self.active_opers.contains_key(&self.irc_name(nick)) {
+            return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        let mut replies = vec![(
+            app_errors::RPL_MAP,
+            vec![format!("{} [0] {}", self.server_name, SERVER_VERSION)],
+        )];
+        self.map_branch(&self.server_name, 0, &mut replies);
+        replies.push((app_errors::RPL_MAPEND, vec![]));
+        Ok(replies)
+    }
+
+    /// Given a server and how deep it is in the map adds a RPL_MAP for each server linked to it,
+    /// followed by the servers linked to that one
+    fn map_branch(
+        &self,
+        uplink: &str,
+        depth: usize,
+        replies: &mut Vec<((i32, &'static str), Vec<String>)>,
+    ) {
+        let mut branch: Vec<(&String, &i32, &String)> = self
+            .servers
+            .iter()
+            .filter(
+                |(_, ForeignServer(_stream, _hopcount, _info, _path, server_uplink))| {
+                    server_uplink == uplink
+                },
+            )
+            .map(
+                |(name, ForeignServer(_stream, hopcount, info, _path, _uplink))| {
+                    (name, hopcount, info)
+                },
+            )
+            .collect();
+        branch.sort();
+        for (name, hopcount, info) in branch {
+            replies.push((
+                app_errors::RPL_MAP,
+                vec![format!(
+                    "{}`- {} [{}] {}",
+                    "  ".repeat(depth),
+                    name,
+                    hopcount,
+                    info
+                )],
+            ));
+            self.map_branch(name, depth + 1, replies);
+        }
+    }
+
+    /// Returns the replies to VERSION, commented with the same tokens of RPL_ISUPPORT
+    pub fn version(&self) -> Vec<((i32, &'static str), Vec<String>)> {
+        vec![(
+            app_errors::RPL_VERSION,
+            vec![
+                SERVER_VERSION.to_string(),
+                self.server_name.clone(),
+                self.isupport(),
+            ],
+        )]
+    }
+
+    /// Returns the replies to TIME, with the current date of this server
+    pub fn time(&self) -> Vec<((i32, &'static str), Vec<String>)> {
+        vec![(
+            app_errors::RPL_TIME,
+            vec![self.server_name.clone(), format_time(now())],
+        )]
+    }
+
+    /// Returns the replies to ADMIN, with the administrative info of the config
+    pub fn admin(&self) -> Vec<((i32, &'static str), Vec<String>)> {
+        self.admin_info.replies(&self.server_name)
+    }
+
+    /// Returns the replies to INFO: the version of the server and since when it's running
+    pub fn info(&self) -> Vec<((i32, &'static str), Vec<String>)> {
+        vec![
+            (app_errors::RPL_INFO, vec![SERVER_VERSION.to_string()]),
+            (
+                app_errors::RPL_INFO,
+                vec![format!("Online since {}", format_time(self.created))],
+            ),
+            (app_errors::RPL_ENDOFINFO, vec![]),
+        ]
+    }
+
+    /// Given a nick returns the replies to a WHOIS meant for this server: the user and host of the user,
+    /// the server it's reached through, whether it's an operator and its away message
+    pub fn whois_replies(&self, nick: &str) -> Vec<((i32, &'static str), Vec<String>)> {
+        let end = (app_errors::RPL_ENDOFWHOIS, vec![nick.to_string()]);
+        let (ForeignClient(_stream, _hopcount, server, away_msg), client) = match (
+            self.streams.get(&self.irc_name(nick)),
+            self.users.get(&self.irc_name(nick)),
+        ) {
+            (Some(foreign_client), Some(client)) => (foreign_client, client),
+            _ => return vec![(app_errors::ERR_NOSUCHNICK, vec![nick.to_string()]), end],
+        };
+        // el host es el que figura en la mascara del usuario
+        let mask = self.client_mask(&nick.to_string()).unwrap_or_default();
+        let host = mask.rsplit_once('@').map(|(_, host)| host).unwrap_or("*");
+        let (server, server_info) = match server {
+            Some(server) => match self.servers.get(server) {
+                Some(ForeignServer(_stream, _hopcount, info, _path, _uplink)) => {
+                    (server.clone(), info.clone())
+                }
+                None => (server.clone(), String::new()),
+            },
+            None => (self.server_name.clone(), SERVER_VERSION.to_string()),
+        };
+        let mut replies = vec![
+            (
+                app_errors::RPL_WHOISUSER,
+                vec![
+                    nick.to_string(),
+                    client.user.clone().unwrap_or_default(),
+                    host.to_string(),
+                    client.realname.clone().unwrap_or_default(),
+                ],
+            ),
+            (
+                app_errors::RPL_WHOISSERVER,
+                vec![nick.to_string(), server, server_info],
+            ),
+        ];
+        if self.active_opers.contains_key(&self.irc_name(nick)) {
+            replies.push((app_errors::RPL_WHOISOPERATOR, vec![nick.to_string()]));
+        }
+        if let Some(away_msg) = away_msg {
+            replies.push((
+                app_errors::RPL_AWAY,
+                vec![nick.to_string(), away_msg.clone()],
+            ));
+        }
+        replies.push(end);
+        replies
+    }
+
+    /// Given the name of a server of the network and a line, writes the line to the neighbour
+    /// the server is reached through, so it gets there. Returns ERR_NOSUCHSERVER if it isn't known
+    pub fn route_to_server(&self, server_name: &str, line: String) -> DefaultAndError {
+        let no_such_server = (app_errors::ERR_NOSUCHSERVER, vec![server_name.to_string()]);
+        let path = match self.servers.get(server_name) {
+            Some(ForeignServer(_stream, _hopcount, _info, path, _uplink)) => path,
+            None => return Err(no_such_server),
+        };
+        match self.servers.get(path) {
+            Some(ForeignServer(Some(stream), _hopcount, _info, _path, _uplink)) => {
+                Self::write_message(format!("{}\n", line), stream.clone());
+                Ok(())
+            }
+            _ => Err(no_such_server),
+        }
+    }
+
+    /// Given the nick of a user that asked this server a query and the replies, sends them to the user.
+    /// Users of other servers get them in RFC format, so the servers in the way can relay them
+    pub fn send_replies(&self, nick: &str, replies: Vec<((i32, &'static str), Vec<String>)>) {
+        let ForeignClient(stream, hopcount, _server, _away_msg) =
+            match self.streams.get(&self.irc_name(nick)) {
+                Some(user) => user,
+                None => return,
+            };
+        let format = match hopcount {
+            0 => self.reply_format,
+            _ => ReplyFormat::Rfc1459,
+        };
+        for reply in replies {
+            let line = Reply::from(reply).format(format, &self.server_name, nick);
+            Self::write_message(format!("{}\n", line), stream.clone());
+        }
+    }
+
+    /// Given a numeric reply that another server sent for a user (the neighbour it arrived through, the server,
+    /// the numeric, the nick and the text) relays it towards the user. The users of this server get it in the format
+    /// of the config. A reply for a user reached through the same neighbour is dropped, sending it back would loop
+    pub fn relay_reply(
+        &self,
+        neighbour: &str,
+        origin: &str,
+        numeric: &str,
+        nick: &str,
+        text: &str,
+    ) {
+        let ForeignClient(stream, hopcount, _server, _away_msg) =
+            match self.streams.get(&self.irc_name(nick)) {
+                Some(ForeignClient(_stream, _hopcount, Some(server), _away_msg))
+                    if server == neighbour =>
+                {
+                    return
+                }
+                Some(user) => user,
+                None => return,
+            };
+        let line = match (hopcount, self.reply_format) {
+            (0, ReplyFormat::Legacy) => format!("{} {}\n", numeric, text),
+            _ => format!(":{} {} {} {}\n", origin, numeric, nick, text),
+        };
+        Self::write_message(line, stream.clone());
+    }
+
+    /// Given the name of a neighbour whose link was lost removes it, the servers reached through it
+    /// and their users, so they can link again. The local users sharing a channel with the lost users
+    /// see them QUIT with the netsplit reason ("this_server neighbour"), the channels left empty are removed
+    /// and the remaining neighbours are told with the same QUITs and a SQUIT for each lost server.
+    pub fn netsplit(&mut self, neighbour: &str) {
+        let lost_servers: Vec<String> = self
+            .servers
+            .iter()
+            .filter(
+                |(_, ForeignServer(_stream, _hopcount, _info, path, _uplink))| path == neighbour,
+            )
+            .map(|(name, _)| name.clone())
+            .collect();
+        // los usuarios de otros servers guardan el primer server del camino hacia ellos
+        let lost_users: Vec<String> = self
+            .streams
+            .iter()
+            .filter(|(_, ForeignClient(_stream, _hopcount, server, _))| {
+                server.as_deref() == Some(neighbour)
+            })
+            .map(|(nick, _)| nick.to_string())
+            .collect();
+        for server in lost_servers.iter() {
+            self.servers.remove(server);
+        }
+        let reason = format!("{} {}", self.server_name, neighbour);
+        for nick in lost_users {
+            println!("Netsplit: quitting client {}", nick);
+            self.remove_client(&nick, &reason);
+            let _ = self.notify_servers(format!(":{} QUIT :{}\n", nick, reason), None);
+        }
+        for server in lost_servers {
+            let _ = self.notify_servers(
+                format!(":{} SQUIT {} :{}\n", self.server_name, server, reason),
+                None,
+            );
+        }
     }
 
     pub fn squit(
@@ -877,18 +1905,19 @@ impl ClientsInfo {
         server_name: String,
         comment: Option<String>,
     ) -> ReplyAndError {
-        if !self.active_opers.contains(&issuer) && !self.servers.contains_key(&issuer) {
+        if !self.has_privilege(&issuer, Privilege::Route) && !self.servers.contains_key(&issuer) {
             return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
         }
         if !self.servers.contains_key(&server_name) {
             return Err((app_errors::ERR_NOSUCHSERVER, vec![server_name]));
         }
-        if self.active_opers.contains(&issuer) {
+        if self.active_opers.contains_key(&self.irc_name(&issuer)) {
             if let Some(ForeignServer(
                 neighbour_stream,
                 _neighbour_hopcount,
                 _neighbour_info,
                 _path,
+                _uplink,
             )) = self.servers.clone().get(&server_name)
             {
                 match neighbour_stream {
@@ -921,7 +1950,7 @@ impl ClientsInfo {
                         let mut to_be_notified = Vec::new();
                         let mut joint_servers = Vec::new();
                         let mut disjoint_servers = Vec::new();
-                        for (server, ForeignServer(stream, _hopcount, _info, path)) in
+                        for (server, ForeignServer(stream, _hopcount, _info, path, _uplink)) in
                             self.servers.iter()
                         {
                             if *path == server_name {
@@ -985,9 +2014,11 @@ impl ClientsInfo {
                         for nick in disjoint_clients.iter() {
                             self.users.remove_entry(nick);
                             self.streams.remove_entry(nick);
+                            self.user_modes.remove(nick);
+                            self.active_opers.remove(nick);
                             let mut empty_chans = Vec::new();
                             for (channel_name, channel) in self.channels.iter_mut() {
-                                channel.remove_if_present(nick);
+                                channel.remove_if_present(&nick.to_string());
                                 if channel.is_empty() {
                                     empty_chans.push(channel_name.clone());
                                 }
@@ -1006,7 +2037,13 @@ impl ClientsInfo {
         } else {
             for (
                 neighbour_name,
-                ForeignServer(neighbour_stream, _neighbour_hopcount, _neighbour_info, _path),
+                ForeignServer(
+                    neighbour_stream,
+                    _neighbour_hopcount,
+                    _neighbour_info,
+                    _path,
+                    _uplink,
+                ),
             ) in self.servers.iter_mut()
             {
                 if let Some(neighbour_stream) = neighbour_stream {
@@ -1026,26 +2063,27 @@ impl ClientsInfo {
             }
             self.servers.remove(&server_name);
         }
-        Ok((app_errors::RPL_SUCCESS, vec![]))
+        Ok((app_errors::RPL_NONE, vec![]))
     }
     pub fn away(&mut self, nick: String, msg: Option<String>) -> Result<(), Box<dyn Error>> {
-        let ForeignClient(stream, hopcount, server, _away_msg) = match self.streams.get(&nick) {
-            Some(client) => client,
-            None => {
-                return Err(Box::new(app_errors::ApplicationError(
-                    "nick not found".to_string(),
-                )))
-            }
-        };
+        let ForeignClient(stream, hopcount, server, _away_msg) =
+            match self.streams.get(&self.irc_name(&nick)) {
+                Some(client) => client,
+                None => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "nick not found".to_string(),
+                    )))
+                }
+            };
         self.streams.insert(
-            nick,
+            self.irc_name(&nick),
             ForeignClient(stream.clone(), *hopcount, server.clone(), msg),
         );
         Ok(())
     }
 
     fn notify_servers(&mut self, msg: String, sender: Option<String>) -> DefaultAndError {
-        for (neighbour_name, ForeignServer(stream, _hopcount, _info, _path)) in
+        for (neighbour_name, ForeignServer(stream, _hopcount, _info, _path, _uplink)) in
             self.servers.iter_mut()
         {
             if let Some(stream) = stream {
@@ -1077,7 +2115,7 @@ impl ClientsInfo {
         }
         match self
             .channels
-            .get_mut(&channel_name)
+            .get_mut(&self.irc_name(&channel_name))
             .unwrap()
             .add_oper(new_oper.clone())
         {
@@ -1089,7 +2127,7 @@ impl ClientsInfo {
                     ),
                     sender,
                 ) {
-                    Ok(_) => Ok((app_errors::RPL_SUCCESS, vec![])),
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
                     Err(code) => Err(code),
                 }
             }
@@ -1114,7 +2152,7 @@ impl ClientsInfo {
         }
         match self
             .channels
-            .get_mut(&channel_name)
+            .get_mut(&self.irc_name(&channel_name))
             .unwrap()
             .set_limit(mode.clone(), new_limit)
         {
@@ -1129,7 +2167,7 @@ impl ClientsInfo {
                     ),
                     sender,
                 ) {
-                    Ok(_) => Ok((app_errors::RPL_SUCCESS, vec![])),
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
                     Err(code) => Err(code),
                 }
             }
@@ -1149,7 +2187,7 @@ impl ClientsInfo {
         }
         match self
             .channels
-            .get_mut(&channel_name)
+            .get_mut(&self.irc_name(&channel_name))
             .unwrap()
             .mode_secret(mode.clone())
         {
@@ -1163,7 +2201,7 @@ impl ClientsInfo {
                     ),
                     sender,
                 ) {
-                    Ok(_) => Ok((app_errors::RPL_SUCCESS, vec![])),
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
                     Err(code) => Err(code),
                 }
             }
@@ -1179,7 +2217,7 @@ impl ClientsInfo {
         prefix: Option<String>,
         sender: Option<String>,
     ) -> ReplyAndError {
-        if let Some(channel) = self.channels.clone().get_mut(&channel_name) {
+        if let Some(channel) = self.channels.clone().get_mut(&self.irc_name(&channel_name)) {
             if sender.is_some() {
                 return self.mode_oper_add(channel_name, new_oper, prefix, sender);
             }
@@ -1187,7 +2225,7 @@ impl ClientsInfo {
                 if channel.is_oper(&nick) {
                     return self.mode_oper_add(channel_name, new_oper, prefix, sender);
                 }
-                return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
             }
         }
         Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
@@ -1201,7 +2239,7 @@ impl ClientsInfo {
         prefix: Option<String>,
         sender: Option<String>,
     ) -> ReplyAndError {
-        if let Some(channel) = self.channels.clone().get_mut(&channel_name) {
+        if let Some(channel) = self.channels.clone().get_mut(&self.irc_name(&channel_name)) {
             if sender.is_some() {
                 return self.set_limit(channel_name, mode, new_limit, prefix, sender);
             }
@@ -1209,7 +2247,7 @@ impl ClientsInfo {
                 if channel.is_oper(&nick) {
                     return self.set_limit(channel_name, mode, new_limit, prefix, sender);
                 }
-                return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
             }
         }
         Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
@@ -1222,7 +2260,7 @@ impl ClientsInfo {
         prefix: Option<String>,
         sender: Option<String>,
     ) -> ReplyAndError {
-        if let Some(channel) = self.channels.clone().get_mut(&channel_name) {
+        if let Some(channel) = self.channels.clone().get_mut(&self.irc_name(&channel_name)) {
             if sender.is_some() {
                 return self.set_mode_secret(channel_name, mode, prefix, sender);
             }
@@ -1230,7 +2268,7 @@ impl ClientsInfo {
                 if channel.is_oper(&nick) {
                     return self.set_mode_secret(channel_name, mode, prefix, sender);
                 }
-                return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
             }
         }
         Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
@@ -1248,7 +2286,7 @@ impl ClientsInfo {
         }
         match self
             .channels
-            .get_mut(&channel_name)
+            .get_mut(&self.irc_name(&channel_name))
             .unwrap()
             .mode_invite(mode.clone())
         {
@@ -1262,7 +2300,7 @@ impl ClientsInfo {
                     ),
                     sender,
                 ) {
-                    Ok(_) => Ok((app_errors::RPL_SUCCESS, vec![])),
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
                     Err(code) => Err(code),
                 }
             }
@@ -1278,7 +2316,7 @@ impl ClientsInfo {
         prefix: Option<String>,
         sender: Option<String>,
     ) -> ReplyAndError {
-        if let Some(channel) = self.channels.clone().get_mut(&channel_name) {
+        if let Some(channel) = self.channels.clone().get_mut(&self.irc_name(&channel_name)) {
             if sender.is_some() {
                 return self.set_mode_invite(channel_name, mode, prefix, sender);
             }
@@ -1286,9 +2324,544 @@ impl ClientsInfo {
                 if channel.is_oper(&nick) {
                     return self.set_mode_invite(channel_name, mode, prefix, sender);
                 }
-                return Err((app_errors::ERR_NOCHANPRIVILEGES, vec![]));
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    fn set_mode_moderated(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        match self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap()
+            .mode_moderated(mode.clone())
+        {
+            Ok(()) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel and the mode (+m or -m) changes whether
+    /// only operators and voiced users can send to the channel.
+    pub fn mode_moderated(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_moderated(channel_name, mode, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if channel.is_oper(&nick) {
+                    return self.set_mode_moderated(channel_name, mode, prefix, sender);
+                }
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
             }
         }
         Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
     }
+
+    fn set_mode_topic_lock(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        match self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap()
+            .mode_topic_lock(mode.clone())
+        {
+            Ok(()) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel and the mode (+t or -t) changes whether
+    /// only operators can change the topic of the channel.
+    pub fn mode_topic_lock(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_topic_lock(channel_name, mode, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if channel.is_oper(&nick) {
+                    return self.set_mode_topic_lock(channel_name, mode, prefix, sender);
+                }
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    fn set_mode_no_external(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        match self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap()
+            .mode_no_external(mode.clone())
+        {
+            Ok(()) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel and the mode (+n or -n) changes whether
+    /// users that are not members can send to the channel.
+    pub fn mode_no_external(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_no_external(channel_name, mode, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if channel.is_oper(&nick) {
+                    return self.set_mode_no_external(channel_name, mode, prefix, sender);
+                }
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    fn set_mode_voice(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        voiced: String,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        match self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap()
+            .mode_voice(mode.clone(), voiced.clone())
+        {
+            Ok(()) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                        voiced
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel, the mode (+v or -v) and a member of the channel
+    /// gives or takes voice to the member.
+    pub fn mode_voice(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        voiced: String,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_voice(channel_name, mode, voiced, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if channel.is_oper(&nick) {
+                    return self.set_mode_voice(channel_name, mode, voiced, prefix, sender);
+                }
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    /// Given the channel and the nick of who asks returns the RPL_CHANNELMODEIS reply with its current modes.
+    /// The key is only shown to the members of the channel.
+    pub fn channel_modes(&mut self, channel_name: String, from: &str) -> ReplyAndError {
+        let channel = match self.channels.get_mut(&self.irc_name(&channel_name)) {
+            Some(channel) => channel,
+            None => return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name])),
+        };
+        let show_key = channel.contains_client(from);
+        Ok((
+            app_errors::RPL_CHANNELMODEIS,
+            vec![channel_name, channel.get_mode_string(show_key)],
+        ))
+    }
+
+    fn set_mode_key(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        key: Option<String>,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        let mut unwrapped_key = "*".to_string();
+        if let Some(key) = key.clone() {
+            unwrapped_key = key;
+        }
+        match self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap()
+            .mode_key(mode.clone(), key)
+        {
+            Ok(()) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                        unwrapped_key
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel, the mode (+k or -k) and the key sets or removes
+    /// the channel key. A key that is already set must be removed before setting a new one,
+    /// unless the change comes from another server.
+    pub fn mode_key(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        key: Option<String>,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_key(channel_name, mode, key, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if !channel.is_oper(&nick) {
+                    return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+                }
+                if let (Mode::Activate(_), Some(_)) = (&mode, channel.get_key()) {
+                    return Err((app_errors::ERR_KEYSET, vec![channel_name]));
+                }
+                return self.set_mode_key(channel_name, mode, key, prefix, sender);
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    /// Given the channel and the mode char ('b' or 'e') returns the replies listing its bans
+    /// or ban exceptions, followed by the end of list reply.
+    pub fn mask_list(&mut self, channel_name: String, list: char) -> RepliesAndError {
+        let channel = match self.channels.get_mut(&self.irc_name(&channel_name)) {
+            Some(channel) => channel,
+            None => return Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name])),
+        };
+        let (masks, item, end) = match list {
+            'e' => (
+                channel.get_exceptions(),
+                app_errors::RPL_EXCEPTLIST,
+                app_errors::RPL_ENDOFEXCEPTLIST,
+            ),
+            _ => (
+                channel.get_bans(),
+                app_errors::RPL_BANLIST,
+                app_errors::RPL_ENDOFBANLIST,
+            ),
+        };
+        let mut replies: Vec<((i32, &'static str), Vec<String>)> = masks
+            .into_iter()
+            .map(|mask| (item, vec![channel_name.clone(), mask]))
+            .collect();
+        replies.push((end, vec![channel_name]));
+        Ok(replies)
+    }
+
+    /// Auxiliary function that adds or removes a ban (mode 'b') or ban exception (mode 'e')
+    /// and informs the neighbour servers
+    fn set_mode_mask(
+        &mut self,
+        channel_name: String,
+        mode: Mode,
+        mask: String,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        let mut unwrapped_prefix = self.server_name.clone();
+        if let Some(prefix) = prefix {
+            unwrapped_prefix = prefix;
+        }
+        let channel = self
+            .channels
+            .get_mut(&self.irc_name(&channel_name))
+            .unwrap();
+        let result = match mode {
+            Mode::Activate('e') | Mode::Deactivate('e') => {
+                channel.mode_exception(mode.clone(), mask)
+            }
+            _ => channel.mode_ban(mode.clone(), mask),
+        };
+        match result {
+            Ok(mask) => {
+                match self.notify_servers(
+                    format!(
+                        ":{} MODE {} {} {}\n",
+                        unwrapped_prefix,
+                        channel_name,
+                        mode.to_mode_string(),
+                        mask
+                    ),
+                    sender,
+                ) {
+                    Ok(_) => Ok((app_errors::RPL_NONE, vec![])),
+                    Err(code) => Err(code),
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Given the nick of the issuer, the channel, the mode (+b, -b, +e or -e) and the mask
+    /// updates the bans or ban exceptions of the channel. Only channel operators can do it,
+    /// unless the change comes from another server.
+    pub fn mode_mask(
+        &mut self,
+        nick: Option<String>,
+        channel_name: String,
+        mode: Mode,
+        mask: String,
+        prefix: Option<String>,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if let Some(channel) = self.channels.get_mut(&self.irc_name(&channel_name)) {
+            if sender.is_some() {
+                return self.set_mode_mask(channel_name, mode, mask, prefix, sender);
+            }
+            if let Some(nick) = nick {
+                if channel.is_oper(&nick) {
+                    return self.set_mode_mask(channel_name, mode, mask, prefix, sender);
+                }
+                return Err((app_errors::ERR_CHANOPRIVSNEEDED, vec![channel_name]));
+            }
+        }
+        Err((app_errors::ERR_NOSUCHCHANNEL, vec![channel_name]))
+    }
+
+    /// Auxiliary function that given the 'i' and 'w' modes of a user and whether they are
+    /// an operator and away returns their modes as a mode string (e.g. "+iow")
+    fn mode_string(modes: Option<&HashSet<char>>, oper: bool, away: bool) -> String {
+        let mut mode_string = "+".to_string();
+        let has = |mode: char| modes.is_some_and(|modes| modes.contains(&mode));
+        for (mode, active) in [('a', away), ('i', has('i')), ('o', oper), ('w', has('w'))] {
+            if active {
+                mode_string.push(mode);
+            }
+        }
+        mode_string
+    }
+
+    /// Given a nick returns the RPL_UMODEIS reply with the current modes of the user
+    pub fn user_modes(&mut self, nick: &str) -> ReplyAndError {
+        let away = match self.streams.get(&self.irc_name(nick)) {
+            Some(ForeignClient(_stream, _hopcount, _server, away_msg)) => away_msg.is_some(),
+            None => return Err((app_errors::ERR_NOSUCHNICK, vec![nick.to_string()])),
+        };
+        let modes = Self::mode_string(
+            self.user_modes.get(&self.irc_name(nick)),
+            self.active_opers.contains_key(&self.irc_name(nick)),
+            away,
+        );
+        Ok((app_errors::RPL_UMODEIS, vec![modes]))
+    }
+
+    /// Given the nick of who asks returns the invisible users (+i) they can't see,
+    /// that is the ones that don't share any channel with them
+    fn hidden_from(&mut self, from: &str) -> HashSet<IrcName> {
+        let from_name = self.irc_name(from);
+        let mut hidden = HashSet::new();
+        for (nick, modes) in self.user_modes.iter() {
+            if !modes.contains(&'i') || *nick == from_name {
+                continue;
+            }
+            let shares_channel = self.channels.values_mut().any(|channel| {
+                channel.contains_client(nick.as_str()) && channel.contains_client(from)
+            });
+            if !shares_channel {
+                hidden.insert(nick.clone());
+            }
+        }
+        hidden
+    }
+
+    /// Given the issuer, the user whose modes are changed, the mode and the server it comes from (if any)
+    /// changes the user mode and informs the neighbour servers.
+    /// Users can only change their own modes, +o is only given by OPER (or by another server)
+    /// and +a is only set by AWAY.
+    pub fn mode_user(
+        &mut self,
+        issuer: String,
+        nick: String,
+        mode: Mode,
+        sender: Option<String>,
+    ) -> ReplyAndError {
+        if !self.streams.contains_key(&self.irc_name(&nick)) {
+            return Err((app_errors::ERR_NOSUCHNICK, vec![nick]));
+        }
+        if sender.is_none() && !self.is_same_name(&issuer, &nick) {
+            return Err((app_errors::ERR_USERSDONTMATCH, vec![]));
+        }
+        let changed = match mode {
+            Mode::Activate(flag @ ('i' | 'w')) => self
+                .user_modes
+                .entry(self.irc_name(&nick))
+                .or_default()
+                .insert(flag),
+            Mode::Deactivate(flag @ ('i' | 'w')) => self
+                .user_modes
+                .get_mut(&self.irc_name(&nick))
+                .is_some_and(|modes| modes.remove(&flag)),
+            Mode::Activate('o') if sender.is_some() => self
+                .active_opers
+                .insert(self.irc_name(&nick), HashSet::new())
+                .is_none(),
+            Mode::Deactivate('o') => self.active_opers.remove(&self.irc_name(&nick)).is_some(),
+            Mode::Activate('o') | Mode::Activate('a') | Mode::Deactivate('a') => false,
+            Mode::Activate(_) | Mode::Deactivate(_) => {
+                return Err((app_errors::ERR_UMODEUNKNOWNFLAG, vec![]))
+            }
+        };
+        if changed {
+            self.notify_servers(
+                format!(":{} MODE {} {}\n", nick, nick, mode.to_mode_string()),
+                sender,
+            )?;
+        }
+        Ok((app_errors::RPL_NONE, vec![]))
+    }
+
+    /// Given the operator (or server) that sends it and the text sends a WALLOPS message
+    /// to every local user with the +w mode and relays it to the neighbour servers.
+    pub fn wallops(
+        &mut self,
+        from: String,
+        text: String,
+        sender: Option<String>,
+    ) -> DefaultAndError {
+        if sender.is_none() && !self.active_opers.contains_key(&self.irc_name(&from)) {
+            return Err((app_errors::ERR_NOPRIVILEGES, vec![]));
+        }
+        let message = format!(":{} WALLOPS :{}\n", from, text);
+        for (nick, modes) in self.user_modes.iter() {
+            if !modes.contains(&'w') {
+                continue;
+            }
+            if let Some(ForeignClient(stream, 0, _server, _away_msg)) = self.streams.get(nick) {
+                Self::write_message(message.clone(), stream.clone());
+            }
+        }
+        self.notify_servers(message, sender)
+    }
 }
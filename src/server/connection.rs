@@ -0,0 +1,207 @@
+use super::listener::Listen;
+use super::tls::Stream;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// bytes that may be waiting to be sent before the connection is dropped, used if the config doesn't say otherwise
+pub const DEFAULT_SENDQ_SIZE: usize = 512 * 1024;
+
+/// What the writer thread of a connection has to do next
+enum Outgoing {
+    Data(Vec<u8>),
+    Close,
+}
+
+/// Outgoing side of a connection to the server.
+/// Writes are queued (without blocking) and sent by a writer thread, so a slow or stuck
+/// receiver never stalls the thread that writes to it (usually holding the ClientsInfo lock).
+/// If the queue grows over the sendq size or the socket fails the connection is closed,
+/// the thread reading from it will notice and remove the client.
+pub struct Connection {
+    stream: Stream,
+    queue: Sender<Outgoing>,
+    queued: Arc<AtomicUsize>, //bytes waiting in the queue
+    sendq_size: usize,
+    close_reason: Arc<Mutex<Option<String>>>,
+    listen: Option<Listen>, //listen block it was accepted from, None if the server opened it
+}
+
+impl Connection {
+    /// Given the stream (a plain text socket or TLS) creates the connection and starts its writer thread
+    pub fn new(stream: impl Into<Stream>) -> io::Result<Connection> {
+        let stream = stream.into();
+        let (queue, receiver) = mpsc::channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let close_reason = Arc::new(Mutex::new(None));
+
+        let mut writer = stream.try_clone()?;
+        let writer_queued = queued.clone();
+        let writer_reason = close_reason.clone();
+        thread::spawn(move || {
+            // termina cuando se cierra la conexion o cuando se dropea el Connection
+            while let Ok(outgoing) = receiver.recv() {
+                match outgoing {
+                    Outgoing::Data(data) => {
+                        writer_queued.fetch_sub(data.len(), Ordering::SeqCst);
+                        if writer.write_all(&data).is_err() {
+                            Self::close(&writer, &writer_reason, "Write error");
+                            return;
+                        }
+                    }
+                    Outgoing::Close => {
+                        let _ = writer.shutdown();
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Connection {
+            stream,
+            queue,
+            queued,
+            sendq_size: DEFAULT_SENDQ_SIZE,
+            close_reason,
+            listen: None,
+        })
+    }
+
+    /// Given the size read from the config sets how many bytes may be waiting to be sent
+    pub fn set_sendq_size(&mut self, sendq_size: usize) {
+        self.sendq_size = sendq_size;
+    }
+
+    /// Given the listen block the connection was accepted from tags it
+    pub fn set_listen(&mut self, listen: Listen) {
+        self.listen = Some(listen);
+    }
+
+    /// Returns the listen block the connection was accepted from, if it wasn't opened by the server
+    pub fn listen(&self) -> Option<&Listen> {
+        self.listen.as_ref()
+    }
+
+    /// Returns the reason the connection was closed by the server, if it was
+    pub fn close_reason(&self) -> Option<String> {
+        self.close_reason.lock().ok()?.clone()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Returns a new handle to the socket, used for reading from it
+    pub fn try_clone_stream(&self) -> io::Result<Stream> {
+        self.stream.try_clone()
+    }
+
+    /// Closes the connection once everything already written has been sent
+    pub fn shutdown(&self) {
+        if self.queue.send(Outgoing::Close).is_err() {
+            if let Ok(mut stream) = self.stream.try_clone() {
+                let _ = stream.shutdown();
+            }
+        }
+    }
+
+    /// Closes the socket right away saving the reason (only the first one is kept)
+    fn close(stream: &Stream, close_reason: &Mutex<Option<String>>, reason: &str) {
+        if let Ok(mut close_reason) = close_reason.lock() {
+            if close_reason.is_none() {
+                *close_reason = Some(reason.to_string());
+            }
+        }
+        let _ = stream.abort();
+    }
+}
+
+impl Write for Connection {
+    /// Queues the data to be sent, it never blocks nor fails.
+    /// If the connection is closed the data is discarded.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.close_reason().is_some() {
+            return Ok(buf.len());
+        }
+        if self.queued.load(Ordering::SeqCst) + buf.len() > self.sendq_size {
+            Self::close(&self.stream, &self.close_reason, "SendQ exceeded");
+            return Ok(buf.len());
+        }
+        self.queued.fetch_add(buf.len(), Ordering::SeqCst);
+        if self.queue.send(Outgoing::Data(buf.to_vec())).is_err() {
+            // el writer ya termino, la conexion esta cerrada
+            self.queued.fetch_sub(buf.len(), Ordering::SeqCst);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod connection_test {
+    use super::Connection;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn written_lines_arrive_in_order() {
+        let listener = TcpListener::bind("localhost:8111").expect("");
+        let stream = TcpStream::connect("localhost:8111").expect("");
+        let receiver = listener.incoming().next().expect("").expect("");
+
+        let mut connection = Connection::new(stream).expect("");
+        connection.write_all(b"PING :uno\n").expect("");
+        connection.write_all(b"PING :dos\n").expect("");
+
+        let mut reader = BufReader::new(receiver);
+        let mut line = String::new();
+        reader.read_line(&mut line).expect("");
+        assert_eq!(line, "PING :uno\n");
+        line.clear();
+        reader.read_line(&mut line).expect("");
+        assert_eq!(line, "PING :dos\n");
+    }
+
+    #[test]
+    fn receiver_that_doesnt_read_is_dropped_when_sendq_is_exceeded() {
+        let listener = TcpListener::bind("localhost:8112").expect("");
+        let stream = TcpStream::connect("localhost:8112").expect("");
+        let _receiver = listener.incoming().next().expect("").expect("");
+
+        let mut connection = Connection::new(stream).expect("");
+        connection.set_sendq_size(1024);
+        // nadie lee del otro lado, la escritura nunca se bloquea
+        let line = [b'a'; 1000];
+        for _ in 0..100_000 {
+            connection.write_all(&line).expect("");
+            if connection.close_reason().is_some() {
+                break;
+            }
+        }
+        assert_eq!(
+            connection.close_reason(),
+            Some("SendQ exceeded".to_string())
+        );
+    }
+
+    #[test]
+    fn shutdown_sends_pending_data_before_closing() {
+        let listener = TcpListener::bind("localhost:8113").expect("");
+        let stream = TcpStream::connect("localhost:8113").expect("");
+        let mut receiver = listener.incoming().next().expect("").expect("");
+
+        let mut connection = Connection::new(stream).expect("");
+        connection.write_all(b"ERROR :Closing Link\n").expect("");
+        connection.shutdown();
+
+        let mut received = String::new();
+        receiver.read_to_string(&mut received).expect("");
+        assert_eq!(received, "ERROR :Closing Link\n");
+    }
+}
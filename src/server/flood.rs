@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+// valores por defecto de las claves flood_burst, flood_rate y flood_excess
+pub const DEFAULT_FLOOD_BURST: u32 = 10;
+pub const DEFAULT_FLOOD_RATE: f64 = 1.0;
+pub const DEFAULT_FLOOD_EXCESS: u32 = 20;
+
+/// How many messages a user may send: `burst` in a row, then `rate` per second.
+/// After `excess` messages in a row that had to be delayed the user is dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloodLimits {
+    pub burst: u32,
+    pub rate: f64,
+    pub excess: u32,
+}
+
+impl Default for FloodLimits {
+    fn default() -> Self {
+        FloodLimits {
+            burst: DEFAULT_FLOOD_BURST,
+            rate: DEFAULT_FLOOD_RATE,
+            excess: DEFAULT_FLOOD_EXCESS,
+        }
+    }
+}
+
+impl FloodLimits {
+    /// Returns whether the limits can be used by a bucket: the burst must be greater than zero
+    /// and the rate a finite number greater than zero (i.e. not NaN nor inf)
+    pub fn is_valid(&self) -> bool {
+        self.burst > 0 && self.rate.is_finite() && self.rate > 0.0
+    }
+}
+
+/// What must be done with a message read from the connection
+#[derive(Debug, PartialEq)]
+pub enum Flood {
+    /// it can be executed right away
+    Allowed,
+    /// it must wait that long before being executed
+    Throttled(Duration),
+    /// the connection sent too many messages and must be dropped ("Excess Flood")
+    Excess,
+}
+
+/// Token bucket of a connection. Each message takes a token, the bucket holds up to
+/// `burst` tokens and refills at `rate` tokens per second.
+#[derive(Clone)]
+pub struct FloodControl {
+    limits: FloodLimits,
+    tokens: f64,
+    last_refill: Instant,
+    throttled: u32, //messages in a row that had to wait for a token
+}
+
+impl FloodControl {
+    /// Given the limits and the current time creates a full bucket
+    pub fn new(limits: FloodLimits, now: Instant) -> FloodControl {
+        FloodControl {
+            limits,
+            tokens: limits.burst as f64,
+            last_refill: now,
+            throttled: 0,
+        }
+    }
+
+    /// Given the time a message is read takes a token for it and returns whether it can be executed,
+    /// must wait for the bucket to refill or the connection is flooding the server
+    pub fn check(&mut self, now: Instant) -> Flood {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limits.rate).min(self.limits.burst as f64);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.throttled = 0;
+            return Flood::Allowed;
+        }
+        self.throttled += 1;
+        if self.throttled > self.limits.excess {
+            return Flood::Excess;
+        }
+        // el token se toma ahora, asi el siguiente mensaje espera al que le sigue
+        let wait = (1.0 - self.tokens) / self.limits.rate;
+        self.tokens -= 1.0;
+        Flood::Throttled(Duration::from_secs_f64(wait))
+    }
+}
+
+#[cfg(test)]
+mod flood_test {
+    use super::*;
+
+    fn limits(burst: u32, rate: f64, excess: u32) -> FloodLimits {
+        FloodLimits {
+            burst,
+            rate,
+            excess,
+        }
+    }
+
+    #[test]
+    fn limits_need_a_burst_and_a_finite_positive_rate() {
+        assert!(FloodLimits::default().is_valid());
+        assert!(!limits(0, 1.0, 5).is_valid());
+        assert!(!limits(5, 0.0, 5).is_valid());
+        assert!(!limits(5, f64::NAN, 5).is_valid());
+        assert!(!limits(5, f64::INFINITY, 5).is_valid());
+    }
+
+    #[test]
+    fn burst_is_allowed_right_away() {
+        let now = Instant::now();
+        let mut flood = FloodControl::new(limits(5, 1.0, 10), now);
+        for _ in 0..5 {
+            assert_eq!(flood.check(now), Flood::Allowed);
+        }
+        assert_eq!(flood.check(now), Flood::Throttled(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn messages_over_the_burst_wait_for_the_refill() {
+        let now = Instant::now();
+        let mut flood = FloodControl::new(limits(2, 2.0, 10), now);
+        flood.check(now);
+        flood.check(now);
+        assert_eq!(
+            flood.check(now),
+            Flood::Throttled(Duration::from_millis(500))
+        );
+        assert_eq!(flood.check(now), Flood::Throttled(Duration::from_secs(1)));
+        // cuando llegan al ritmo permitido no esperan
+        let later = now + Duration::from_secs(5);
+        assert_eq!(flood.check(later), Flood::Allowed);
+    }
+
+    #[test]
+    fn sustained_flood_is_an_excess() {
+        let now = Instant::now();
+        let mut flood = FloodControl::new(limits(3, 1.0, 4), now);
+        for _ in 0..3 {
+            assert_eq!(flood.check(now), Flood::Allowed);
+        }
+        for _ in 0..4 {
+            assert!(matches!(flood.check(now), Flood::Throttled(_)));
+        }
+        assert_eq!(flood.check(now), Flood::Excess);
+    }
+
+    #[test]
+    fn messages_at_the_allowed_rate_are_never_throttled() {
+        let mut now = Instant::now();
+        let mut flood = FloodControl::new(limits(3, 1.0, 4), now);
+        for _ in 0..3 {
+            assert_eq!(flood.check(now), Flood::Allowed);
+        }
+        for _ in 0..20 {
+            now += Duration::from_secs(1);
+            assert_eq!(flood.check(now), Flood::Allowed);
+        }
+    }
+}
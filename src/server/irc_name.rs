@@ -0,0 +1,130 @@
+use crate::app_errors;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Rule used to decide when two nicks or channel names are the same.
+/// Rfc1459 also treats '{}|^' as the lowercase of '[]\~' (RFC 1459 section 2.2),
+/// Ascii only folds the letters A to Z.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CaseMapping {
+    #[default]
+    Rfc1459,
+    Ascii,
+}
+
+impl CaseMapping {
+    /// Given the value of the `casemapping` config key returns the corresponding case mapping
+    pub fn build(value: &str) -> Result<CaseMapping, Box<dyn Error>> {
+        match value.trim() {
+            "rfc1459" => Ok(CaseMapping::Rfc1459),
+            "ascii" => Ok(CaseMapping::Ascii),
+            other => Err(Box::new(app_errors::ApplicationError(format!(
+                "Unknown casemapping: {other}"
+            )))),
+        }
+    }
+
+    /// Given self returns the name advertised in the CASEMAPPING token of RPL_ISUPPORT
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaseMapping::Rfc1459 => "rfc1459",
+            CaseMapping::Ascii => "ascii",
+        }
+    }
+
+    /// Given self and a name returns its lowercase form
+    pub fn to_lower(self, name: &str) -> String {
+        name.chars()
+            .map(|c| match (self, c) {
+                (CaseMapping::Rfc1459, '[') => '{',
+                (CaseMapping::Rfc1459, ']') => '}',
+                (CaseMapping::Rfc1459, '\\') => '|',
+                (CaseMapping::Rfc1459, '~') => '^',
+                _ => c.to_ascii_lowercase(),
+            })
+            .collect()
+    }
+}
+
+/// A nick or channel name. Two names are equal (and hash the same) if they are
+/// equal after applying the case mapping, but the name is shown as it was given.
+#[derive(Clone, Debug)]
+pub struct IrcName {
+    name: String,
+    key: String,
+}
+
+impl IrcName {
+    pub fn new(name: &str, casemapping: CaseMapping) -> IrcName {
+        IrcName {
+            name: name.to_string(),
+            key: casemapping.to_lower(name),
+        }
+    }
+
+    /// Given self returns the name as it was given
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+}
+
+impl PartialEq for IrcName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for IrcName {}
+
+impl Hash for IrcName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl fmt::Display for IrcName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod irc_name_test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn names_that_only_differ_in_case_are_equal() {
+        let rust = IrcName::new("#Rust", CaseMapping::Rfc1459);
+        assert_eq!(rust, IrcName::new("#rust", CaseMapping::Rfc1459));
+        assert_eq!(rust.to_string(), "#Rust");
+    }
+
+    #[test]
+    fn rfc1459_casemapping_folds_brackets() {
+        let nick = IrcName::new("nico[]\\~", CaseMapping::Rfc1459);
+        assert_eq!(nick, IrcName::new("NICO{}|^", CaseMapping::Rfc1459));
+        let nick = IrcName::new("nico[]", CaseMapping::Ascii);
+        assert_ne!(nick, IrcName::new("NICO{}", CaseMapping::Ascii));
+        assert_eq!(nick, IrcName::new("NICO[]", CaseMapping::Ascii));
+    }
+
+    #[test]
+    fn map_keyed_by_name_finds_any_case() {
+        let mut map = HashMap::new();
+        map.insert(IrcName::new("Pepe", CaseMapping::Rfc1459), 1);
+        assert_eq!(
+            map.get(&IrcName::new("pEPE", CaseMapping::Rfc1459)),
+            Some(&1)
+        );
+        let (name, _) = map.iter().next().expect("");
+        assert_eq!(name.as_str(), "Pepe");
+    }
+
+    #[test]
+    fn unknown_casemapping_fails() {
+        assert!(CaseMapping::build("strict-rfc1459").is_err());
+        assert_eq!(CaseMapping::build("ascii").expect(""), CaseMapping::Ascii);
+    }
+}
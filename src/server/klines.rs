@@ -0,0 +1,250 @@
+use crate::app_errors;
+use crate::server::irc_name::CaseMapping;
+use crate::server::mask;
+use crate::server::time::now;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+
+/// A ban of every connection whose 'user@host' matches the mask.
+/// Without expiry it's permanent, otherwise it's lifted at that second since the unix epoch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kline {
+    pub mask: String,
+    pub reason: String,
+    pub expiry: Option<u64>,
+}
+
+/// List of the K-lines of the server, kept in sync with the csv file they were loaded from
+#[derive(Default)]
+pub struct Klines {
+    path: Option<String>,
+    entries: Vec<Kline>,
+}
+
+impl Kline {
+    /// Given self returns whether it was already lifted
+    fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expiry, Some(expiry) if expiry <= now)
+    }
+}
+
+impl Klines {
+    /// Given a path to a csv file with the format "user@host,reason,expiry" returns its K-lines.
+    /// The expiry is in seconds since the unix epoch, empty for the permanent ones.
+    /// If the file doesn't exist yet the list starts empty and it's created with the first K-line,
+    /// any other error reading it is returned (so the K-lines it has aren't overwritten later).
+    pub fn load(path: String) -> Result<Klines, Box<dyn Error>> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let mut entries = vec![];
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            // el motivo puede tener comas, la mascara y la expiracion no
+            let (mask, rest) = line.trim().split_once(',').unwrap_or_default();
+            let (reason, expiry) = match rest.rsplit_once(',') {
+                Some(split) if !mask.is_empty() => split,
+                _ => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "Invalid klines format.".into(),
+                    )))
+                }
+            };
+            let expiry = match expiry.trim() {
+                "" => None,
+                expiry => Some(expiry.parse()?),
+            };
+            entries.push(Kline {
+                mask: mask.to_string(),
+                reason: reason.to_string(),
+                expiry,
+            });
+        }
+        Ok(Klines {
+            path: Some(path),
+            entries,
+        })
+    }
+
+    /// Given a 'user@host' and the casemapping of the server returns the K-line that bans it,
+    /// if there is one in force
+    pub fn find(&self, user_host: &str, casemapping: CaseMapping) -> Option<&Kline> {
+        let now = now();
+        self.entries.iter().find(|kline| {
+            !kline.is_expired(now) && mask::matches(&kline.mask, user_host, casemapping)
+        })
+    }
+
+    /// Given the host of a connection that didn't send USER yet returns the K-line that bans it,
+    /// only the K-lines that ban any user of the host (i.e. '*@host') are taken into account
+    pub fn find_host(&self, host: &str, casemapping: CaseMapping) -> Option<&Kline> {
+        let now = now();
+        self.entries
+            .iter()
+            .find(|kline| match kline.mask.split_once('@') {
+                Some((user, host_mask)) => {
+                    !kline.is_expired(now)
+                        && user.chars().all(|c| c == '*')
+                        && mask::matches(host_mask, host, casemapping)
+                }
+                None => false,
+            })
+    }
+
+    /// Given self returns the path of the file the K-lines are saved to, if any
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Given a K-line and the casemapping of the server adds it (replacing the one with the same mask,
+    /// if any) and saves the file
+    pub fn add(&mut self, kline: Kline, casemapping: CaseMapping) -> Result<(), Box<dyn Error>> {
+        let mask = casemapping.to_lower(&kline.mask);
+        self.entries
+            .retain(|old| casemapping.to_lower(&old.mask) != mask);
+        self.entries.push(kline);
+        self.save()
+    }
+
+    /// Given a mask and the casemapping of the server removes its K-line and saves the file,
+    /// returns whether there was one
+    pub fn remove(&mut self, mask: &str, casemapping: CaseMapping) -> Result<bool, Box<dyn Error>> {
+        let mask = casemapping.to_lower(mask);
+        let len = self.entries.len();
+        self.entries
+            .retain(|kline| casemapping.to_lower(&kline.mask) != mask);
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Writes the K-lines in force to the file they were loaded from (if any)
+    fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let now = now();
+        self.entries.retain(|kline| !kline.is_expired(now));
+        if let Some(path) = &self.path {
+            let contents: String = self
+                .entries
+                .iter()
+                .map(|kline| {
+                    let expiry = kline.expiry.map(|x| x.to_string()).unwrap_or_default();
+                    format!("{},{},{}\n", kline.mask, kline.reason, expiry)
+                })
+                .collect();
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod klines_test {
+    use super::*;
+
+    #[test]
+    fn klines_are_loaded_from_csv() {
+        let klines = Klines::load("tests/test_files/valid_klines".to_string()).expect("");
+        let kline = klines
+            .find("botter@10.0.0.1", CaseMapping::Rfc1459)
+            .expect("");
+        assert_eq!(kline.reason, "flood, spam");
+        assert_eq!(kline.expiry, None);
+        assert!(klines
+            .find("juan@127.0.0.1", CaseMapping::Rfc1459)
+            .is_none());
+    }
+
+    #[test]
+    fn expired_klines_are_ignored() {
+        let klines = Klines::load("tests/test_files/valid_klines".to_string()).expect("");
+        assert!(klines
+            .find("pepe@192.168.0.7", CaseMapping::Rfc1459)
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_klines_file_fails() {
+        assert!(Klines::load("tests/test_files/invalid_csv_file".to_string()).is_err());
+    }
+
+    #[test]
+    fn missing_klines_file_starts_empty() {
+        let klines = Klines::load("tests/test_files/missing_klines".to_string()).expect("");
+        assert!(klines
+            .find("botter@10.0.0.1", CaseMapping::Rfc1459)
+            .is_none());
+    }
+
+    #[test]
+    fn unreadable_klines_file_fails() {
+        let path = std::env::temp_dir().join("irc_klines_not_utf8.csv");
+        fs::write(&path, b"*@10.0.0.*,\xff\xfe,\n").expect("");
+        assert!(Klines::load(path.to_string_lossy().to_string()).is_err());
+        assert!(Klines::load("tests/test_files".to_string()).is_err());
+    }
+
+    #[test]
+    fn host_klines_only_use_masks_for_any_user() {
+        let mut klines = Klines::default();
+        klines
+            .add(
+                Kline {
+                    mask: "juan@127.0.0.*".to_string(),
+                    reason: "no".to_string(),
+                    expiry: None,
+                },
+                CaseMapping::Rfc1459,
+            )
+            .expect("");
+        assert!(klines
+            .find_host("127.0.0.1", CaseMapping::Rfc1459)
+            .is_none());
+        klines
+            .add(
+                Kline {
+                    mask: "*@127.0.0.*".to_string(),
+                    reason: "no".to_string(),
+                    expiry: None,
+                },
+                CaseMapping::Rfc1459,
+            )
+            .expect("");
+        assert!(klines
+            .find_host("127.0.0.1", CaseMapping::Rfc1459)
+            .is_some());
+        assert!(klines
+            .remove("*@127.0.0.*", CaseMapping::Rfc1459)
+            .expect(""));
+        assert!(!klines
+            .remove("*@127.0.0.*", CaseMapping::Rfc1459)
+            .expect(""));
+        assert!(klines
+            .find_host("127.0.0.1", CaseMapping::Rfc1459)
+            .is_none());
+    }
+
+    #[test]
+    fn masks_of_klines_are_compared_with_the_casemapping() {
+        let kline = |mask: &str| Kline {
+            mask: mask.to_string(),
+            reason: "no".to_string(),
+            expiry: None,
+        };
+        let mut klines = Klines::default();
+        klines
+            .add(kline("nick[1]@host"), CaseMapping::Rfc1459)
+            .expect("");
+        klines
+            .add(kline("NICK{1}@HOST"), CaseMapping::Rfc1459)
+            .expect("");
+        assert_eq!(klines.entries, vec![kline("NICK{1}@HOST")]);
+        assert!(!klines.remove("nick[1]@host", CaseMapping::Ascii).expect(""));
+        assert!(klines
+            .remove("nick[1]@host", CaseMapping::Rfc1459)
+            .expect(""));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::app_errors;
+use crate::server::client_s::ClientS;
+use crate::server::connection::Connection;
+use crate::server::listener::{join_address, split_address};
+use crate::server::logger::Logger;
+use crate::server::tls::Stream;
+use crate::server::ClientsInfo;
+use rustls::ClientConfig;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// espera maxima entre dos intentos de un autoconnect que sigue fallando
+const MAX_AUTOCONNECT_WAIT: Duration = Duration::from_secs(600);
+// cuanto se espera la respuesta del otro server al registrarse
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A connect block of the config: a server this one may link to with CONNECT,
+/// or on its own every autoconnect interval while the link is down
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectBlock {
+    pub name: String,
+    pub address: String, //without brackets, even if it's an IPv6 address
+    pub port: String,
+    pub password: Option<String>, //sent with PASS when registering
+    pub autoconnect: Option<Duration>,
+    pub tls: bool,
+}
+
+impl ConnectBlock {
+    /// Given the name, address and port returns a plain text block without password nor autoconnect
+    pub fn new(name: String, address: String, port: String) -> ConnectBlock {
+        ConnectBlock {
+            name,
+            address,
+            port,
+            password: None,
+            autoconnect: None,
+            tls: false,
+        }
+    }
+
+    /// Given the value of a connect key with the format
+    /// "name address:port [password=password] [autoconnect=seconds] [tls]" returns the block.
+    /// IPv6 addresses go between brackets, i.e. "server_dos [::1]:7000 autoconnect=30"
+    pub fn build(value: &str) -> Result<ConnectBlock, Box<dyn Error>> {
+        let mut words = value.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let mut block = match split_address(words.next().unwrap_or_default()) {
+            Some((address, port)) => {
+                ConnectBlock::new(name.to_string(), address.to_string(), port.to_string())
+            }
+            None => return Err(Self::invalid()),
+        };
+        for option in words {
+            match option.split_once('=') {
+                Some(("password", password)) => block.password = Some(password.to_string()),
+                Some(("autoconnect", seconds)) => match seconds.parse() {
+                    Ok(seconds) if seconds > 0 => {
+                        block.autoconnect = Some(Duration::from_secs(seconds))
+                    }
+                    _ => return Err(Self::invalid()),
+                },
+                None if option == "tls" => block.tls = true,
+                _ => return Err(Self::invalid()),
+            }
+        }
+        Ok(block)
+    }
+
+    fn invalid() -> Box<dyn Error> {
+        Box::new(app_errors::ApplicationError(
+            "Invalid connect format.".into(),
+        ))
+    }
+
+    /// Returns the address to connect to, with the IPv6 addresses between brackets
+    pub fn connect_address(&self) -> String {
+        join_address(&self.address, &self.port)
+    }
+}
+
+/// Everything needed to serve the connections of this server and to open links to other servers:
+/// the one given in the command line and the ones of the connect blocks
+#[derive(Clone)]
+pub struct Linker {
+    clients: Arc<Mutex<ClientsInfo>>,
+    trusted_servers: HashMap<String, Option<String>>,
+    log: Arc<Mutex<Logger>>,
+    server_name: String,
+    password: Option<String>,
+    sendq_size: usize,
+    connect_blocks: Vec<ConnectBlock>,
+    tls_config: Option<Arc<ClientConfig>>, //used by the links with TLS
+}
+
+impl Linker {
+    /// Given the ClientsInfo lock, the trusted servers, the logger lock, the password of the server
+    /// and the sendq size of its connections creates the linker, without connect blocks
+    pub fn new(
+        clients: Arc<Mutex<ClientsInfo>>,
+        trusted_servers: HashMap<String, Option<String>>,
+        log: Arc<Mutex<Logger>>,
+        password: Option<String>,
+        sendq_size: usize,
+    ) -> Linker {
+        let server_name = clients
+            .lock()
+            .expect("Error: poisoned clients lock creating the linker")
+            .server_name();
+        Linker {
+            clients,
+            trusted_servers,
+            log,
+            server_name,
+            password,
+            sendq_size,
+            connect_blocks: vec![],
+            tls_config: None,
+        }
+    }
+
+    /// Given the connect blocks of the config and the TLS config for the links that use it sets them
+    pub fn set_connect_blocks(
+        &mut self,
+        connect_blocks: Vec<ConnectBlock>,
+        tls_config: Option<Arc<ClientConfig>>,
+    ) {
+        self.connect_blocks = connect_blocks;
+        self.tls_config = tls_config;
+    }
+
+    /// Given the name of a server returns its connect block, if there is one
+    pub fn connect_block(&self, name: &str) -> Option<ConnectBlock> {
+        self.connect_blocks
+            .iter()
+            .find(|block| block.name == name)
+            .cloned()
+    }
+
+    /// Returns the connect blocks with autoconnect
+    pub fn autoconnect_blocks(&self) -> Vec<ConnectBlock> {
+        self.connect_blocks
+            .iter()
+            .filter(|block| block.autoconnect.is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// Given a connection (accepted by a listener or opened by this server) returns the client that serves it
+    pub fn client(&self, mut connection: Connection) -> Result<ClientS, Box<dyn Error>> {
+        connection.set_sendq_size(self.sendq_size);
+        let mut client = ClientS::new(
+            self.clients.clone(),
+            Arc::new(Mutex::new(connection)),
+            self.trusted_servers.clone(),
+            self.log.clone(),
+        )?;
+        client.set_linker(self.clone());
+        Ok(client)
+    }
+
+    /// Given a connect block connects to its server and registers sending PASS (if the block has a password)
+    /// and SERVER. Returns the connection once the other server accepts this one
+    pub fn open(&self, block: &ConnectBlock) -> Result<Connection, Box<dyn Error>> {
+        let mut stream = match (block.tls, &self.tls_config) {
+            (true, Some(tls_config)) => {
+                Stream::connect(&block.address, &block.port, tls_config.clone())?
+            }
+            (true, None) => {
+                return Err(Box::new(app_errors::ApplicationError(format!(
+                    "Error: the link to {} needs a TLS config",
+                    block.name
+                ))))
+            }
+            (false, _) => Stream::from(TcpStream::connect(block.connect_address())?),
+        };
+        Self::register(&mut stream, &block.password, &self.server_name)?;
+        Ok(Connection::new(stream)?)
+    }
+
+    /// Given the connection to a server this one registered to and its name adds the server to the network
+    /// and serves the link in a new thread, that ends when the link is lost.
+    /// Fails (closing the link) if the server was already known, there would be a loop in the network
+    pub fn start(
+        &self,
+        connection: Connection,
+        name: String,
+    ) -> Result<JoinHandle<io::Result<()>>, Box<dyn Error>> {
+        let mut client = self.client(connection)?;
+        client.set_parent(name, self.password.clone())?;
+        Ok(thread::spawn(move || client.run()))
+    }
+
+    /// Given a connect block links this server to the one of the block (see open and start)
+    pub fn link(&self, block: &ConnectBlock) -> Result<JoinHandle<io::Result<()>>, Box<dyn Error>> {
+        let connection = self.open(block)?;
+        self.start(connection, block.name.clone())
+    }
+
+    /// Given a block with autoconnect keeps this server linked to the one of the block, never returns.
+    /// While the server isn't part of the network it tries to link every interval,
+    /// doubling the wait after each failed attempt (up to MAX_AUTOCONNECT_WAIT)
+    pub fn autoconnect(&self, block: ConnectBlock) {
+        let interval = match block.autoconnect {
+            Some(interval) => interval,
+            None => return,
+        };
+        let mut wait = interval;
+        loop {
+            let linked = self
+                .clients
+                .lock()
+                .expect("Error: poisoned clients lock during autoconnect")
+                .is_known_server(&block.name);
+            if !linked {
+                match self.link(&block) {
+                    Ok(link) => {
+                        self.write_log(format!("Linked to {}", block.name));
+                        // vuelve a intentar cuando se pierde el enlace
+                        let _ = link.join();
+                        self.write_log(format!("Lost link with {}", block.name));
+                        wait = interval;
+                    }
+                    Err(err) => {
+                        self.write_log(format!("Error linking to {}: {}", block.name, err));
+                        thread::sleep(wait);
+                        wait = Self::next_wait(wait, interval);
+                        continue;
+                    }
+                }
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    /// Given the last wait between two attempts of an autoconnect and its interval returns the next wait
+    fn next_wait(wait: Duration, interval: Duration) -> Duration {
+        (wait * 2).min(MAX_AUTOCONNECT_WAIT.max(interval))
+    }
+
+    fn write_log(&self, line: String) {
+        self.log
+            .lock()
+            .expect("Error: log lock poisoned")
+            .write(line);
+    }
+
+    /// Given the stream to a server, the password to send and the name of this server registers to it.
+    /// An error numeric in the answer (i.e. wrong password) is returned as an error
+    fn register(
+        stream: &mut Stream,
+        password: &Option<String>,
+        server_name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(password) = password {
+            stream.write_all(format!("PASS {}\n", password).as_bytes())?;
+        }
+        stream.write_all(format!("SERVER {} 1 info\n", server_name).as_bytes())?;
+        stream.set_read_timeout(Some(REGISTER_TIMEOUT))?;
+        let ans = Self::read_line(stream)?;
+        stream.set_read_timeout(None)?;
+        println!("{ans}");
+        if Self::is_error_reply(&ans) {
+            return Err(Box::new(app_errors::ApplicationError(ans)));
+        }
+        Ok(())
+    }
+
+    /// Given the answer of the other server returns whether it is an error numeric (4xx or 5xx).
+    /// The answer may or may not start with the server's prefix.
+    fn is_error_reply(ans: &str) -> bool {
+        let mut words = ans.trim().split(' ');
+        let mut numeric = words.next().unwrap_or_default();
+        if numeric.starts_with(':') {
+            numeric = words.next().unwrap_or_default();
+        }
+        numeric.len() == 3 && (numeric.starts_with('4') || numeric.starts_with('5'))
+    }
+
+    /// Reads the first non-empty line from the stream and returns it.
+    /// It's read a byte at a time, so the lines after it are left for the client that serves the link
+    fn read_line(stream: &mut impl Read) -> Result<String, Box<dyn Error>> {
+        let mut line = vec![];
+        let mut byte = [0];
+        loop {
+            stream.read_exact(&mut byte)?;
+            match byte[0] {
+                b'\n' if !line.is_empty() => break,
+                b'\n' | b'\r' => {}
+                other => line.push(other),
+            }
+        }
+        Ok(String::from_utf8(line)?)
+    }
+}
+
+#[cfg(test)]
+mod link_test {
+    use super::*;
+
+    #[test]
+    fn connect_block_is_parsed() {
+        let block = ConnectBlock::build("server_dos [::1]:7000 password=2222 autoconnect=30 tls")
+            .expect("");
+        assert_eq!(block.name, "server_dos");
+        assert_eq!(block.connect_address(), "[::1]:7000");
+        assert_eq!(block.password, Some("2222".to_string()));
+        assert_eq!(block.autoconnect, Some(Duration::from_secs(30)));
+        assert!(block.tls);
+        assert!(ConnectBlock::build("server_dos localhost").is_err());
+        assert!(ConnectBlock::build("server_dos localhost:7000 autoconnect=0").is_err());
+        assert!(ConnectBlock::build("server_dos localhost:7000 udp").is_err());
+    }
+
+    #[test]
+    fn autoconnect_wait_doubles_up_to_the_max() {
+        let interval = Duration::from_secs(5);
+        let mut wait = interval;
+        for _ in 0..3 {
+            wait = Linker::next_wait(wait, interval);
+        }
+        assert_eq!(wait, Duration::from_secs(40));
+        for _ in 0..10 {
+            wait = Linker::next_wait(wait, interval);
+        }
+        assert_eq!(wait, MAX_AUTOCONNECT_WAIT);
+    }
+
+    #[test]
+    fn error_numerics_from_the_other_server_are_detected() {
+        assert!(Linker::is_error_reply(
+            ":server_uno 464 * :Password incorrect"
+        ));
+        assert!(Linker::is_error_reply("464 :Password incorrect"));
+        assert!(!Linker::is_error_reply("SERVER server_uno 1 :info"));
+    }
+
+    #[test]
+    fn first_line_is_read_without_the_next_ones() {
+        let mut answer = "\r\n:server_uno 001 server_dos\r\nSERVER server_tres 2 info\n".as_bytes();
+        assert_eq!(
+            Linker::read_line(&mut answer).expect(""),
+            ":server_uno 001 server_dos"
+        );
+        assert_eq!(answer, "SERVER server_tres 2 info\n".as_bytes());
+        assert!(Linker::read_line(&mut "sin fin".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::app_errors;
+use crate::server::connection::Connection;
+use crate::server::tls::Stream;
+use rustls::ServerConfig;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+/// Which connections a listener accepts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListenerKind {
+    #[default]
+    Any,
+    Clients,
+    Servers,
+}
+
+/// A listen block of the config: where to bind, whether it uses TLS and who may connect through it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Listen {
+    pub address: String, //without brackets, even if it's an IPv6 address
+    pub port: String,
+    pub tls: bool,
+    pub kind: ListenerKind,
+}
+
+impl Listen {
+    /// Given the address and port returns a plain text listen block for any connection
+    pub fn new(address: String, port: String) -> Listen {
+        Listen {
+            address,
+            port,
+            tls: false,
+            kind: ListenerKind::Any,
+        }
+    }
+
+    /// Given the value of a listen key with the format "address:port [tls] [clients|servers]" returns the block.
+    /// IPv6 addresses go between brackets, i.e. "[::1]:6697 tls clients"
+    pub fn build(value: &str) -> Result<Listen, Box<dyn Error>> {
+        let mut words = value.split_whitespace();
+        let mut listen = match split_address(words.next().unwrap_or_default()) {
+            Some((address, port)) => Listen::new(address.to_string(), port.to_string()),
+            _ => {
+                return Err(Box::new(app_errors::ApplicationError(
+                    "Invalid listen format.".into(),
+                )))
+            }
+        };
+        for option in words {
+            match option {
+                "tls" => listen.tls = true,
+                "clients" => listen.kind = ListenerKind::Clients,
+                "servers" => listen.kind = ListenerKind::Servers,
+                _ => {
+                    return Err(Box::new(app_errors::ApplicationError(
+                        "Invalid listen format.".into(),
+                    )))
+                }
+            }
+        }
+        Ok(listen)
+    }
+
+    /// Returns the address to bind, with the IPv6 addresses between brackets
+    pub fn bind_address(&self) -> String {
+        join_address(&self.address, &self.port)
+    }
+
+    /// Returns whether users may register through this listener
+    pub fn accepts_clients(&self) -> bool {
+        self.kind != ListenerKind::Servers
+    }
+
+    /// Returns whether servers may link through this listener
+    pub fn accepts_servers(&self) -> bool {
+        self.kind != ListenerKind::Clients
+    }
+}
+
+/// Given "address:port" (IPv6 addresses between brackets) returns the address without brackets and the port.
+/// Returns None if the port isn't a number
+pub fn split_address(value: &str) -> Option<(&str, &str)> {
+    let split = match value.strip_prefix('[') {
+        Some(value) => value.split_once("]:"),
+        None => value.rsplit_once(':'),
+    };
+    split.filter(|(address, port)| !address.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// Given the address and the port returns "address:port", with the IPv6 addresses between brackets
+pub fn join_address(address: &str, port: &str) -> String {
+    match address.contains(':') {
+        true => format!("[{}]:{}", address, port),
+        false => format!("{}:{}", address, port),
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.bind_address())?;
+        if self.tls {
+            write!(f, " tls")?;
+        }
+        match self.kind {
+            ListenerKind::Any => Ok(()),
+            ListenerKind::Clients => write!(f, " clients"),
+            ListenerKind::Servers => write!(f, " servers"),
+        }
+    }
+}
+
+/// Socket bound for a listen block. Every connection it accepts is tagged with the block
+pub struct Listener {
+    socket: TcpListener,
+    listen: Listen,
+    tls_config: Option<Arc<ServerConfig>>,
+}
+
+impl Listener {
+    /// Given the listen block and the TLS config (needed if the block uses TLS) binds its socket
+    pub fn bind(
+        listen: Listen,
+        tls_config: Option<Arc<ServerConfig>>,
+    ) -> Result<Listener, Box<dyn Error>> {
+        if listen.tls && tls_config.is_none() {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Error: the tls port needs a certificate and a key".into(),
+            )));
+        }
+        Ok(Listener {
+            socket: TcpListener::bind(listen.bind_address())?,
+            tls_config: tls_config.filter(|_| listen.tls),
+            listen,
+        })
+    }
+
+    pub fn listen(&self) -> &Listen {
+        &self.listen
+    }
+
+    /// Waits for the next connection and returns it, tagged with the listen block.
+    /// The TLS handshake is completed as the data of the client is read
+    pub fn accept(&self) -> io::Result<Connection> {
+        let (socket, _) = self.socket.accept()?;
+        let stream = match &self.tls_config {
+            Some(tls_config) => Stream::accept(socket, tls_config.clone())?,
+            None => Stream::from(socket),
+        };
+        let mut connection = Connection::new(stream)?;
+        connection.set_listen(self.listen.clone());
+        Ok(connection)
+    }
+}
+
+#[cfg(test)]
+mod listener_test {
+    use super::*;
+
+    #[test]
+    fn listen_block_is_parsed() {
+        let listen = Listen::build("0.0.0.0:6697 tls clients").expect("");
+        assert_eq!(listen.address, "0.0.0.0");
+        assert_eq!(listen.port, "6697");
+        assert!(listen.tls);
+        assert!(listen.accepts_clients());
+        assert!(!listen.accepts_servers());
+    }
+
+    #[test]
+    fn ipv6_listen_block_is_parsed() {
+        let listen = Listen::build("[::1]:7000 servers").expect("");
+        assert_eq!(listen.address, "::1");
+        assert_eq!(listen.bind_address(), "[::1]:7000");
+        assert!(!listen.tls);
+        assert!(!listen.accepts_clients());
+        assert_eq!(listen.to_string(), "[::1]:7000 servers");
+    }
+
+    #[test]
+    fn invalid_listen_block_fails() {
+        assert!(Listen::build("localhost").is_err());
+        assert!(Listen::build("localhost:puerto").is_err());
+        assert!(Listen::build("localhost:7000 udp").is_err());
+    }
+}
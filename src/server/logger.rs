@@ -8,10 +8,7 @@ pub struct Logger {
 /// either for debugging or to save session information
 impl Logger {
     pub fn build(path: String) -> Logger {
-        let file = std::fs::OpenOptions::new()
-            .write(true)
-            .append(true)
-            .open(path);
+        let file = std::fs::OpenOptions::new().append(true).open(path);
 
         match file {
             Ok(x) => Logger { file: x },
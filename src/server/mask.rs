@@ -0,0 +1,103 @@
+use crate::server::irc_name::CaseMapping;
+
+/// Given a mask as written by the user returns it in the complete 'nick!user@host' form.
+/// Missing parts are replaced by '*' (e.g. "juan" is "juan!*@*" and "*@host" is "*!*@host").
+pub fn normalize(mask: &str) -> String {
+    let (nick_user, host) = match mask.split_once('@') {
+        Some((nick_user, host)) => (nick_user, host),
+        None if mask.contains('!') => (mask, "*"),
+        None => return format!("{}!*@*", mask),
+    };
+    let (nick, user) = match nick_user.split_once('!') {
+        Some((nick, user)) => (nick, user),
+        None => ("*", nick_user),
+    };
+    format!("{}!{}@{}", or_any(nick), or_any(user), or_any(host))
+}
+
+/// Given a part of a mask returns '*' if it's empty
+fn or_any(part: &str) -> &str {
+    if part.is_empty() {
+        "*"
+    } else {
+        part
+    }
+}
+
+/// Given a mask with wildcards ('*' any sequence, '?' any character), a name and the casemapping
+/// of the server returns whether the name matches the mask. The case is ignored as in the nicks.
+pub fn matches(mask: &str, name: &str, casemapping: CaseMapping) -> bool {
+    let mask: Vec<char> = casemapping.to_lower(mask).chars().collect();
+    let name: Vec<char> = casemapping.to_lower(name).chars().collect();
+    let (mut m, mut n) = (0, 0);
+    // posicion del ultimo '*' visto y de donde se estaba comparando el nombre
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if m < mask.len() && (mask[m] == '?' || mask[m] == name[n]) {
+            m += 1;
+            n += 1;
+        } else if m < mask.len() && mask[m] == '*' {
+            backtrack = Some((m, n));
+            m += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // el '*' se come un caracter mas
+            m = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    mask[m..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod mask_test {
+    use crate::server::irc_name::CaseMapping;
+    use crate::server::mask::{matches, normalize};
+
+    #[test]
+    fn incomplete_masks_are_normalized() {
+        assert_eq!(normalize("juan"), "juan!*@*");
+        assert_eq!(normalize("*@127.0.0.1"), "*!*@127.0.0.1");
+        assert_eq!(normalize("juan!botter"), "juan!botter@*");
+        assert_eq!(normalize("juan!botter@host"), "juan!botter@host");
+    }
+
+    #[test]
+    fn wildcards_match_any_sequence_or_character() {
+        let casemapping = CaseMapping::Rfc1459;
+        assert!(matches(
+            "*!*@127.0.0.1",
+            "juan!botter@127.0.0.1",
+            casemapping
+        ));
+        assert!(matches("ju?n!*@*", "JUAN!botter@host", casemapping));
+        assert!(matches("*a*a*", "banana", casemapping));
+        assert!(!matches(
+            "*!*@127.0.0.1",
+            "juan!botter@127.0.0.2",
+            casemapping
+        ));
+        assert!(!matches("ju?n!*@*", "jun!botter@host", casemapping));
+    }
+
+    #[test]
+    fn masks_fold_case_with_the_casemapping() {
+        assert!(matches(
+            "juan[1]!*@*",
+            "JUAN{1}!botter@host",
+            CaseMapping::Rfc1459
+        ));
+        assert!(!matches(
+            "juan[1]!*@*",
+            "JUAN{1}!botter@host",
+            CaseMapping::Ascii
+        ));
+        assert!(matches(
+            "juan[1]!*@*",
+            "JUAN[1]!botter@host",
+            CaseMapping::Ascii
+        ));
+    }
+}
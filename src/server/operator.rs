@@ -0,0 +1,155 @@
+use crate::app_errors;
+use crate::server::irc_name::CaseMapping;
+use crate::server::mask;
+use crate::server::password;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// What an operator may do besides being one (i.e. sending WALLOPS)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Privilege {
+    /// SQUIT and CONNECT
+    Route,
+    /// KILL
+    Kill,
+    /// KLINE and UNKLINE
+    Kline,
+}
+
+impl Privilege {
+    /// Given the name of a privilege as written in the operators file returns it
+    fn build(name: &str) -> Result<Privilege, Box<dyn Error>> {
+        match name.to_lowercase().as_str() {
+            "route" => Ok(Privilege::Route),
+            "kill" => Ok(Privilege::Kill),
+            "kline" => Ok(Privilege::Kline),
+            _ => Err(Box::new(app_errors::ApplicationError(format!(
+                "Unknown operator privilege {}.",
+                name
+            )))),
+        }
+    }
+
+    /// Returns every privilege, the ones of the operators that don't list them
+    pub fn all() -> HashSet<Privilege> {
+        HashSet::from([Privilege::Route, Privilege::Kill, Privilege::Kline])
+    }
+}
+
+/// An operator block: the name and password given to OPER, the masks of the hosts it may log in from
+/// and what it may do. The name doesn't need to be the nick of who logs in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operator {
+    pub name: String,
+    pub password: String, //plaintext or hashed
+    pub hosts: Vec<String>,
+    pub privileges: HashSet<Privilege>,
+}
+
+impl Operator {
+    /// Given the name and password returns a block for any host with every privilege
+    pub fn new(name: String, password: String) -> Operator {
+        Operator {
+            name,
+            password,
+            hosts: vec!["*@*".to_string()],
+            privileges: Privilege::all(),
+        }
+    }
+
+    /// Given a line of the operators file with the format "name,password[,hosts[,privileges]]" returns the block.
+    /// Hosts and privileges are separated by spaces, if they are missing (like in the files of older versions)
+    /// the operator may log in from any host and has every privilege. The password may be a hash with commas.
+    pub fn build(line: &str) -> Result<Operator, Box<dyn Error>> {
+        let (name, rest) = match line.trim().split_once(',') {
+            Some((name, rest)) if !name.is_empty() => (name, rest),
+            _ => {
+                return Err(Box::new(app_errors::ApplicationError(
+                    "Invalid operators format.".into(),
+                )))
+            }
+        };
+        let (password, rest) = password::split_password(rest);
+        let fields: Vec<&str> = match rest {
+            Some(rest) => rest.split(',').collect(),
+            None => vec![],
+        };
+        if fields.len() > 2 {
+            return Err(Box::new(app_errors::ApplicationError(
+                "Invalid operators format.".into(),
+            )));
+        }
+        let mut operator = Operator::new(name.to_string(), password.to_string());
+        let hosts: Vec<String> = match fields.first() {
+            Some(hosts) => hosts.split_whitespace().map(String::from).collect(),
+            None => vec![],
+        };
+        if !hosts.is_empty() {
+            operator.hosts = hosts;
+        }
+        if let Some(privileges) = fields
+            .get(1)
+            .filter(|privileges| !privileges.trim().is_empty())
+        {
+            operator.privileges = privileges
+                .split_whitespace()
+                .map(Privilege::build)
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(operator)
+    }
+
+    /// Given the user and host of a connection and the casemapping of the server returns whether
+    /// the operator may log in from it. A mask without '@' is compared only with the host.
+    pub fn allows_host(&self, user: &str, host: &str, casemapping: CaseMapping) -> bool {
+        let user_host = format!("{}@{}", user, host);
+        self.hosts.iter().any(|mask| match mask.contains('@') {
+            true => mask::matches(mask, &user_host, casemapping),
+            false => mask::matches(mask, host, casemapping),
+        })
+    }
+}
+
+#[cfg(test)]
+mod operator_test {
+    use super::*;
+
+    #[test]
+    fn operator_of_older_versions_has_every_privilege() {
+        let operator = Operator::build("juan,123").expect("");
+        assert_eq!(operator.name, "juan");
+        assert_eq!(operator.password, "123");
+        assert!(operator.allows_host("juan", "10.0.0.1", CaseMapping::Rfc1459));
+        assert_eq!(operator.privileges, Privilege::all());
+    }
+
+    #[test]
+    fn operator_is_restricted_to_its_hosts() {
+        let operator =
+            Operator::build("juan,123,*@127.0.0.1 botter@10.0.0.*,kill kline").expect("");
+        assert!(operator.allows_host("pepe", "127.0.0.1", CaseMapping::Rfc1459));
+        assert!(operator.allows_host("botter", "10.0.0.7", CaseMapping::Rfc1459));
+        assert!(!operator.allows_host("pepe", "10.0.0.7", CaseMapping::Rfc1459));
+        assert_eq!(
+            operator.privileges,
+            HashSet::from([Privilege::Kill, Privilege::Kline])
+        );
+    }
+
+    #[test]
+    fn operator_with_hashed_password_keeps_the_whole_hash() {
+        let stored = password::hash("botter").expect("");
+        let operator = Operator::build(&format!("juan,{},*@127.0.0.1,kill", stored)).expect("");
+        assert_eq!(operator.password, stored);
+        assert!(password::verify(&operator.password, "botter"));
+        assert_eq!(operator.hosts, vec!["*@127.0.0.1".to_string()]);
+        assert_eq!(operator.privileges, HashSet::from([Privilege::Kill]));
+    }
+
+    #[test]
+    fn invalid_operator_fails() {
+        assert!(Operator::build("juan").is_err());
+        assert!(Operator::build("juan,123,*,kill,otro").is_err());
+        assert!(Operator::build("juan,123,*,volar").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::error::Error;
+
+/// Given a password returns its salted argon2 hash, in the PHC format ("$argon2id$...")
+/// used in the operators and trusted servers files
+pub fn hash(password: &str) -> Result<String, Box<dyn Error>> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| err.to_string())?;
+    Ok(hash.to_string())
+}
+
+/// Given a password read from a file returns whether it's a hash (the plaintext ones are still accepted)
+pub fn is_hashed(stored: &str) -> bool {
+    stored.starts_with("$argon2")
+}
+
+/// Given the password read from a file (hashed or plaintext) and the one sent by a connection
+/// returns whether they match. The comparison takes the same time wherever they differ
+pub fn verify(stored: &str, password: &str) -> bool {
+    if !is_hashed(stored) {
+        return constant_time_eq(stored.as_bytes(), password.as_bytes());
+    }
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Given the fields of a line of a file that start with a password returns the password and the
+/// fields after it, if there are any. The parameters of a hash are separated by commas
+/// ("$argon2id$v=19$m=19456,t=2,p=1$..."), so a hash ends at the first comma after its last '$'
+pub fn split_password(fields: &str) -> (&str, Option<&str>) {
+    let start = match is_hashed(fields) {
+        true => fields.rfind('$').unwrap_or(0),
+        false => 0,
+    };
+    match fields[start..].find(',') {
+        Some(end) => (&fields[..start + end], Some(&fields[start + end + 1..])),
+        None => (fields, None),
+    }
+}
+
+/// Given two byte strings returns whether they are equal, going over all of them
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod password_test {
+    use super::*;
+
+    #[test]
+    fn hashed_password_is_verified() {
+        let stored = hash("botter").expect("");
+        assert!(is_hashed(&stored));
+        assert!(verify(&stored, "botter"));
+        assert!(!verify(&stored, "Botter"));
+    }
+
+    #[test]
+    fn same_password_gets_different_salts() {
+        assert_ne!(hash("botter").expect(""), hash("botter").expect(""));
+    }
+
+    #[test]
+    fn plaintext_password_is_still_verified() {
+        assert!(!is_hashed("botter"));
+        assert!(verify("botter", "botter"));
+        assert!(!verify("botter", "botte"));
+        assert!(!verify("botter", "bottes"));
+    }
+
+    #[test]
+    fn hash_is_split_at_the_comma_after_it() {
+        let stored = hash("botter").expect("");
+        assert!(stored.contains(','));
+        assert_eq!(split_password(&stored), (stored.as_str(), None));
+        let fields = format!("{},*@127.0.0.1,kill", stored);
+        assert_eq!(
+            split_password(&fields),
+            (stored.as_str(), Some("*@127.0.0.1,kill"))
+        );
+        assert_eq!(split_password("123,*,kill"), ("123", Some("*,kill")));
+        assert_eq!(split_password(""), ("", None));
+    }
+
+    #[test]
+    fn invalid_hash_never_matches() {
+        assert!(!verify("$argon2id$roto", "$argon2id$roto"));
+    }
+}
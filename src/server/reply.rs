@@ -0,0 +1,145 @@
+use crate::app_errors;
+use std::error::Error;
+
+/// Format used when writing numeric replies to a connection.
+/// Rfc1459 is the one expected by standard clients, Legacy is the bare
+/// "<numeric> <text>" format understood by our GTK client.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ReplyFormat {
+    #[default]
+    Rfc1459,
+    Legacy,
+}
+
+impl ReplyFormat {
+    /// Given the value of the `reply_format` config key returns the corresponding format
+    pub fn build(value: &str) -> Result<ReplyFormat, Box<dyn Error>> {
+        match value.trim() {
+            "rfc1459" => Ok(ReplyFormat::Rfc1459),
+            "legacy" => Ok(ReplyFormat::Legacy),
+            other => Err(Box::new(app_errors::ApplicationError(format!(
+                "Unknown reply format: {other}"
+            )))),
+        }
+    }
+}
+
+/// A numeric reply (one of the `app_errors` constants) with its parameters.
+/// Online reference for reply format: rfc-editor.org/rfc/rfc1459#section-2.4
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Reply {
+    code: (i32, &'static str),
+    params: Vec<String>,
+}
+
+impl Reply {
+    pub fn build(code: (i32, &'static str), params: Vec<String>) -> Reply {
+        Reply { code, params }
+    }
+
+    /// Given self returns the numeric of the reply
+    pub fn number(&self) -> i32 {
+        self.code.0
+    }
+
+    /// Given self returns the reply text with every placeholder replaced by its parameter.
+    /// Placeholders without a parameter are replaced by '*'.
+    /// It's done in one pass, so a parameter holding "{}" (valid in nicks) isn't taken as a placeholder.
+    pub fn text(&self) -> String {
+        let mut pieces = self.code.1.split("{}");
+        let mut text = pieces.next().unwrap_or_default().to_string();
+        for (index, piece) in pieces.enumerate() {
+            text.push_str(self.params.get(index).map(String::as_str).unwrap_or("*"));
+            text.push_str(piece);
+        }
+        text
+    }
+
+    /// Given self, the format, the name of this server and the nick of the receiver
+    /// returns the line to be written (without the line ending).
+    /// The receiver should be '*' while the connection has no nick.
+    pub fn format(&self, format: ReplyFormat, server_name: &str, target: &str) -> String {
+        match format {
+            ReplyFormat::Rfc1459 => format!(
+                ":{} {:03} {} {}",
+                server_name,
+                self.number(),
+                target,
+                self.text()
+            ),
+            ReplyFormat::Legacy => format!("{:03} {}", self.number(), self.text()),
+        }
+    }
+
+    /// Given self returns whether the reply must be written to the client.
+    /// RPL_NONE is used by commands that don't answer with a numeric.
+    pub fn is_none(&self) -> bool {
+        self.code == app_errors::RPL_NONE
+    }
+}
+
+impl From<((i32, &'static str), Vec<String>)> for Reply {
+    fn from(reply: ((i32, &'static str), Vec<String>)) -> Self {
+        Reply::build(reply.0, reply.1)
+    }
+}
+
+#[cfg(test)]
+mod reply_test {
+    use crate::app_errors;
+    use crate::server::reply::{Reply, ReplyFormat};
+
+    #[test]
+    fn rfc_reply_has_prefix_padded_numeric_and_target() {
+        let reply = Reply::build(app_errors::RPL_WELCOME, vec!["nico!nico@host".to_string()]);
+        assert_eq!(
+            reply.format(ReplyFormat::Rfc1459, "server_uno", "nico"),
+            ":server_uno 001 nico :Welcome to the Internet Relay Network nico!nico@host"
+        );
+    }
+
+    #[test]
+    fn legacy_reply_has_no_prefix_nor_target() {
+        let reply = Reply::build(app_errors::ERR_NOSUCHCHANNEL, vec!["#rust".to_string()]);
+        assert_eq!(
+            reply.format(ReplyFormat::Legacy, "server_uno", "nico"),
+            "403 #rust :No such channel"
+        );
+    }
+
+    #[test]
+    fn params_are_substituted_in_order() {
+        let reply = Reply::build(
+            app_errors::ERR_USERONCHANNEL,
+            vec!["juan".to_string(), "#rust".to_string()],
+        );
+        assert_eq!(reply.text(), "juan #rust :is already on channel");
+    }
+
+    #[test]
+    fn params_with_braces_are_not_placeholders() {
+        let reply = Reply::build(
+            app_errors::ERR_USERONCHANNEL,
+            vec!["a{}b".to_string(), "#rust".to_string()],
+        );
+        assert_eq!(reply.text(), "a{}b #rust :is already on channel");
+    }
+
+    #[test]
+    fn missing_params_are_replaced_by_asterisk() {
+        let reply = Reply::build(app_errors::ERR_NEEDMOREPARAMS, vec![]);
+        assert_eq!(
+            reply.format(ReplyFormat::Rfc1459, "server_uno", "*"),
+            ":server_uno 461 * * :Not enough parameters"
+        );
+    }
+
+    #[test]
+    fn reply_format_builds_from_config_value() {
+        assert_eq!(
+            ReplyFormat::build("legacy").expect("fail build"),
+            ReplyFormat::Legacy
+        );
+        assert!(ReplyFormat::build("xml").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the seconds since the unix epoch, the time kept by channels, nicks and K-lines
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// Given the seconds since the unix epoch returns the date as "YYYY-MM-DD hh:mm:ss UTC".
+/// Used by the replies that show dates to the user (e.g. RPL_CREATED).
+pub fn format_time(secs: u64) -> String {
+    // civil from days, online reference: howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod time_test {
+    use crate::server::time::format_time;
+
+    #[test]
+    fn time_is_formatted_as_utc_date() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(1700000000), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_time(951782400), "2000-02-29 00:00:00 UTC");
+    }
+}
@@ -0,0 +1,246 @@
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Socket of a connection, in plain text or encrypted with TLS.
+/// It can be cloned like a TcpStream: every handle shares the TLS session, so one thread
+/// can block reading while others write. Reads from the socket don't lock the session.
+pub struct Stream {
+    tcp: TcpStream,
+    tls: Option<Arc<Mutex<rustls::Connection>>>,
+}
+
+impl Stream {
+    /// Given an accepted socket and the TLS config of the listener starts the server side of the handshake,
+    /// it's completed as the data of the client is read
+    pub fn accept(tcp: TcpStream, config: Arc<ServerConfig>) -> io::Result<Stream> {
+        let session = ServerConnection::new(config).map_err(io::Error::other)?;
+        Ok(Stream {
+            tcp,
+            tls: Some(Arc::new(Mutex::new(session.into()))),
+        })
+    }
+
+    /// Given the address, the port and the TLS config connects to a server and sends the first
+    /// message of the handshake. The certificate of the server must be valid for the address.
+    pub fn connect(address: &str, port: &str, config: Arc<ClientConfig>) -> io::Result<Stream> {
+        let name = ServerName::try_from(address.to_string())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let session = ClientConnection::new(config, name).map_err(io::Error::other)?;
+        let mut stream = Stream {
+            tcp: TcpStream::connect(format!("{}:{}", address, port))?,
+            tls: Some(Arc::new(Mutex::new(session.into()))),
+        };
+        stream.flush_tls()?;
+        Ok(stream)
+    }
+
+    /// Returns a new handle to the same socket (and TLS session)
+    pub fn try_clone(&self) -> io::Result<Stream> {
+        Ok(Stream {
+            tcp: self.tcp.try_clone()?,
+            tls: self.tls.clone(),
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.peer_addr()
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp.set_read_timeout(timeout)
+    }
+
+    /// Closes the socket, telling the other side the TLS session ends (if there is one)
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        if let Some(tls) = self.tls.clone() {
+            Self::lock(&tls)?.send_close_notify();
+            let _ = self.flush_tls();
+        }
+        self.tcp.shutdown(Shutdown::Both)
+    }
+
+    /// Closes the socket right away, without waiting to tell the other side
+    pub fn abort(&self) -> io::Result<()> {
+        self.tcp.shutdown(Shutdown::Both)
+    }
+
+    /// Writes to the socket every TLS record waiting to be sent
+    fn flush_tls(&mut self) -> io::Result<()> {
+        if let Some(tls) = self.tls.clone() {
+            let mut session = Self::lock(&tls)?;
+            while session.wants_write() {
+                session.write_tls(&mut self.tcp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(tls: &Mutex<rustls::Connection>) -> io::Result<MutexGuard<'_, rustls::Connection>> {
+        tls.lock()
+            .map_err(|_| io::Error::other("TLS session lock poisoned"))
+    }
+}
+
+impl From<TcpStream> for Stream {
+    /// Given a socket returns it as a plain text stream
+    fn from(tcp: TcpStream) -> Stream {
+        Stream { tcp, tls: None }
+    }
+}
+
+impl Read for Stream {
+    /// Reads the decrypted data. If there is none yet it waits for more records from the socket
+    /// (answering the handshake if needed), the read timeout of the socket applies to that wait.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let tls = match self.tls.clone() {
+            Some(tls) => tls,
+            None => return self.tcp.read(buf),
+        };
+        loop {
+            match Self::lock(&tls)?.reader().read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+            // se lee del socket sin tomar la sesion, asi otros hilos pueden escribir mientras
+            let mut incoming = [0; 4096];
+            let read = self.tcp.read(&mut incoming)?;
+            if read == 0 {
+                return Ok(0);
+            }
+            let mut incoming = &incoming[..read];
+            {
+                let mut session = Self::lock(&tls)?;
+                while !incoming.is_empty() {
+                    session.read_tls(&mut incoming)?;
+                    session
+                        .process_new_packets()
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                }
+            }
+            self.flush_tls()?;
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let tls = match self.tls.clone() {
+            Some(tls) => tls,
+            None => return self.tcp.write(buf),
+        };
+        let written = Self::lock(&tls)?.writer().write(buf)?;
+        self.flush_tls()?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_tls()?;
+        self.tcp.flush()
+    }
+}
+
+/// Given the paths to the certificate chain and the private key (both PEM) returns the config of a TLS listener
+pub fn server_config(cert_path: &str, key_path: &str) -> Result<Arc<ServerConfig>, Box<dyn Error>> {
+    let certs = CertificateDer::pem_file_iter(cert_path)?.collect::<Result<Vec<_>, _>>()?;
+    let key = PrivateKeyDer::from_pem_file(key_path)?;
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+    Ok(Arc::new(config))
+}
+
+/// Given the path to a file with the trusted certificates (PEM) returns the config used to connect to a server.
+/// Without a file the certificates of the well known authorities are trusted.
+pub fn client_config(ca_path: Option<&str>) -> Result<Arc<ClientConfig>, Box<dyn Error>> {
+    let mut roots = RootCertStore::empty();
+    match ca_path {
+        Some(ca_path) => {
+            for cert in CertificateDer::pem_file_iter(ca_path)? {
+                roots.add(cert?)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tls_test {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Writes a self-signed certificate for localhost and its key to the temp dir, returns their paths
+    fn self_signed(name: &str) -> (String, String) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).expect("");
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("{name}_cert.pem"));
+        let key_path = dir.join(format!("{name}_key.pem"));
+        std::fs::write(&cert_path, cert.cert.pem()).expect("");
+        std::fs::write(&key_path, cert.key_pair.serialize_pem()).expect("");
+        (
+            cert_path.to_string_lossy().to_string(),
+            key_path.to_string_lossy().to_string(),
+        )
+    }
+
+    #[test]
+    fn lines_are_exchanged_over_tls() {
+        let (cert_path, key_path) = self_signed("tls_test_exchange");
+        let server_config = server_config(&cert_path, &key_path).expect("");
+        let listener = TcpListener::bind("localhost:8125").expect("");
+        let server = thread::spawn(move || {
+            let tcp = listener.incoming().next().expect("").expect("");
+            let stream = Stream::accept(tcp, server_config).expect("");
+            let mut writer = stream.try_clone().expect("");
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("");
+            writer
+                .write_all(format!("eco {line}").as_bytes())
+                .expect("");
+        });
+
+        let config = client_config(Some(&cert_path)).expect("");
+        let mut client = Stream::connect("localhost", "8125", config).expect("");
+        client.write_all(b"PING :hola\n").expect("");
+        let mut line = String::new();
+        BufReader::new(client.try_clone().expect(""))
+            .read_line(&mut line)
+            .expect("");
+        assert_eq!(line, "eco PING :hola\n");
+        server.join().expect("");
+    }
+
+    #[test]
+    fn untrusted_certificate_is_rejected() {
+        let (cert_path, key_path) = self_signed("tls_test_untrusted");
+        let server_config = server_config(&cert_path, &key_path).expect("");
+        let listener = TcpListener::bind("localhost:8126").expect("");
+        thread::spawn(move || {
+            let tcp = listener.incoming().next().expect("").expect("");
+            let mut stream = Stream::accept(tcp, server_config).expect("");
+            let _ = stream.read(&mut [0; 16]);
+        });
+
+        // confia en las autoridades conocidas, no en el certificado autofirmado
+        let config = client_config(None).expect("");
+        let mut client = Stream::connect("localhost", "8126", config).expect("");
+        client.write_all(b"PING :hola\n").expect("");
+        assert!(client.read(&mut [0; 16]).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::server::irc_name::{CaseMapping, IrcName};
+
+// longitudes maximas por defecto, las de RFC 1459 (sección 1.2 y 1.3)
+pub const DEFAULT_NICK_LEN: usize = 9;
+pub const DEFAULT_CHANNEL_LEN: usize = 200;
+
+// caracteres permitidos en un nick además de letras y digitos
+const NICK_SPECIAL: &str = "-[]\\`^{}";
+// caracteres que no pueden formar parte del nombre de un canal (espacio, coma, ^G, NUL, CR, LF)
+const CHANNEL_FORBIDDEN: &str = " ,\x07\0\r\n";
+
+/// Given a nick and the max length allowed returns whether it follows the RFC 1459 grammar:
+/// a letter followed by letters, digits or special characters ('-', '[', ']', '\', '`', '^', '{', '}')
+pub fn is_valid_nick(nick: &str, max_len: usize) -> bool {
+    let mut chars = nick.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    nick.len() <= max_len && chars.all(|c| c.is_ascii_alphanumeric() || NICK_SPECIAL.contains(c))
+}
+
+/// Given a channel name and the max length allowed returns whether it follows the RFC 1459 grammar:
+/// a '#' or '&' followed by any characters except space, comma, ^G, NUL, CR and LF
+pub fn is_valid_channel(channel: &str, max_len: usize) -> bool {
+    let mut chars = channel.chars();
+    match chars.next() {
+        Some('#') | Some('&') => {}
+        _ => return false,
+    }
+    channel.len() > 1 && channel.len() <= max_len && chars.all(|c| !CHANNEL_FORBIDDEN.contains(c))
+}
+
+/// Given a nick, the list of reserved nicks and the casemapping returns whether the nick is one of them,
+/// the comparison ignores case so 'nickserv' can't be used to impersonate 'NickServ'
+pub fn is_reserved_nick(nick: &str, reserved: &[String], casemapping: CaseMapping) -> bool {
+    let nick = IrcName::new(nick, casemapping);
+    reserved
+        .iter()
+        .any(|reserved_nick| IrcName::new(reserved_nick, casemapping) == nick)
+}
+
+#[cfg(test)]
+mod validation_test {
+    use super::*;
+
+    #[test]
+    fn nick_starting_with_a_letter_is_valid() {
+        assert!(is_valid_nick("nico", DEFAULT_NICK_LEN));
+        assert!(is_valid_nick("a[b]c-d`^", DEFAULT_NICK_LEN));
+    }
+
+    #[test]
+    fn nick_with_invalid_characters_is_not_valid() {
+        assert!(!is_valid_nick("1nico", DEFAULT_NICK_LEN));
+        assert!(!is_valid_nick("-nico", DEFAULT_NICK_LEN));
+        assert!(!is_valid_nick("ni co", DEFAULT_NICK_LEN));
+        assert!(!is_valid_nick("nico!", DEFAULT_NICK_LEN));
+        assert!(!is_valid_nick("", DEFAULT_NICK_LEN));
+    }
+
+    #[test]
+    fn nick_longer_than_the_max_length_is_not_valid() {
+        assert!(is_valid_nick("abcdefghi", 9));
+        assert!(!is_valid_nick("abcdefghij", 9));
+        assert!(is_valid_nick("abcdefghij", 10));
+    }
+
+    #[test]
+    fn channel_starting_with_hash_or_ampersand_is_valid() {
+        assert!(is_valid_channel("#rust", DEFAULT_CHANNEL_LEN));
+        assert!(is_valid_channel("&local", DEFAULT_CHANNEL_LEN));
+        assert!(is_valid_channel("#a.b-c!", DEFAULT_CHANNEL_LEN));
+    }
+
+    #[test]
+    fn channel_with_invalid_characters_is_not_valid() {
+        assert!(!is_valid_channel("rust", DEFAULT_CHANNEL_LEN));
+        assert!(!is_valid_channel("#", DEFAULT_CHANNEL_LEN));
+        assert!(!is_valid_channel("#ru st", DEFAULT_CHANNEL_LEN));
+        assert!(!is_valid_channel("#ru,st", DEFAULT_CHANNEL_LEN));
+        assert!(!is_valid_channel("#ru\x07st", DEFAULT_CHANNEL_LEN));
+    }
+
+    #[test]
+    fn channel_longer_than_the_max_length_is_not_valid() {
+        assert!(is_valid_channel("#abcd", 5));
+        assert!(!is_valid_channel("#abcde", 5));
+    }
+
+    #[test]
+    fn reserved_nicks_are_compared_ignoring_case() {
+        let reserved = vec!["NickServ".to_string(), "Chan[Serv]".to_string()];
+        assert!(is_reserved_nick(
+            "nickserv",
+            &reserved,
+            CaseMapping::Rfc1459
+        ));
+        assert!(is_reserved_nick(
+            "chan{serv}",
+            &reserved,
+            CaseMapping::Rfc1459
+        ));
+        assert!(!is_reserved_nick(
+            "chan{serv}",
+            &reserved,
+            CaseMapping::Ascii
+        ));
+        assert!(!is_reserved_nick("nico", &reserved, CaseMapping::Rfc1459));
+    }
+}
@@ -1,8 +1,175 @@
-use irc_2c_2022::server::ClientsInfo;
+// Cada archivo de tests usa solo algunas de estas funciones
+#![allow(dead_code)]
+use irc_2c_2022::config::Config;
+use irc_2c_2022::server::admin::AdminInfo;
+use irc_2c_2022::server::client_s::ClientS;
+use irc_2c_2022::server::clients_info::DEFAULT_PING_TIMEOUT;
+use irc_2c_2022::server::connection::{Connection, DEFAULT_SENDQ_SIZE};
+use irc_2c_2022::server::flood::FloodLimits;
+use irc_2c_2022::server::irc_name::CaseMapping;
+use irc_2c_2022::server::listener::Listen;
+use irc_2c_2022::server::logger::Logger;
+use irc_2c_2022::server::operator::Operator;
+use irc_2c_2022::server::reply::ReplyFormat;
+use irc_2c_2022::server::validation::{DEFAULT_CHANNEL_LEN, DEFAULT_NICK_LEN};
+use irc_2c_2022::server::{ClientsInfo, Server};
 use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn setup() -> ClientsInfo {
     let mut opers = HashMap::new();
-    opers.insert("juan".to_string(), "botter".to_string());
-    return ClientsInfo::new("tests".to_string(), Some("hola".to_string()), opers);
+    opers.insert(
+        "juan".to_string(),
+        Operator::new("juan".to_string(), "botter".to_string()),
+    );
+    ClientsInfo::new("tests".to_string(), Some("hola".to_string()), opers)
+}
+
+/// Wraps the stream in the connection used by the server to write to it
+pub fn connection(stream: TcpStream) -> Arc<Mutex<Connection>> {
+    Arc::new(Mutex::new(Connection::new(stream).expect("")))
+}
+
+/// Port of the test where each accepted connection is served by a client of the server
+pub struct TestServer {
+    listener: TcpListener,
+    port: String,
+    server: Arc<Mutex<ClientsInfo>>,
+    trusted_servers: HashMap<String, Option<String>>,
+    logger: Arc<Mutex<Logger>>,
+}
+
+impl TestServer {
+    /// Given the port, the server and its trusted servers, binds the port and returns the test server
+    pub fn bind(
+        port: &str,
+        server: Arc<Mutex<ClientsInfo>>,
+        trusted_servers: HashMap<String, Option<String>>,
+    ) -> TestServer {
+        TestServer {
+            listener: TcpListener::bind(format!("localhost:{port}")).expect(""),
+            port: port.to_string(),
+            server,
+            trusted_servers,
+            logger: Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string()))),
+        }
+    }
+
+    /// Connects to the port and runs the client of the server for the connection in its own thread,
+    /// returns the stream of the user side
+    pub fn accept(&self) -> TcpStream {
+        let stream = TcpStream::connect(format!("localhost:{}", self.port)).expect("");
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("");
+        let server_stream = self.listener.incoming().next().expect("").expect("");
+        let mut client = ClientS::new(
+            self.server.clone(),
+            connection(server_stream),
+            self.trusted_servers.clone(),
+            self.logger.clone(),
+        )
+        .expect("");
+        thread::spawn(move || client.run());
+        stream
+    }
+
+    /// Given the lines to send and a text, accepts a new connection, sends the lines and reads until a line
+    /// contains the text, returns the stream and its reader
+    pub fn connect(&self, lines: &[u8], text: &str) -> (TcpStream, BufReader<TcpStream>) {
+        let mut stream = self.accept();
+        stream.write_all(lines).expect("");
+        let mut reader = BufReader::new(stream.try_clone().expect(""));
+        read_until(&mut reader, text);
+        (stream, reader)
+    }
+}
+
+/// Reads lines until one contains the text, returns it
+pub fn read_until(reader: &mut impl BufRead, text: &str) -> String {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).expect("") == 0 {
+            panic!("connection closed before receiving {text:?}");
+        }
+        if line.contains(text) {
+            return line;
+        }
+    }
+}
+
+/// Config of a server with a single listener on the port and the test files
+pub fn config(name: &str, password: &str, port: &str) -> Config {
+    Config {
+        name: name.to_string(),
+        password: Some(password.to_string()),
+        listeners: vec![Listen::new("localhost".to_string(), port.to_string())],
+        log_path: "logs/log1.txt".to_string(),
+        operators_path: "tests/test_files/valid_operators".to_string(),
+        trusted_servers_path: "tests/test_files/valid_trusted_servers".to_string(),
+        parent_name: None,
+        parent_ip: None,
+        parent_port: None,
+        parent_pwd: None,
+        reply_format: ReplyFormat::Rfc1459,
+        motd_path: None,
+        ping_timeout: DEFAULT_PING_TIMEOUT,
+        sendq_size: DEFAULT_SENDQ_SIZE,
+        nick_len: DEFAULT_NICK_LEN,
+        channel_len: DEFAULT_CHANNEL_LEN,
+        reserved_nicks: vec![],
+        casemapping: CaseMapping::Rfc1459,
+        klines_path: None,
+        flood_limits: FloodLimits::default(),
+        tls_cert_path: None,
+        tls_key_path: None,
+        parent_tls: false,
+        tls_ca_path: None,
+        connect_blocks: vec![],
+        admin_info: AdminInfo::default(),
+    }
+}
+
+/// Runs a server with the config in its own thread and waits for it to start listening
+pub fn start(config: Config) {
+    let mut server = Server::build(config).expect("");
+    thread::spawn(move || server.run().is_ok());
+    thread::sleep(Duration::from_millis(200));
+}
+
+/// Waits up to the timeout for a connection to the listener
+pub fn accept_within(listener: &TcpListener, timeout: Duration) -> TcpStream {
+    listener.set_nonblocking(true).expect("");
+    let start = Instant::now();
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).expect("");
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .expect("");
+                return stream;
+            }
+            Err(_) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(20)),
+            Err(err) => panic!("no connection in {timeout:?}: {err}"),
+        }
+    }
+}
+
+/// Connects a user to the port and registers it
+pub fn register(port: &str, password: &str, nick: &str) -> (TcpStream, BufReader<TcpStream>) {
+    let mut user = TcpStream::connect(format!("localhost:{port}")).expect("");
+    user.set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("");
+    let mut reader = BufReader::new(user.try_clone().expect(""));
+    user.write_all(
+        format!("PASS {password}\nNICK {nick}\nUSER {nick} {nick} {nick} :{nick}\n").as_bytes(),
+    )
+    .expect("");
+    read_until(&mut reader, " 001 ");
+    (user, reader)
 }
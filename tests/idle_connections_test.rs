@@ -0,0 +1,63 @@
+// Benchmark: many connected clients that don't send anything must not keep the server busy.
+// The cpu time is read from /proc, so it only runs on linux.
+#![cfg(target_os = "linux")]
+
+mod common;
+use std::{
+    collections::HashMap,
+    fs,
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use irc_2c_2022::server::{client_s::ClientS, logger::Logger};
+
+#[test]
+fn test_idle_clients_barely_use_cpu() {
+    const CLIENTS: usize = 300;
+    let server = Arc::new(Mutex::new(common::setup()));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+
+    let listener = TcpListener::bind("localhost:8110").expect("");
+    let mut users = Vec::new();
+    for _ in 0..CLIENTS {
+        users.push(TcpStream::connect("localhost:8110").expect(""));
+        let stream = listener.incoming().next().expect("").expect("");
+        let mut client = ClientS::new(
+            server.clone(),
+            common::connection(stream),
+            HashMap::new(),
+            logger.clone(),
+        )
+        .expect("");
+        thread::spawn(move || client.run());
+    }
+    // se espera a que todos los hilos queden bloqueados leyendo
+    thread::sleep(Duration::from_millis(300));
+
+    let cpu_before = cpu_time();
+    let start = Instant::now();
+    thread::sleep(Duration::from_secs(1));
+    let cpu_used = cpu_time() - cpu_before;
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} idle clients used {:?} of cpu in {:?}",
+        CLIENTS, cpu_used, elapsed
+    );
+    assert!(cpu_used < elapsed / 10);
+}
+
+/// Returns the cpu time (user + system) used by this process so far
+fn cpu_time() -> Duration {
+    let stat = fs::read_to_string("/proc/self/stat").expect("");
+    // el nombre del proceso va entre parentesis, despues de el empieza el campo 3 (state)
+    let (_, fields) = stat.rsplit_once(')').expect("");
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let utime: u64 = fields[11].parse().expect("");
+    let stime: u64 = fields[12].parse().expect("");
+    // los tiempos de /proc estan en ticks de USER_HZ (100 por segundo)
+    Duration::from_millis((utime + stime) * 10)
+}
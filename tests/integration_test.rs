@@ -1,12 +1,23 @@
 mod common;
+use common::{read_until, TestServer};
 use std::{
     collections::HashMap,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Write},
     net::{TcpListener, TcpStream},
     sync::Arc,
+    thread,
+    time::Duration,
 };
 
-use irc_2c_2022::server::{client_s::ClientS, logger::Logger};
+use irc_2c_2022::server::{
+    client_s::{message::command::Mode, ClientS},
+    flood::FloodLimits,
+    klines::Klines,
+    listener::{Listen, Listener},
+    logger::Logger,
+    operator::{Operator, Privilege},
+    password, ClientsInfo,
+};
 use std::sync::Mutex;
 
 #[test]
@@ -16,52 +27,32 @@ fn test_add_user_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8087").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8087").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8087").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream,
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream, 0, None)
         .expect("");
 
-    assert!(server
-        .lock()
-        .expect("")
-        .contains_client(&"nico".to_string()));
+    assert!(server.lock().expect("").contains_client("nico"));
 }
 
 #[test]
 fn test_add_user_returns_error_with_incorrect_password() {
     let server = Arc::new(Mutex::new(common::setup()));
 
-    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
-
-    let _listener = TcpListener::bind("localhost:8086").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8086").expect("")));
-
-    let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
-
-    server
-        .lock()
-        .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream,
-            Some("incorecta".to_string()),
-            0,
-            None,
-        )
-        .expect_err("");
+    assert!(!ClientsInfo::password_matches(
+        &server,
+        &Some("incorecta".to_string())
+    ));
+    assert!(!ClientsInfo::password_matches(&server, &None));
+    assert!(ClientsInfo::password_matches(
+        &server,
+        &Some("hola".to_string())
+    ));
 }
 
 #[test]
@@ -71,33 +62,19 @@ fn test_add_user_returns_error_with_nick_collision() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8088").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8088").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8088").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client.clone(),
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client.clone(), stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream,
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream, 0, None)
         .expect_err("");
 }
 
@@ -108,21 +85,14 @@ fn test_privmsg_to_nonexistant_user_returns_error() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8097").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8097").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8097").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream,
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream, 0, None)
         .expect("");
 
     server
@@ -144,21 +114,14 @@ fn test_quit_user_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8089").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8089").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8089").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream,
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream, 0, None)
         .expect("");
     server
         .lock()
@@ -166,10 +129,7 @@ fn test_quit_user_functions_correctly() {
         .quit_client("nico".to_string(), None, None)
         .expect("");
 
-    assert!(!server
-        .lock()
-        .expect("")
-        .contains_client(&"nico".to_string()))
+    assert!(!server.lock().expect("").contains_client("nico"))
 }
 
 #[test]
@@ -179,21 +139,14 @@ fn test_create_channel_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8090").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8090").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8090").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -206,10 +159,7 @@ fn test_create_channel_functions_correctly() {
             None,
         )
         .expect("");
-    assert!(server
-        .lock()
-        .expect("")
-        .contains_channel(&"#channel".to_string()))
+    assert!(server.lock().expect("").contains_channel("#channel"))
 }
 
 #[test]
@@ -219,21 +169,14 @@ fn test_delete_channels_function_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8091").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8091").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8091").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -256,10 +199,7 @@ fn test_delete_channels_function_correctly() {
             None,
         )
         .expect("");
-    assert!(!server
-        .lock()
-        .expect("")
-        .contains_channel(&"#channel".to_string()))
+    assert!(!server.lock().expect("").contains_channel("#channel"))
 }
 
 #[test]
@@ -269,7 +209,7 @@ fn test_delete_channels_work_with_multiple_clients() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8092").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8092").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8092").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -283,26 +223,12 @@ fn test_delete_channels_work_with_multiple_clients() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -336,10 +262,7 @@ fn test_delete_channels_work_with_multiple_clients() {
             None,
         )
         .expect("");
-    assert!(server
-        .lock()
-        .expect("")
-        .contains_channel(&"#channel".to_string()));
+    assert!(server.lock().expect("").contains_channel("#channel"));
     server
         .lock()
         .expect("")
@@ -350,10 +273,7 @@ fn test_delete_channels_work_with_multiple_clients() {
             None,
         )
         .expect("");
-    assert!(!server
-        .lock()
-        .expect("")
-        .contains_channel(&"#channel".to_string()));
+    assert!(!server.lock().expect("").contains_channel("#channel"));
 }
 
 #[test]
@@ -363,7 +283,7 @@ fn test_correct_oper_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8093").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8093").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8093").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -376,23 +296,11 @@ fn test_correct_oper_functions_correctly() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
-    match server
-        .lock()
-        .expect("")
-        .oper_login(&"juan".to_string(), &"botter".to_string())
-    {
-        Ok(_) => return,
-        Err(_) => panic!("Error login operator"),
-    };
+    if ClientsInfo::oper_login(&server, "nico", "juan", "botter", "nico", "127.0.0.1").is_err() {
+        panic!("Error login operator");
+    }
 }
 
 #[test]
@@ -402,7 +310,7 @@ fn test_incorrect_oper_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8094").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8094").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8094").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -415,23 +323,11 @@ fn test_incorrect_oper_functions_correctly() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
-    match server
-        .lock()
-        .expect("")
-        .oper_login(&"nico".to_string(), &"amigo".to_string())
-    {
-        Ok(_) => panic!("Operator should be invalid"),
-        Err(_) => return,
-    };
+    if ClientsInfo::oper_login(&server, "nico", "nico", "amigo", "nico", "127.0.0.1").is_ok() {
+        panic!("Operator should be invalid");
+    }
 }
 
 #[test]
@@ -441,7 +337,7 @@ fn test_private_message_functions_beetween_users() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8095").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8095").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8095").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -455,28 +351,14 @@ fn test_private_message_functions_beetween_users() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
-    let client_stream = listener.incoming().nth(0).expect("").expect("");
+    let client_stream = listener.incoming().next().expect("").expect("");
     server
         .lock()
         .expect("")
@@ -500,7 +382,7 @@ fn test_private_message_functions_beetween_users_in_channel() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8096").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8096").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8096").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -514,26 +396,12 @@ fn test_private_message_functions_beetween_users_in_channel() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -557,7 +425,7 @@ fn test_private_message_functions_beetween_users_in_channel() {
             None,
         )
         .expect("");
-    let client_stream = listener.incoming().nth(0).expect("").expect("");
+    let client_stream = listener.incoming().next().expect("").expect("");
     server
         .lock()
         .expect("")
@@ -581,21 +449,14 @@ fn test_names_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8089").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8089").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8089").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client.clone(),
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client.clone(), stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -613,12 +474,12 @@ fn test_names_functions_correctly() {
         .expect("")
         .names(vec!["#channel".to_string()], "nico".to_string());
 
-    let client_stream = listener.incoming().nth(0).expect("").expect("");
+    let client_stream = listener.incoming().next().expect("").expect("");
 
     let mut buf = String::new();
     let mut reader = BufReader::new(client_stream);
     reader.read_line(&mut buf).expect("");
-    assert_eq!(buf, "#channel: nico;\n");
+    assert_eq!(buf, "#channel: @nico;\n");
 }
 
 #[test]
@@ -628,21 +489,14 @@ fn test_list_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8098").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8098").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8098").expect(""));
 
     let client = ClientS::new(server.clone(), stream.clone(), HashMap::new(), logger).expect("");
 
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client.clone(),
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client.clone(), stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -669,7 +523,7 @@ fn test_list_functions_correctly() {
         .expect("")
         .list(vec!["#channel".to_string()], "nico".to_string());
 
-    let client_stream = listener.incoming().nth(0).expect("").expect("");
+    let client_stream = listener.incoming().next().expect("").expect("");
 
     let mut buf = String::new();
     let mut reader = BufReader::new(client_stream);
@@ -684,7 +538,7 @@ fn test_kick_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8099").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8099").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8099").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -698,26 +552,12 @@ fn test_kick_functions_correctly() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -758,14 +598,14 @@ fn test_kick_functions_correctly() {
         .expect("")
         .names(vec!["#channel".to_string()], "nico".to_string());
 
-    let client_stream = listener.incoming().nth(0).expect("").expect("");
+    let client_stream = listener.incoming().next().expect("").expect("");
 
     let mut buf = String::new();
     let mut reader = BufReader::new(client_stream);
     reader.read_line(&mut buf).expect("");
     buf.clear();
     reader.read_line(&mut buf).expect("");
-    assert_eq!(buf, "#channel: nico;\n");
+    assert_eq!(buf, "#channel: @nico;\n");
 }
 
 #[test]
@@ -775,7 +615,7 @@ fn test_invite_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8100").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8100").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8100").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -789,26 +629,12 @@ fn test_invite_functions_correctly() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -841,7 +667,7 @@ fn test_invite_fails_without_oper_status() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8101").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8101").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8101").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -862,38 +688,17 @@ fn test_invite_fails_without_oper_status() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "pedro".to_string(),
-            client3,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("pedro".to_string(), client3, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -937,7 +742,7 @@ fn test_invite_fails_if_nick_doesnt_exist() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8102").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8102").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8102").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -957,26 +762,12 @@ fn test_invite_fails_if_nick_doesnt_exist() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
-        .add_client(
-            "juan".to_string(),
-            client2,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("juan".to_string(), client2, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -1020,7 +811,7 @@ fn test_topic_functions_correctly() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8103").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8103").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8103").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -1033,14 +824,7 @@ fn test_topic_functions_correctly() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -1071,7 +855,7 @@ fn test_topic_fails_if_not_channel() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let _listener = TcpListener::bind("localhost:8104").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8104").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8104").expect(""));
 
     let client = ClientS::new(
         server.clone(),
@@ -1084,14 +868,7 @@ fn test_topic_fails_if_not_channel() {
     server
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
@@ -1111,7 +888,7 @@ fn test_who_works_with_one_client() {
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
     let listener = TcpListener::bind("localhost:8105").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8105").expect("")));
+    let stream = common::connection(TcpStream::connect("localhost:8105").expect(""));
 
     let mut client = ClientS::new(
         server.clone(),
@@ -1127,21 +904,14 @@ fn test_who_works_with_one_client() {
     server.clone()
         .lock()
         .expect("")
-        .add_client(
-            "nico".to_string(),
-            client,
-            stream.clone(),
-            Some("hola".to_string()),
-            0,
-            None,
-        )
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
         .expect("");
     server
         .lock()
         .expect("")
         .who("*".to_string(), "nico".to_string()).expect("");
 
-        let client_stream = listener.incoming().nth(0).expect("").expect("");
+        let client_stream = listener.incoming().next().expect("").expect("");
 
         let mut buf = String::new();
         let mut reader = BufReader::new(client_stream);
@@ -1156,8 +926,8 @@ fn test_whois_functions_correctly() {
 
     let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
 
-    let listener = TcpListener::bind("localhost:8107").expect("");
-    let stream = Arc::new(Mutex::new(TcpStream::connect("localhost:8107").expect("")));
+    let _listener = TcpListener::bind("localhost:8107").expect("");
+    let stream = common::connection(TcpStream::connect("localhost:8107").expect(""));
 
     let mut client = ClientS::new(
         server.clone(),
@@ -1173,25 +943,1234 @@ fn test_whois_functions_correctly() {
     server.clone()
         .lock()
         .expect("")
-        .add_client(
+        .register_client("nico".to_string(), client, stream.clone(), 0, None)
+        .expect("");
+    let replies = server.lock().expect("").whois_replies("nico");
+    let numerics: Vec<i32> = replies.iter().map(|((number, _), _)| *number).collect();
+    assert_eq!(numerics, vec![311, 312, 318]);
+    assert_eq!(replies[0].1[0..2], ["nico", "juan"]);
+    assert_eq!(replies[0].1[3], "juan botter");
+}
+
+#[test]
+fn test_registration_burst_includes_motd() {
+    let mut server = common::setup();
+    server.set_motd_path(Some("tests/test_files/valid_motd".to_string()));
+
+    let burst = server.registration_burst("nico!juan@localhost".to_string());
+    let numerics: Vec<i32> = burst.iter().map(|((number, _), _)| *number).collect();
+
+    assert_eq!(numerics, vec![1, 2, 3, 4, 5, 375, 372, 372, 376]);
+    assert_eq!(burst[0].1, vec!["nico!juan@localhost".to_string()]);
+    assert_eq!(
+        burst[6].1,
+        vec!["Bienvenidos al servidor de pruebas".to_string()]
+    );
+}
+
+#[test]
+fn test_registration_burst_without_motd_returns_nomotd() {
+    let mut server = common::setup();
+    server.set_motd_path(Some("tests/test_files/missing_motd".to_string()));
+
+    let burst = server.registration_burst("nico!juan@localhost".to_string());
+    let ((number, _), _) = burst.last().expect("");
+
+    assert_eq!(*number, 422);
+}
+
+#[test]
+fn test_ping_is_answered_with_pong() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8108", server, HashMap::new());
+    let mut user_stream = test_server.accept();
+
+    user_stream.write_all(b"PING :nico\n").expect("");
+
+    let mut buf = String::new();
+    let mut reader = BufReader::new(user_stream);
+    reader.read_line(&mut buf).expect("");
+    assert_eq!(buf, ":tests PONG tests :nico\n");
+}
+
+#[test]
+fn test_idle_client_is_pinged_and_dropped_after_timeout() {
+    let mut clients = common::setup();
+    clients.set_ping_timeout(Duration::from_secs(1));
+    let server = Arc::new(Mutex::new(clients));
+
+    let test_server = TestServer::bind("8109", server.clone(), HashMap::new());
+    let mut user_stream = test_server.accept();
+
+    user_stream
+        .write_all(b"PASS hola\nNICK nico\nUSER juan juan botter\n")
+        .expect("");
+
+    // se saltea la rafaga de registro hasta que llegue el PING
+    let mut buf = String::new();
+    let mut reader = BufReader::new(user_stream);
+    while !buf.starts_with("PING") {
+        buf.clear();
+        reader.read_line(&mut buf).expect("");
+    }
+    assert_eq!(buf, "PING :tests\n");
+    assert!(server.lock().expect("").contains_client("nico"));
+
+    // sin PONG el servidor corta la conexion y libera el nick
+    buf.clear();
+    reader.read_line(&mut buf).expect("");
+    assert_eq!(buf, "ERROR :Closing Link: Ping timeout\n");
+    assert!(!server.lock().expect("").contains_client("nico"));
+}
+
+#[test]
+fn test_line_longer_than_the_limit_drops_the_connection() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8142", server.clone(), HashMap::new());
+    let mut user_stream = test_server.accept();
+
+    user_stream
+        .write_all(b"PASS hola\nNICK nico\nUSER juan juan botter\n")
+        .expect("");
+    let mut buf = String::new();
+    let mut reader = BufReader::new(user_stream.try_clone().expect(""));
+    while !buf.contains(" 422 ") {
+        buf.clear();
+        reader.read_line(&mut buf).expect("");
+    }
+
+    // una linea sin fin que supera los 512 bytes no se sigue acumulando
+    user_stream.write_all(&[b'a'; 600]).expect("");
+    buf.clear();
+    reader.read_line(&mut buf).expect("");
+    assert_eq!(buf, "ERROR :Closing Link: Line too long\n");
+    assert!(!server.lock().expect("").contains_client("nico"));
+}
+
+#[test]
+fn test_banned_user_can_only_join_with_an_exception() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+
+    let _listener = TcpListener::bind("localhost:8114").expect("");
+    for nick in ["juan", "nico"] {
+        let stream = common::connection(TcpStream::connect("localhost:8114").expect(""));
+        let mut client = ClientS::new(
+            server.clone(),
+            stream.clone(),
+            HashMap::new(),
+            logger.clone(),
+        )
+        .expect("");
+        client.user = Some(nick.to_string());
+        client.realname = Some(nick.to_string());
+        server
+            .lock()
+            .expect("")
+            .register_client(nick.to_string(), client, stream, 0, None)
+            .expect("");
+    }
+    let mut server = server.lock().expect("");
+    server
+        .join_channel("juan".to_string(), None, "#rust".to_string(), None, None)
+        .expect("");
+    server
+        .mode_mask(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('b'),
+            "nico".to_string(),
+            None,
+            None,
+        )
+        .expect("");
+
+    let ((number, _), _) = server
+        .join_channel("nico".to_string(), None, "#rust".to_string(), None, None)
+        .expect_err("");
+    assert_eq!(number, 474);
+    let bans = server.mask_list("#rust".to_string(), 'b').expect("");
+    assert_eq!(bans[0].1, vec!["#rust".to_string(), "nico!*@*".to_string()]);
+
+    server
+        .mode_mask(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('e'),
+            "*!nico@127.0.0.1".to_string(),
+            None,
+            None,
+        )
+        .expect("");
+    server
+        .join_channel("nico".to_string(), None, "#rust".to_string(), None, None)
+        .expect("");
+}
+
+#[test]
+fn test_only_voiced_users_can_talk_on_moderated_channels() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+
+    let listener = TcpListener::bind("localhost:8115").expect("");
+    for nick in ["juan", "nico"] {
+        let stream = common::connection(TcpStream::connect("localhost:8115").expect(""));
+        let mut client = ClientS::new(
+            server.clone(),
+            stream.clone(),
+            HashMap::new(),
+            logger.clone(),
+        )
+        .expect("");
+        client.user = Some(nick.to_string());
+        client.realname = Some(nick.to_string());
+        let mut server = server.lock().expect("");
+        server
+            .register_client(nick.to_string(), client, stream.clone(), 0, None)
+            .expect("");
+        server
+            .join_channel(
+                nick.to_string(),
+                Some(stream),
+                "#rust".to_string(),
+                None,
+                None,
+            )
+            .expect("");
+    }
+    let _juan_stream = listener.incoming().next().expect("").expect("");
+    let nico_stream = listener.incoming().next().expect("").expect("");
+
+    let mut server = server.lock().expect("");
+    server
+        .mode_moderated(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('m'),
+            None,
+            None,
+        )
+        .expect("");
+    let ((number, _), _) = server
+        .send_privmsg(
             "nico".to_string(),
-            client,
+            "#rust".to_string(),
+            ":hola".to_string(),
+            None,
+        )
+        .expect_err("");
+    assert_eq!(number, 404);
+
+    server
+        .mode_voice(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('v'),
+            "nico".to_string(),
+            None,
+            None,
+        )
+        .expect("");
+    server
+        .send_privmsg(
+            "nico".to_string(),
+            "#rust".to_string(),
+            ":hola".to_string(),
+            None,
+        )
+        .expect("");
+
+    server.names(vec!["#rust".to_string()], "nico".to_string());
+    let mut buf = String::new();
+    let mut reader = BufReader::new(nico_stream);
+    reader.read_line(&mut buf).expect("");
+    let names = buf
+        .trim_end()
+        .trim_start_matches("#rust: ")
+        .trim_end_matches(';');
+    let mut names: Vec<&str> = names.split(' ').collect();
+    names.sort();
+    assert_eq!(names, vec!["+nico", "@juan"]);
+}
+
+#[test]
+fn test_topic_lock_and_no_external_messages() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+
+    let _listener = TcpListener::bind("localhost:8116").expect("");
+    for nick in ["juan", "nico", "pedro"] {
+        let stream = common::connection(TcpStream::connect("localhost:8116").expect(""));
+        let mut client = ClientS::new(
+            server.clone(),
             stream.clone(),
-            Some("hola".to_string()),
-            0,
+            HashMap::new(),
+            logger.clone(),
+        )
+        .expect("");
+        client.user = Some(nick.to_string());
+        client.realname = Some(nick.to_string());
+        let mut server = server.lock().expect("");
+        server
+            .register_client(nick.to_string(), client, stream.clone(), 0, None)
+            .expect("");
+        if nick != "pedro" {
+            server
+                .join_channel(
+                    nick.to_string(),
+                    Some(stream),
+                    "#rust".to_string(),
+                    None,
+                    None,
+                )
+                .expect("");
+        }
+    }
+    let mut server = server.lock().expect("");
+
+    server
+        .mode_topic_lock(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('t'),
+            None,
+            None,
+        )
+        .expect("");
+    let ((number, _), _) = server
+        .topic(
+            "#rust".to_string(),
+            Some("off topic".to_string()),
+            "nico".to_string(),
+        )
+        .expect_err("");
+    assert_eq!(number, 482);
+    server
+        .topic(
+            "#rust".to_string(),
+            Some("rust".to_string()),
+            "juan".to_string(),
+        )
+        .expect("");
+    let replies = server
+        .topic("#rust".to_string(), None, "nico".to_string())
+        .expect("");
+    assert_eq!(replies[0].1, vec!["#rust".to_string(), "rust".to_string()]);
+    assert_eq!((replies[1].0).0, 333);
+    assert_eq!(replies[1].1[1], "juan");
+
+    // sin +n cualquiera puede mandar mensajes al canal
+    server
+        .send_privmsg(
+            "pedro".to_string(),
+            "#rust".to_string(),
+            ":hola".to_string(),
             None,
         )
         .expect("");
     server
+        .mode_no_external(
+            Some("juan".to_string()),
+            "#rust".to_string(),
+            Mode::Activate('n'),
+            None,
+            None,
+        )
+        .expect("");
+    let ((number, _), _) = server
+        .send_privmsg(
+            "pedro".to_string(),
+            "#rust".to_string(),
+            ":hola".to_string(),
+            None,
+        )
+        .expect_err("");
+    assert_eq!(number, 404);
+}
+
+#[test]
+fn test_combined_modes_are_applied_and_listed() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8117", server.clone(), HashMap::new());
+    let mut user_stream = test_server.accept();
+
+    user_stream
+        .write_all(b"PASS hola\nNICK nico\nUSER juan juan botter\nJOIN #rust\n")
+        .expect("");
+    user_stream
+        .write_all(b"MODE #rust +ntk-t secret\nMODE #rust +k otra\nMODE #rust\n")
+        .expect("");
+
+    let mut buf = String::new();
+    let mut reader = BufReader::new(user_stream);
+    while !buf.contains(" 467 ") {
+        buf.clear();
+        reader.read_line(&mut buf).expect("");
+    }
+    assert_eq!(buf, ":tests 467 nico #rust :Channel key already set\n");
+    buf.clear();
+    reader.read_line(&mut buf).expect("");
+    assert_eq!(buf, ":tests 324 nico #rust +nk secret\n");
+
+    let ((number, _), _) = server
         .lock()
         .expect("")
-        .whois("nico".to_string(), "nico".to_string()).expect("");
+        .channel_modes("#canal".to_string(), "nico")
+        .expect_err("");
+    assert_eq!(number, 403);
+}
+
+#[test]
+fn test_user_modes_hide_invisible_users_and_receive_wallops() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8118", server.clone(), HashMap::new());
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for _ in 0..2 {
+        let user_stream = test_server.accept();
+        writers.push(user_stream.try_clone().expect(""));
+        readers.push(BufReader::new(user_stream));
+    }
+
+    writers[1]
+        .write_all(b"PASS hola\nNICK nico\nUSER nico nico\nMODE nico +iw\nMODE nico\n")
+        .expect("");
+    let buf = read_until(&mut readers[1], " 221 ");
+    assert_eq!(buf, ":tests 221 nico +iw\n");
+
+    writers[0]
+        .write_all(b"PASS hola\nNICK juan\nUSER juan juan botter\nOPER juan botter\nWHO nico\n")
+        .expect("");
+    read_until(&mut readers[0], " 381 ");
+    // nico es invisible y no comparte canales con juan
+    let mut buf = String::new();
+    readers[0].read_line(&mut buf).expect("");
+    assert_eq!(buf, "\n");
+
+    writers[0]
+        .write_all(b"MODE nico -i\nWALLOPS :mantenimiento\n")
+        .expect("");
+    let buf = read_until(&mut readers[0], " 502 ");
+    assert_eq!(buf, ":tests 502 juan :Cant change mode for other users\n");
+    let buf = read_until(&mut readers[1], "WALLOPS");
+    assert_eq!(buf, ":juan WALLOPS :mantenimiento\n");
+}
+
+#[test]
+fn test_registered_user_changes_nick_keeping_channels() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8119", server.clone(), HashMap::new());
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for _ in 0..2 {
+        let user_stream = test_server.accept();
+        writers.push(user_stream.try_clone().expect(""));
+        readers.push(BufReader::new(user_stream));
+    }
+
+    writers[0]
+        .write_all(b"PASS hola\nNICK juan\nUSER juan juan\nJOIN #rust\n")
+        .expect("");
+    read_until(&mut readers[0], " 331 ");
+    writers[1]
+        .write_all(b"PASS hola\nNICK nico\nUSER nico nico\nJOIN #rust\n")
+        .expect("");
+    read_until(&mut readers[1], " 331 ");
+    writers[0]
+        .write_all(b"JOIN #privado\nMODE #privado +i\nINVITE nico #privado\n")
+        .expect("");
+    read_until(&mut readers[0], " 341 ");
+
+    writers[1].write_all(b"NICK nicolas\n").expect("");
+    let buf = read_until(&mut readers[0], "NICK");
+    assert_eq!(buf, ":nico NICK nicolas\n");
+    let buf = read_until(&mut readers[1], "NICK");
+    assert_eq!(buf, ":nico NICK nicolas\n");
+    // la invitacion pendiente sigue valiendo con el nick nuevo
+    writers[1].write_all(b"JOIN #privado\n").expect("");
+    read_until(&mut readers[1], " 331 nicolas #privado ");
+
+    writers[0]
+        .write_all(b"NICK nicolas\nNICK 9juan\nNAMES #rust\n")
+        .expect("");
+    let buf = read_until(&mut readers[0], " 433 ");
+    assert_eq!(buf, ":tests 433 juan nicolas :Nickname is already in use\n");
+    let buf = read_until(&mut readers[0], " 432 ");
+    assert_eq!(buf, ":tests 432 juan 9juan :Erroneus nickname\n");
+    let mut buf = String::new();
+    readers[0].read_line(&mut buf).expect("");
+    assert!(buf == "#rust: @juan nicolas;\n" || buf == "#rust: nicolas @juan;\n");
+    assert!(!server.lock().expect("").contains_client("nico"));
+}
 
-        let client_stream = listener.incoming().nth(0).expect("").expect("");
+#[test]
+fn test_invalid_and_reserved_names_are_rejected() {
+    let mut clients_info = common::setup();
+    clients_info.set_name_limits(5, 6, vec!["NickServ".to_string()]);
+    let server = Arc::new(Mutex::new(clients_info));
+
+    let test_server = TestServer::bind("8120", server.clone(), HashMap::new());
+    let mut user_stream = test_server.accept();
+    let mut reader = BufReader::new(user_stream.try_clone().expect(""));
+
+    user_stream
+        .write_all(b"PASS hola\nNICK nickserv\nNICK nicolas\nNICK nico\nUSER nico nico\n")
+        .expect("");
+    let buf = read_until(&mut reader, " 432 ");
+    assert_eq!(buf, ":tests 432 * nickserv :Erroneus nickname\n");
+    let buf = read_until(&mut reader, " 432 ");
+    assert_eq!(buf, ":tests 432 * nicolas :Erroneus nickname\n");
+    let buf = read_until(&mut reader, " 005 ");
+    assert!(buf.contains(" NICKLEN=5 CHANNELLEN=6 "));
+
+    user_stream
+        .write_all(b"JOIN rust\nJOIN #ru\x07st\nJOIN #rustacean\nJOIN #rust\n")
+        .expect("");
+    let buf = read_until(&mut reader, " 403 ");
+    assert_eq!(buf, ":tests 403 nico rust :No such channel\n");
+    let buf = read_until(&mut reader, " 403 ");
+    assert_eq!(buf, ":tests 403 nico #ru\x07st :No such channel\n");
+    let buf = read_until(&mut reader, " 403 ");
+    assert_eq!(buf, ":tests 403 nico #rustacean :No such channel\n");
+    read_until(&mut reader, " 331 ");
+    assert!(server.lock().expect("").contains_client("nico"));
+}
+
+#[test]
+fn test_nicks_and_channels_ignore_case() {
+    let server = Arc::new(Mutex::new(common::setup()));
 
+    let test_server = TestServer::bind("8121", server.clone(), HashMap::new());
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for _ in 0..2 {
+        let user_stream = test_server.accept();
+        writers.push(user_stream.try_clone().expect(""));
+        readers.push(BufReader::new(user_stream));
+    }
+
+    writers[0]
+        .write_all(b"PASS hola\nNICK Pepe[1]\nUSER pepe pepe\nJOIN #Rust\n")
+        .expect("");
+    read_until(&mut readers[0], " 331 ");
+    writers[1]
+        .write_all(b"PASS hola\nNICK pepe{1}\nNICK juan\nUSER juan juan\nJOIN #RUST\n")
+        .expect("");
+    let buf = read_until(&mut readers[1], " 433 ");
+    assert_eq!(buf, ":tests 433 * pepe{1} :Nickname is already in use\n");
+    read_until(&mut readers[1], " 331 ");
+
+    writers[1]
+        .write_all(b"NAMES #rust\nPRIVMSG PEPE[1] :hola\n")
+        .expect("");
+    let mut buf = String::new();
+    readers[1].read_line(&mut buf).expect("");
+    assert!(buf == "#rust: @Pepe[1] juan;\n" || buf == "#rust: juan @Pepe[1];\n");
+    let buf = read_until(&mut readers[0], "PRIVMSG");
+    assert_eq!(buf, ":juan PRIVMSG PEPE[1] :hola\n");
+
+    writers[0].write_all(b"KICK #rUST JUAN\n").expect("");
+    let buf = read_until(&mut readers[1], "KICK");
+    assert_eq!(buf, ":Pepe[1] KICK #rUST JUAN \n");
+    assert!(server.lock().expect("").contains_channel("#rust"));
+    assert!(server.lock().expect("").contains_client("pEPE{1}"));
+}
+
+#[test]
+fn test_operator_kills_user() {
+    let server = Arc::new(Mutex::new(common::setup()));
+
+    let test_server = TestServer::bind("8122", server.clone(), HashMap::new());
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for _ in 0..3 {
+        let user_stream = test_server.accept();
+        writers.push(user_stream.try_clone().expect(""));
+        readers.push(BufReader::new(user_stream));
+    }
+
+    writers[0]
+        .write_all(b"PASS hola\nNICK juan\nUSER juan juan\nOPER juan botter\n")
+        .expect("");
+    read_until(&mut readers[0], " 381 ");
+    writers[1]
+        .write_all(b"PASS hola\nNICK nico\nUSER nico nico\nJOIN #rust\n")
+        .expect("");
+    read_until(&mut readers[1], " 331 ");
+    writers[2]
+        .write_all(b"PASS hola\nNICK pepe\nUSER pepe pepe\nJOIN #rust\nKILL nico :no\n")
+        .expect("");
+    let buf = read_until(&mut readers[2], " 481 ");
+    assert_eq!(
+        buf,
+        ":tests 481 pepe :Permission Denied- You're not an IRC operator\n"
+    );
+
+    writers[0]
+        .write_all(b"KILL tests :no\nKILL nadie :no\nKILL NICO :flood de mensajes\n")
+        .expect("");
+    let buf = read_until(&mut readers[0], " 483 ");
+    assert_eq!(buf, ":tests 483 juan :You cant kill a server!\n");
+    let buf = read_until(&mut readers[0], " 401 ");
+    assert_eq!(buf, ":tests 401 juan nadie :No such nick/channel\n");
+
+    let buf = read_until(&mut readers[1], "ERROR");
+    assert_eq!(
+        buf,
+        "ERROR :Closing Link: nico (Killed (juan (flood de mensajes)))\n"
+    );
+    let mut buf = String::new();
+    assert_eq!(readers[1].read_line(&mut buf).expect(""), 0);
+    let buf = read_until(&mut readers[2], "QUIT");
+    assert_eq!(buf, ":nico QUIT :Killed (juan (flood de mensajes))\n");
+    assert!(!server.lock().expect("").contains_client("nico"));
+}
+
+#[test]
+fn test_operator_klines_connections() {
+    let path = std::env::temp_dir().join("irc_klines_test.csv");
+    std::fs::write(&path, "*@10.0.0.*,abuso,\n").expect("");
+    let path = path.to_string_lossy().to_string();
+    let server = Arc::new(Mutex::new(common::setup()));
+    server
+        .lock()
+        .expect("")
+        .set_klines(Klines::load(path.clone()).expect(""));
+
+    let test_server = TestServer::bind("8123", server.clone(), HashMap::new());
+    let connect = || {
+        let user_stream = test_server.accept();
+        (
+            user_stream.try_clone().expect(""),
+            BufReader::new(user_stream),
+        )
+    };
+
+    let (mut oper, mut oper_reader) = connect();
+    oper.write_all(b"PASS hola\nNICK juan\nUSER juan juan\n")
+        .expect("");
+    read_until(&mut oper_reader, " 001 ");
+    oper.write_all(b"KLINE nico@127.0.0.1 :flood\n").expect("");
+    let buf = read_until(&mut oper_reader, " 481 ");
+    assert_eq!(
+        buf,
+        ":tests 481 juan :Permission Denied- You're not an IRC operator\n"
+    );
+    oper.write_all(b"OPER juan botter\nKLINE 5 nico@127.0.0.1 :flood\n")
+        .expect("");
+    let buf = read_until(&mut oper_reader, "NOTICE");
+    assert_eq!(buf, ":tests NOTICE juan :Added K-line for nico@127.0.0.1\n");
+    let klines = std::fs::read_to_string(&path).expect("");
+    assert!(klines.starts_with("*@10.0.0.*,abuso,\nnico@127.0.0.1,flood,"));
+
+    // el K-line de usuario se chequea al registrarse
+    let (mut user, mut user_reader) = connect();
+    user.write_all(b"PASS hola\nNICK nico\nUSER nico nico\n")
+        .expect("");
+    let buf = read_until(&mut user_reader, " 465 ");
+    assert_eq!(buf, ":tests 465 nico :You are banned from this server\n");
+    let buf = read_until(&mut user_reader, "ERROR");
+    assert_eq!(buf, "ERROR :Closing Link: 127.0.0.1 (K-lined: flood)\n");
+    assert!(!server.lock().expect("").contains_client("nico"));
+
+    // el K-line de host se chequea apenas se acepta la conexion
+    oper.write_all(b"KLINE 127.0.0.1 :todos\n").expect("");
+    read_until(&mut oper_reader, "NOTICE");
+    let (_user, mut user_reader) = connect();
+    let buf = read_until(&mut user_reader, " 465 ");
+    assert_eq!(buf, ":tests 465 * :You are banned from this server\n");
+    read_until(&mut user_reader, "ERROR");
+    let mut buf = String::new();
+    assert_eq!(user_reader.read_line(&mut buf).expect(""), 0);
+
+    oper.write_all(b"UNKLINE 127.0.0.1\nUNKLINE 127.0.0.1\n")
+        .expect("");
+    let buf = read_until(&mut oper_reader, "NOTICE");
+    assert_eq!(buf, ":tests NOTICE juan :Removed K-line for 127.0.0.1\n");
+    let buf = read_until(&mut oper_reader, "NOTICE");
+    assert_eq!(buf, ":tests NOTICE juan :No K-line for 127.0.0.1\n");
+    let klines = std::fs::read_to_string(&path).expect("");
+    assert!(!klines.contains("todos"));
+    assert!(klines.contains("nico@127.0.0.1,flood,"));
+}
+
+#[test]
+fn test_operator_is_told_when_klines_cant_be_saved() {
+    let path = std::env::temp_dir()
+        .join("irc_missing_dir")
+        .join("klines.csv");
+    let path = path.to_string_lossy().to_string();
+    let server = Arc::new(Mutex::new(common::setup()));
+    server
+        .lock()
+        .expect("")
+        .set_klines(Klines::load(path.clone()).expect(""));
+
+    let test_server = TestServer::bind("8146", server, HashMap::new());
+    let (mut oper, mut oper_reader) = test_server.connect(
+        b"PASS hola\nNICK juan\nUSER juan juan\nOPER juan botter\n",
+        " 381 ",
+    );
+    oper.write_all(b"KLINE nico@10.0.0.1 :flood\nUNKLINE nico@10.0.0.1\n")
+        .expect("");
+    let buf = read_until(&mut oper_reader, " 424 ");
+    assert_eq!(
+        buf,
+        format!(":tests 424 juan :File error doing write on {}\n", path)
+    );
+    read_until(&mut oper_reader, " 424 ");
+}
+
+#[test]
+fn test_flooding_user_is_dropped() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    server.lock().expect("").set_flood_limits(FloodLimits {
+        burst: 5,
+        rate: 1.0,
+        excess: 2,
+    });
+
+    let test_server = TestServer::bind("8124", server.clone(), HashMap::new());
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    for _ in 0..3 {
+        let user_stream = test_server.accept();
+        writers.push(user_stream.try_clone().expect(""));
+        readers.push(BufReader::new(user_stream));
+    }
+
+    writers[0]
+        .write_all(b"PASS hola\nNICK nico\nUSER nico nico\nJOIN #rust\n")
+        .expect("");
+    read_until(&mut readers[0], " 331 ");
+
+    // los operadores no se limitan
+    writers[1]
+        .write_all(b"PASS hola\nNICK juan\nUSER juan juan\nOPER juan botter\n")
+        .expect("");
+    read_until(&mut readers[1], " 381 ");
+    let flood = "PRIVMSG nico :hola\n".repeat(10);
+    writers[1].write_all(flood.as_bytes()).expect("");
+    for _ in 0..10 {
+        let buf = read_until(&mut readers[0], "PRIVMSG");
+        assert_eq!(buf, ":juan PRIVMSG nico :hola\n");
+    }
+
+    writers[2]
+        .write_all(b"PASS hola\nNICK pepe\nUSER pepe pepe\nJOIN #rust\n")
+        .expect("");
+    read_until(&mut readers[2], " 331 ");
+    let flood = "PRIVMSG #rust :spam\n".repeat(10);
+    writers[2].write_all(flood.as_bytes()).expect("");
+    let buf = read_until(&mut readers[2], "ERROR");
+    assert_eq!(buf, "ERROR :Closing Link: Excess Flood\n");
+    let buf = read_until(&mut readers[0], "QUIT");
+    assert_eq!(buf, ":pepe QUIT :Excess Flood\n");
+    assert!(!server.lock().expect("").contains_client("pepe"));
+}
+
+#[test]
+fn test_listeners_only_accept_their_kind_of_connection() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+
+    let clients_only =
+        Listener::bind(Listen::build("localhost:8130 clients").expect(""), None).expect("");
+    let servers_only =
+        Listener::bind(Listen::build("localhost:8131 servers").expect(""), None).expect("");
+    let connect = |listener: &Listener, port: &str, lines: &[u8]| {
+        let mut stream = TcpStream::connect(format!("localhost:{port}")).expect("");
+        let connection = Arc::new(Mutex::new(listener.accept().expect("")));
+        let mut client = ClientS::new(
+            server.clone(),
+            connection,
+            trusted_servers.clone(),
+            logger.clone(),
+        )
+        .expect("");
+        thread::spawn(move || client.run());
+        stream.write_all(lines).expect("");
+        let mut reader = BufReader::new(stream);
         let mut buf = String::new();
-        let mut reader = BufReader::new(client_stream);
         reader.read_line(&mut buf).expect("");
-        assert_eq!(buf, "juan nico: juan botter\n");
-    
+        buf
+    };
+
+    // un servidor no puede conectarse por el puerto de los usuarios
+    let buf = connect(
+        &clients_only,
+        "8130",
+        b"PASS hola\nSERVER server_dos 1 :info\n",
+    );
+    assert!(buf.contains(" 463 "));
+    // ni un usuario por el de los servidores
+    let buf = connect(
+        &servers_only,
+        "8131",
+        b"PASS hola\nNICK pepe\nUSER pepe pepe\n",
+    );
+    assert!(buf.contains(" 463 "));
+    let buf = connect(
+        &servers_only,
+        "8131",
+        b"PASS hola\nSERVER server_dos 1 :info\n",
+    );
+    assert!(buf.contains(" 001 "));
+}
+
+#[test]
+fn test_hashed_passwords_are_accepted() {
+    let mut opers = HashMap::new();
+    let operator = Operator::new("juan".to_string(), password::hash("botter").expect(""));
+    opers.insert("juan".to_string(), operator);
+    let server = Arc::new(Mutex::new(ClientsInfo::new(
+        "tests".to_string(),
+        Some("hola".to_string()),
+        opers,
+    )));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert(
+        "server_dos".to_string(),
+        Some(password::hash("enlace").expect("")),
+    );
+    trusted_servers.insert("server_tres".to_string(), None);
+
+    let test_server = TestServer::bind("8132", server, trusted_servers);
+
+    test_server.connect(
+        b"PASS hola\nNICK juan\nUSER juan juan\nOPER juan botter\n",
+        " 381 ",
+    );
+    // un servidor de confianza con contraseña propia se enlaza con esa y no con la del servidor
+    test_server.connect(b"PASS hola\nSERVER server_dos 1 :info\n", " 464 ");
+    test_server.connect(b"PASS enlace\nSERVER server_dos 1 :info\n", " 001 ");
+    // sin contraseña propia se usa la del servidor
+    test_server.connect(b"PASS enlace\nSERVER server_tres 1 :info\n", " 464 ");
+    test_server.connect(b"PASS hola\nSERVER server_tres 1 :info\n", " 001 ");
+}
+
+#[test]
+fn test_operator_blocks_restrict_hosts_and_privileges() {
+    let mut opers = HashMap::new();
+    let mut juan = Operator::new("juan".to_string(), "botter".to_string());
+    juan.hosts = vec!["*@127.0.0.1".to_string()];
+    juan.privileges = [Privilege::Kill].into();
+    opers.insert("juan".to_string(), juan);
+    let mut ana = Operator::new("ana".to_string(), "botter".to_string());
+    ana.hosts = vec!["*@10.0.0.*".to_string()];
+    opers.insert("ana".to_string(), ana);
+    let server = Arc::new(Mutex::new(ClientsInfo::new(
+        "tests".to_string(),
+        Some("hola".to_string()),
+        opers,
+    )));
+
+    let test_server = TestServer::bind("8133", server, HashMap::new());
+    let mut user = test_server.accept();
+    let mut reader = BufReader::new(user.try_clone().expect(""));
+
+    user.write_all(b"PASS hola\nNICK pepe\nUSER pepe pepe\nOPER ana botter\n")
+        .expect("");
+    read_until(&mut reader, " 491 ");
+    // el nombre del bloque no es el nick
+    user.write_all(b"OPER juan botter\n").expect("");
+    read_until(&mut reader, " 381 ");
+    user.write_all(b"KLINE nico@10.0.0.1 :no\n").expect("");
+    read_until(&mut reader, " 481 ");
+    user.write_all(b"SQUIT server_dos :no\n").expect("");
+    read_until(&mut reader, " 481 ");
+    user.write_all(b"KILL nico :no\n").expect("");
+    read_until(&mut reader, " 401 ");
+}
+
+#[test]
+fn test_lost_link_quits_the_users_and_servers_behind_it() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+    trusted_servers.insert("server_cuatro".to_string(), None);
+
+    let test_server = TestServer::bind("8138", server.clone(), trusted_servers);
+    let read_line = |reader: &mut BufReader<TcpStream>| {
+        let mut buf = String::new();
+        reader.read_line(&mut buf).expect("");
+        buf
+    };
+
+    let (_nico, mut nico_reader) = test_server.connect(
+        b"PASS hola\nNICK nico\nUSER nico nico\nJOIN #rust\n",
+        " 331 ",
+    );
+    let (_cuatro, mut cuatro_reader) =
+        test_server.connect(b"PASS hola\nSERVER server_cuatro 1 :info\n", " 001 ");
+    // pepe esta en server_tres, detras de server_dos
+    let (dos, _dos_reader) = test_server.connect(
+        b"PASS hola\nSERVER server_dos 1 :info\nSERVER server_tres 2 :info\nNICK pepe 2\n:pepe USER pepe pepe\n:pepe JOIN #rust\n:pepe JOIN #solo\n",
+        " 001 ",
+    );
+    read_until(&mut cuatro_reader, "JOIN #solo");
+    assert!(server.lock().expect("").contains_channel("#solo"));
+
+    dos.shutdown(std::net::Shutdown::Both).expect("");
+    let mut buf = read_line(&mut nico_reader);
+    while buf.contains("JOIN") {
+        buf = read_line(&mut nico_reader);
+    }
+    assert_eq!(buf, ":pepe QUIT :tests server_dos\n");
+    let mut relayed = vec![];
+    while relayed.len() < 3 {
+        let buf = read_line(&mut cuatro_reader);
+        if buf.contains("QUIT") || buf.contains("SQUIT") {
+            relayed.push(buf);
+        }
+    }
+    relayed.sort();
+    assert_eq!(
+        relayed,
+        vec![
+            ":pepe QUIT :tests server_dos\n",
+            ":tests SQUIT server_dos :tests server_dos\n",
+            ":tests SQUIT server_tres :tests server_dos\n",
+        ]
+    );
+    {
+        let mut server = server.lock().expect("");
+        assert!(!server.contains_client("pepe"));
+        assert!(!server.contains_channel("#solo"));
+        assert!(server.contains_channel("#rust"));
+    }
+    // server_dos puede volver a enlazarse
+    test_server.connect(b"PASS hola\nSERVER server_dos 1 :info\n", " 001 ");
+}
+
+#[test]
+fn test_linked_servers_resolve_nick_and_channel_collisions_by_age() {
+    let uno = Arc::new(Mutex::new(ClientsInfo::new(
+        "server_uno".to_string(),
+        Some("hola".to_string()),
+        HashMap::new(),
+    )));
+    let dos = Arc::new(Mutex::new(ClientsInfo::new(
+        "server_dos".to_string(),
+        Some("hola".to_string()),
+        HashMap::new(),
+    )));
+    let logger = Arc::new(Mutex::new(Logger::build("logs/log1.txt".to_string())));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_uno".to_string(), None);
+    trusted_servers.insert("server_dos".to_string(), None);
+
+    let uno_server = TestServer::bind("8139", uno.clone(), trusted_servers.clone());
+    let dos_server = TestServer::bind("8144", dos.clone(), trusted_servers.clone());
+
+    // pepe y #rust de server_uno son mas antiguos que los de server_dos
+    let (mut pepe_uno, mut pepe_uno_reader) = uno_server.connect(
+        b"PASS hola\nNICK pepe\nUSER pepe pepe\nJOIN #rust\n",
+        " 331 ",
+    );
+    thread::sleep(Duration::from_millis(1100));
+    let (_pepe_dos, mut pepe_dos_reader) = dos_server.connect(
+        b"PASS hola\nNICK pepe\nUSER pepe pepe\nJOIN #dos\n",
+        " 331 ",
+    );
+    let (mut juan, mut juan_reader) = dos_server.connect(
+        b"PASS hola\nNICK juan\nUSER juan juan\nJOIN #rust\n",
+        " 331 ",
+    );
+
+    // server_dos se enlaza a server_uno y cada uno recibe la rafaga del otro
+    let mut link = uno_server.accept();
+    link.write_all(b"PASS hola\nSERVER server_dos 1 :info\n")
+        .expect("");
+    let mut client = ClientS::new(
+        dos.clone(),
+        common::connection(link),
+        trusted_servers,
+        logger,
+    )
+    .expect("");
+    client
+        .set_parent("server_uno".to_string(), Some("hola".to_string()))
+        .expect("");
+    thread::spawn(move || client.run());
+
+    let buf = read_until(&mut pepe_dos_reader, "ERROR");
+    assert_eq!(
+        buf,
+        "ERROR :Closing Link: pepe (Killed (server_dos (Nick collision)))\n"
+    );
+    let buf = read_until(&mut juan_reader, "MODE");
+    assert_eq!(buf, ":server_dos MODE #rust -o juan\n");
+
+    // los dos servidores quedan con el mismo pepe, el de server_uno
+    juan.write_all(b"PRIVMSG pepe :hola\n").expect("");
+    let buf = read_until(&mut pepe_uno_reader, "PRIVMSG");
+    assert!(buf.starts_with(":juan PRIVMSG pepe :hola"));
+    pepe_uno.write_all(b"PRIVMSG juan :hola\n").expect("");
+    read_until(&mut juan_reader, "PRIVMSG");
+
+    // solo queda como operador de #rust el del canal mas antiguo
+    pepe_uno.write_all(b"NAMES #rust\n").expect("");
+    let buf = read_until(&mut pepe_uno_reader, "#rust:");
+    assert!(buf.contains("@pepe") && buf.contains(" juan") && !buf.contains("@juan"));
+    juan.write_all(b"NAMES #rust\n").expect("");
+    let buf = read_until(&mut juan_reader, "#rust:");
+    assert!(buf.contains("@pepe") && buf.contains(" juan") && !buf.contains("@juan"));
+    // el JOIN del pepe que perdio no llega a crear #dos en server_uno
+    assert!(!uno.lock().expect("").contains_channel("#dos"));
+    assert!(!dos.lock().expect("").contains_channel("#dos"));
+}
+
+#[test]
+fn test_links_and_map_show_the_servers_of_the_network() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+    trusted_servers.insert("server_cuatro".to_string(), None);
+
+    let test_server = TestServer::bind("8140", server.clone(), trusted_servers);
+    let read_replies = |reader: &mut BufReader<TcpStream>, end: &str| {
+        let mut lines = vec![];
+        loop {
+            let mut buf = String::new();
+            reader.read_line(&mut buf).expect("");
+            if buf.contains(end) {
+                return lines;
+            }
+            lines.push(buf);
+        }
+    };
+
+    // server_seis esta enlazado a server_cinco, sin prefijo server_tres se cuelga de server_dos
+    let (_dos, _dos_reader) = test_server.connect(
+        b"PASS hola\nSERVER server_dos 1 :dos\nSERVER server_tres 2 :tres\n:server_dos SERVER server_cinco 2 :cinco\n:server_cinco SERVER server_seis 3 :seis\n",
+        " 001 ",
+    );
+    let (_cuatro, _cuatro_reader) =
+        test_server.connect(b"PASS hola\nSERVER server_cuatro 1 :cuatro\n", " 001 ");
+    while !server.lock().expect("").is_known_server("server_seis") {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let (mut nico, mut nico_reader) =
+        test_server.connect(b"PASS hola\nNICK nico\nUSER nico nico\n", " 422 ");
+
+    nico.write_all(b"LINKS\n").expect("");
+    let links = read_replies(&mut nico_reader, " 365 ");
+    assert_eq!(links.len(), 6);
+    assert!(links[0].starts_with(":tests 364 nico tests tests :0 "));
+    assert_eq!(
+        links[1..],
+        [
+            ":tests 364 nico server_cuatro tests :1 cuatro\n",
+            ":tests 364 nico server_dos tests :1 dos\n",
+            ":tests 364 nico server_cinco server_dos :2 cinco\n",
+            ":tests 364 nico server_tres server_dos :2 tres\n",
+            ":tests 364 nico server_seis server_cinco :3 seis\n",
+        ]
+    );
+    nico.write_all(b"LINKS server_t*\n").expect("");
+    let links = read_replies(&mut nico_reader, " 365 nico server_t* ");
+    assert_eq!(
+        links,
+        vec![":tests 364 nico server_tres server_dos :2 tres\n"]
+    );
+
+    // solo los operadores ven el mapa
+    nico.write_all(b"MAP\n").expect("");
+    let mut buf = String::new();
+    nico_reader.read_line(&mut buf).expect("");
+    assert!(buf.contains(" 481 "));
+    nico.write_all(b"OPER juan botter\n").expect("");
+    read_replies(&mut nico_reader, " 381 ");
+    nico.write_all(b"MAP\n").expect("");
+    let map = read_replies(&mut nico_reader, " 017 ");
+    assert!(map[0].starts_with(":tests 015 nico :tests [0] "));
+    assert_eq!(
+        map[1..],
+        [
+            ":tests 015 nico :`- server_cuatro [1] cuatro\n",
+            ":tests 015 nico :`- server_dos [1] dos\n",
+            ":tests 015 nico :  `- server_cinco [2] cinco\n",
+            ":tests 015 nico :    `- server_seis [3] seis\n",
+            ":tests 015 nico :  `- server_tres [2] tres\n",
+        ]
+    );
+}
+
+#[test]
+fn test_queries_for_other_servers_are_routed_and_answered() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+
+    let test_server = TestServer::bind("8141", server.clone(), trusted_servers);
+
+    // server_dos tiene a pepe y llega a server_tres
+    let (mut dos, mut dos_reader) = test_server.connect(
+        b"PASS hola\nSERVER server_dos 1 :dos\nSERVER server_tres 2 :tres\nNICK pepe 2\n:pepe USER pepe pepe\n",
+        " 001 ",
+    );
+    while !server.lock().expect("").contains_client("pepe") {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let (mut nico, mut nico_reader) =
+        test_server.connect(b"PASS hola\nNICK nico\nUSER nico nico\n", " 422 ");
+
+    // las consultas sin servidor las responde este
+    nico.write_all(b"TIME\n").expect("");
+    let line = read_until(&mut nico_reader, " 391 ");
+    assert!(line.starts_with(":tests 391 nico tests :"));
+    nico.write_all(b"ADMIN server_cinco\n").expect("");
+    let line = read_until(&mut nico_reader, " 402 ");
+    assert_eq!(line, ":tests 402 nico server_cinco :No such server\n");
+
+    // las de otro servidor viajan por el enlace y su respuesta vuelve al usuario
+    nico.write_all(b"VERSION server_tres\n").expect("");
+    read_until(&mut dos_reader, ":nico VERSION server_tres");
+    dos.write_all(b":server_tres 351 nico irc-1.0 server_tres :tres\n")
+        .expect("");
+    let line = read_until(&mut nico_reader, " 351 ");
+    assert_eq!(line, ":server_tres 351 nico irc-1.0 server_tres :tres\n");
+    nico.write_all(b"WHOIS server_dos pepe\n").expect("");
+    read_until(&mut dos_reader, ":nico WHOIS server_dos pepe");
+
+    // las que llegan por el enlace se responden al usuario del otro servidor
+    dos.write_all(b":pepe WHOIS tests nico\n:pepe ADMIN tests\n")
+        .expect("");
+    let line = read_until(&mut dos_reader, " 311 ");
+    assert!(line.starts_with(":tests 311 pepe nico nico "));
+    assert!(line.ends_with(" * :nico\n"));
+    let line = read_until(&mut dos_reader, " 312 ");
+    assert!(line.starts_with(":tests 312 pepe nico tests :"));
+    read_until(&mut dos_reader, ":tests 318 pepe nico :End of /WHOIS list");
+    let line = read_until(&mut dos_reader, " 423 ");
+    assert_eq!(
+        line,
+        ":tests 423 pepe tests :No administrative info available\n"
+    );
+
+    // WHOIS sin servidor lo responde este, aunque el usuario sea de otro servidor
+    nico.write_all(b"WHOIS pepe\n").expect("");
+    let line = read_until(&mut nico_reader, " 311 ");
+    assert!(line.starts_with(":tests 311 nico pepe pepe "));
+    let line = read_until(&mut nico_reader, " 312 ");
+    assert_eq!(line, ":tests 312 nico pepe server_dos :dos\n");
+    read_until(&mut nico_reader, " 318 ");
+
+    // una respuesta para un usuario del mismo enlace no se devuelve por el
+    dos.write_all(b":server_tres 351 pepe irc-1.0 server_tres :tres\n:pepe TIME\n")
+        .expect("");
+    let mut line = String::new();
+    dos_reader.read_line(&mut line).expect("");
+    assert!(line.starts_with(":tests 391 pepe tests :"));
+
+    // los usuarios no pueden enviar respuestas numericas
+    nico.write_all(b"391 pepe tests :ahora\n").expect("");
+    read_until(&mut nico_reader, ":tests 421 nico 391 :Unknown command");
+}
+
+#[test]
+fn test_links_keep_channel_keys_and_kill_both_users_on_equal_nick_times() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+
+    let test_server = TestServer::bind("8143", server.clone(), trusted_servers);
+
+    // '*' es una clave valida de #clave, #libre no tiene
+    let (_pepe, mut pepe_reader) =
+        test_server.connect(b"PASS hola\nNICK pepe\nUSER pepe pepe\n", " 422 ");
+    let _juan = test_server.connect(
+        b"PASS hola\nNICK juan\nUSER juan juan\nJOIN #libre\nJOIN #clave *\n",
+        " 331 juan #clave ",
+    );
+
+    let (mut dos, mut dos_reader) =
+        test_server.connect(b"PASS hola\nSERVER server_dos 1 :dos\n", " 001 ");
+    let line = read_until(&mut dos_reader, " NICK pepe ");
+    let nick_time = line.trim_end().rsplit(' ').next().expect("").to_string();
+    let mut joins = [
+        read_until(&mut dos_reader, ":juan JOIN #"),
+        read_until(&mut dos_reader, ":juan JOIN #"),
+    ];
+    joins.sort();
+    assert!(joins[0].starts_with(":juan JOIN #clave * "));
+    assert!(joins[1].starts_with(":juan JOIN #libre , "));
+
+    // con el mismo momento ninguno de los dos pepe es mas antiguo, se desconectan ambos
+    dos.write_all(format!("NICK pepe 2 {}\n:pepe USER pepe pepe\n", nick_time).as_bytes())
+        .expect("");
+    let line = read_until(&mut pepe_reader, "ERROR");
+    assert_eq!(
+        line,
+        "ERROR :Closing Link: pepe (Killed (tests (Nick collision)))\n"
+    );
+
+    // los canales que llegan por el enlace conservan su clave, aunque sea '*'
+    dos.write_all(
+        b"NICK ana 2\n:ana USER ana ana\n:ana JOIN #nuevo , 1666000000\n:ana JOIN #cerrado * 1666000000\n",
+    )
+    .expect("");
+    while !server.lock().expect("").contains_channel("#cerrado") {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let (_nuevo_pepe, mut nuevo_pepe_reader) = test_server.connect(
+        b"PASS hola\nNICK pepe\nUSER pepe pepe\nJOIN #nuevo\nJOIN #cerrado\n",
+        " 422 ",
+    );
+    read_until(&mut nuevo_pepe_reader, " 331 pepe #nuevo ");
+    read_until(&mut nuevo_pepe_reader, " 475 pepe #cerrado ");
+}
+
+#[test]
+fn test_renames_over_links_keep_the_nick_time_and_resolve_collisions_by_age() {
+    let server = Arc::new(Mutex::new(common::setup()));
+    let mut trusted_servers = HashMap::new();
+    trusted_servers.insert("server_dos".to_string(), None);
+
+    let test_server = TestServer::bind("8145", server.clone(), trusted_servers);
+    let (mut nico, mut nico_reader) =
+        test_server.connect(b"PASS hola\nNICK nico\nUSER nico nico\n", " 422 ");
+    let (_pepe, mut pepe_reader) =
+        test_server.connect(b"PASS hola\nNICK pepe\nUSER pepe pepe\n", " 422 ");
+    let (mut dos, mut dos_reader) =
+        test_server.connect(b"PASS hola\nSERVER server_dos 1 :dos\n", " 001 ");
+    read_until(&mut dos_reader, " NICK pepe ");
+
+    // el cambio de nick viaja con el momento en que se tomo el nuevo
+    nico.write_all(b"NICK nicolas\n").expect("");
+    read_until(&mut nico_reader, ":nico NICK nicolas");
+    let line = read_until(&mut dos_reader, ":nico NICK nicolas ");
+    let nick_time = line.trim_end().strip_prefix(":nico NICK nicolas 1 ");
+    assert!(nick_time.expect("").parse::<u64>().is_ok());
+
+    // ana toma el nick de pepe con un momento anterior, pierde el pepe de este servidor
+    dos.write_all(b"NICK ana 2 1666000000\n:ana USER ana ana\n:ana NICK pepe 2 1666000000\n")
+        .expect("");
+    let line = read_until(&mut pepe_reader, "ERROR");
+    assert_eq!(
+        line,
+        "ERROR :Closing Link: pepe (Killed (tests (Nick collision)))\n"
+    );
+
+    // juan toma el de nicolas con un momento posterior y pierde, sin respuesta al enlace
+    dos.write_all(b"NICK juan 2 1666000000\n:juan USER juan juan\n:juan NICK nicolas 2 4000000000\nPING :server_dos\n")
+        .expect("");
+    loop {
+        let line = read_until(&mut dos_reader, "");
+        assert!(!line.contains(" 436 ") && !line.contains("KILL"));
+        if line.contains("PONG") {
+            break;
+        }
+    }
+    let mut server = server.lock().expect("");
+    assert!(server.contains_client("pepe") && !server.contains_client("ana"));
+    assert!(server.contains_client("nicolas") && !server.contains_client("juan"));
 }
@@ -0,0 +1,79 @@
+// Links between servers opened from the connect blocks of the config:
+// by an operator with CONNECT and on their own with autoconnect, again after the link is lost.
+mod common;
+use std::{
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use common::{accept_within, config, read_until, register, start};
+use irc_2c_2022::server::link::ConnectBlock;
+
+#[test]
+fn test_autoconnect_links_again_after_losing_the_link() {
+    // server_uno es el test, que corta el primer enlace
+    let uno = TcpListener::bind("localhost:8134").expect("");
+    let mut dos = config("server_dos", "2222", "8135");
+    dos.connect_blocks =
+        vec![
+            ConnectBlock::build("server_uno localhost:8134 password=2222 autoconnect=1").expect(""),
+        ];
+    start(dos);
+
+    let mut link = accept_within(&uno, Duration::from_secs(5));
+    let mut reader = BufReader::new(link.try_clone().expect(""));
+    read_until(&mut reader, "PASS 2222");
+    read_until(&mut reader, "SERVER server_dos 1");
+    link.write_all(b":server_uno 001 server_dos\n").expect("");
+    drop(reader);
+    drop(link);
+
+    // despues del intervalo se vuelve a enlazar, ya sin server_uno en la red
+    let mut link = accept_within(&uno, Duration::from_secs(5));
+    let mut reader = BufReader::new(link.try_clone().expect(""));
+    read_until(&mut reader, "SERVER server_dos 1");
+    link.write_all(b":server_uno 001 server_dos\nPING :server_uno\n")
+        .expect("");
+    read_until(&mut reader, "PONG server_dos :server_uno");
+}
+
+#[test]
+fn test_operators_link_servers_with_connect() {
+    start(config("server_uno", "1111", "8136"));
+    let mut tres = config("server_tres", "3333", "8137");
+    tres.connect_blocks =
+        vec![ConnectBlock::build("server_uno localhost:8136 password=3333").expect("")];
+    start(tres);
+
+    let (mut nico, mut nico_reader) = register("8137", "3333", "nico");
+    nico.write_all(b"CONNECT server_uno\n").expect("");
+    read_until(&mut nico_reader, " 481 ");
+    nico.write_all(b"OPER nico 123\n").expect("");
+    read_until(&mut nico_reader, " 381 ");
+    nico.write_all(b"CONNECT server_cinco\n").expect("");
+    read_until(&mut nico_reader, " 402 ");
+    nico.write_all(b"CONNECT server_uno\n").expect("");
+    read_until(&mut nico_reader, "Linked to server_uno");
+    nico.write_all(b"CONNECT server_uno\n").expect("");
+    read_until(&mut nico_reader, "Server server_uno already exists");
+
+    // nico llego a server_uno en la rafaga del enlace
+    let (mut pepe, _pepe_reader) = register("8136", "1111", "pepe");
+    pepe.write_all(b"PRIVMSG nico :hola\n").expect("");
+    let line = read_until(&mut nico_reader, "PRIVMSG");
+    assert!(line.starts_with(":pepe PRIVMSG nico :hola"));
+
+    // otro camino a server_tres formaria un ciclo
+    let mut fake = TcpStream::connect("localhost:8136").expect("");
+    fake.set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("");
+    let mut fake_reader = BufReader::new(fake.try_clone().expect(""));
+    fake.write_all(b"PASS 3333\nSERVER server_tres 1 info\n")
+        .expect("");
+    read_until(&mut fake_reader, " 462 ");
+    read_until(
+        &mut fake_reader,
+        "ERROR :Closing Link: Server server_tres already exists",
+    );
+}
@@ -0,0 +1,88 @@
+// Servers with a TLS listener: users register over TLS and a server links to its parent over TLS.
+// The certificate is self-signed and generated when the test runs.
+mod common;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use common::{config, read_until, start};
+use irc_2c_2022::server::{
+    listener::Listen,
+    tls::{self, Stream},
+};
+
+/// Writes a self-signed certificate for localhost and its key to the temp dir, returns their paths
+fn self_signed(name: &str) -> (String, String) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).expect("");
+    let dir = std::env::temp_dir();
+    let cert_path = dir.join(format!("{name}_cert.pem"));
+    let key_path = dir.join(format!("{name}_key.pem"));
+    std::fs::write(&cert_path, cert.cert.pem()).expect("");
+    std::fs::write(&key_path, cert.key_pair.serialize_pem()).expect("");
+    (
+        cert_path.to_string_lossy().to_string(),
+        key_path.to_string_lossy().to_string(),
+    )
+}
+
+#[test]
+fn test_users_and_servers_connect_over_tls() {
+    let (cert_path, key_path) = self_signed("tls_connections_test");
+
+    // server_uno escucha en texto plano y en TLS
+    let mut uno = config("server_uno", "1111", "8127");
+    let mut tls_listen = Listen::new("localhost".to_string(), "8128".to_string());
+    tls_listen.tls = true;
+    uno.listeners.push(tls_listen);
+    uno.tls_cert_path = Some(cert_path.clone());
+    uno.tls_key_path = Some(key_path);
+    start(uno);
+
+    // server_dos se registra en server_uno por el puerto TLS
+    let mut dos = config("server_dos", "2222", "8129");
+    dos.parent_name = Some("server_uno".to_string());
+    dos.parent_ip = Some("localhost".to_string());
+    dos.parent_port = Some("8128".to_string());
+    dos.parent_pwd = Some("2222".to_string());
+    dos.parent_tls = true;
+    dos.tls_ca_path = Some(cert_path.clone());
+    start(dos);
+
+    let tls_config = tls::client_config(Some(&cert_path)).expect("");
+    let mut nico = Stream::connect("localhost", "8128", tls_config).expect("");
+    nico.set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("");
+    let mut nico_reader = BufReader::new(nico.try_clone().expect(""));
+    nico.write_all(b"PASS 1111\nNICK nico\nUSER nico nico nico :Nicolas\n")
+        .expect("");
+    read_until(&mut nico_reader, " 001 ");
+
+    let mut pepe = TcpStream::connect("localhost:8129").expect("");
+    pepe.set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("");
+    let mut pepe_reader = BufReader::new(pepe.try_clone().expect(""));
+    pepe.write_all(b"PASS 2222\nNICK pepe\nUSER pepe pepe pepe :Pedro\n")
+        .expect("");
+    read_until(&mut pepe_reader, " 001 ");
+    thread::sleep(Duration::from_millis(200));
+
+    // el mensaje viaja de server_dos a server_uno por el enlace TLS
+    pepe.write_all(b"PRIVMSG nico :hola por tls\n").expect("");
+    let line = read_until(&mut nico_reader, "PRIVMSG");
+    assert!(line.starts_with(":pepe PRIVMSG nico :hola por tls"));
+
+    // el puerto TLS no acepta texto plano
+    let mut plain = TcpStream::connect("localhost:8128").expect("");
+    plain
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("");
+    plain
+        .write_all(b"PASS 1111\nNICK plano\nUSER plano plano plano :Plano\n")
+        .expect("");
+    let mut line = String::new();
+    let read = BufReader::new(plain).read_line(&mut line);
+    assert!(!line.contains(" 001 "), "{read:?}");
+}